@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE capabilities DROP COLUMN IF EXISTS validation_confidence;
@@ -0,0 +1,3 @@
+-- Confidence in validated_level produced by the validation scoring policy
+
+ALTER TABLE capabilities ADD COLUMN IF NOT EXISTS validation_confidence REAL;
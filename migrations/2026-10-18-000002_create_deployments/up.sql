@@ -0,0 +1,23 @@
+-- Records a person being pulled onto an emergency response
+
+CREATE TABLE IF NOT EXISTS deployments (
+    id UUID DEFAULT gen_random_uuid() PRIMARY KEY,
+
+    person_id UUID NOT NULL,
+    FOREIGN KEY(person_id)
+        REFERENCES persons(id) ON DELETE RESTRICT,
+
+    role_id UUID,
+    FOREIGN KEY(role_id)
+        REFERENCES roles(id) ON DELETE RESTRICT,
+
+    event_name VARCHAR(256) NOT NULL,
+    effort FLOAT NOT NULL,
+
+    start_datestamp TIMESTAMP NOT NULL,
+    end_date TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS deployments_person_id_idx ON deployments (person_id);
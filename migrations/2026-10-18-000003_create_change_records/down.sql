@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE IF EXISTS change_records;
+DROP TYPE IF EXISTS change_action;
@@ -0,0 +1,20 @@
+-- Append-only history of every change made through a mutation
+
+CREATE TYPE change_action AS ENUM ('create', 'update', 'delete');
+
+CREATE TABLE IF NOT EXISTS change_records (
+    id UUID DEFAULT gen_random_uuid() PRIMARY KEY,
+    entity_type VARCHAR(64) NOT NULL,
+    entity_id UUID NOT NULL,
+    action change_action NOT NULL,
+    -- {"field": {"before": .., "after": ..}} for each changed field
+    changes JSONB NOT NULL,
+
+    actor_id UUID,
+    FOREIGN KEY(actor_id)
+        REFERENCES users(id) ON DELETE SET NULL,
+
+    changed_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS change_records_entity_id_idx ON change_records (entity_id, changed_at);
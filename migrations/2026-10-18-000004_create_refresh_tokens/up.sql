@@ -0,0 +1,41 @@
+-- Rotating refresh tokens and a denylist for revoked access tokens
+
+CREATE TABLE IF NOT EXISTS refresh_tokens (
+    id UUID DEFAULT gen_random_uuid() PRIMARY KEY,
+
+    user_id UUID NOT NULL,
+    FOREIGN KEY(user_id)
+        REFERENCES users(id) ON DELETE CASCADE,
+
+    -- SHA-256 of the opaque token, the token itself is never stored
+    token_hash VARCHAR(64) UNIQUE NOT NULL,
+    -- Shared by every token rotated from the same sign in
+    family_id UUID NOT NULL,
+    replaced_by UUID,
+
+    expires_at TIMESTAMP NOT NULL,
+    revoked_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS refresh_tokens_family_id_idx ON refresh_tokens (family_id);
+CREATE INDEX IF NOT EXISTS refresh_tokens_user_id_idx ON refresh_tokens (user_id);
+
+-- A row with a jti denies that access token.
+-- A row without a jti denies every access token for user_id issued before issued_before.
+CREATE TABLE IF NOT EXISTS revoked_tokens (
+    id UUID DEFAULT gen_random_uuid() PRIMARY KEY,
+    jti UUID,
+
+    user_id UUID NOT NULL,
+    FOREIGN KEY(user_id)
+        REFERENCES users(id) ON DELETE CASCADE,
+
+    issued_before TIMESTAMP,
+    -- Once every denied token has expired the row can be removed
+    expires_at TIMESTAMP NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS revoked_tokens_jti_idx ON revoked_tokens (jti);
+CREATE INDEX IF NOT EXISTS revoked_tokens_user_id_idx ON revoked_tokens (user_id);
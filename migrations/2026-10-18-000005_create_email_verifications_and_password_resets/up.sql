@@ -0,0 +1,35 @@
+-- Email verification and password reset tokens, and when a user's email was verified
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS email_verified_at TIMESTAMP;
+
+-- One outstanding verification per user, requesting another replaces it
+CREATE TABLE IF NOT EXISTS email_verifications (
+    id UUID DEFAULT gen_random_uuid() PRIMARY KEY,
+
+    user_id UUID UNIQUE NOT NULL,
+    FOREIGN KEY(user_id)
+        REFERENCES users(id) ON DELETE CASCADE,
+
+    -- The address being verified, a verification is void if the user's email changes
+    email_address VARCHAR(128) NOT NULL,
+    -- SHA-256 of the code sent by email, the code itself is never stored
+    code_hash VARCHAR(64) UNIQUE NOT NULL,
+
+    expires_on TIMESTAMP NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+-- One outstanding reset per user, requesting another replaces it
+CREATE TABLE IF NOT EXISTS password_reset_tokens (
+    id UUID DEFAULT gen_random_uuid() PRIMARY KEY,
+
+    user_id UUID UNIQUE NOT NULL,
+    FOREIGN KEY(user_id)
+        REFERENCES users(id) ON DELETE CASCADE,
+
+    -- SHA-256 of the token sent by email
+    token_hash VARCHAR(64) UNIQUE NOT NULL,
+
+    expires_on TIMESTAMP NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
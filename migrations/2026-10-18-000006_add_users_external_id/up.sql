@@ -0,0 +1,3 @@
+-- Links a user to an identity at the OIDC provider, stored as "<issuer>|<subject>"
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS external_id VARCHAR(256) UNIQUE;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE IF EXISTS api_keys;
+DROP TYPE IF EXISTS api_scope;
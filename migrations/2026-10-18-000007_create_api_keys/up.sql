@@ -0,0 +1,31 @@
+-- Scoped, expiring API keys for integration accounts
+
+CREATE TYPE api_scope AS ENUM ('read', 'manage_people', 'manage_capabilities', 'manage_organization', 'manage_deployments');
+
+CREATE TABLE IF NOT EXISTS api_keys (
+    id UUID DEFAULT gen_random_uuid() PRIMARY KEY,
+
+    -- The integration account the key acts as
+    user_id UUID NOT NULL,
+    FOREIGN KEY(user_id)
+        REFERENCES users(id) ON DELETE CASCADE,
+
+    name VARCHAR(128) NOT NULL,
+    -- First characters of the key, to tell keys apart without storing them
+    key_prefix VARCHAR(16) NOT NULL,
+    -- SHA-256 of the key, the key itself is never stored
+    key_hash VARCHAR(64) UNIQUE NOT NULL,
+    scopes api_scope[] NOT NULL,
+
+    expires_at TIMESTAMP NOT NULL,
+    last_used_at TIMESTAMP,
+    revoked_at TIMESTAMP,
+
+    created_by UUID,
+    FOREIGN KEY(created_by)
+        REFERENCES users(id) ON DELETE SET NULL,
+
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS api_keys_user_id_idx ON api_keys (user_id);
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+DROP INDEX IF EXISTS users_pending_approval_idx;
+
+ALTER TABLE users DROP COLUMN IF EXISTS approved_at;
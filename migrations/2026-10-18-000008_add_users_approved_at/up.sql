@@ -0,0 +1,8 @@
+-- Self-registered users wait for an Admin to approve them before they can sign in
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS approved_at TIMESTAMP;
+
+-- Users created before self-registration were added by an Admin
+UPDATE users SET approved_at = created_at WHERE approved_at IS NULL;
+
+CREATE INDEX IF NOT EXISTS users_pending_approval_idx ON users (created_at) WHERE approved_at IS NULL;
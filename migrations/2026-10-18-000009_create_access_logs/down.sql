@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE IF EXISTS access_logs;
+
+DROP TYPE IF EXISTS granularity;
+
+DROP TYPE IF EXISTS access_rationale;
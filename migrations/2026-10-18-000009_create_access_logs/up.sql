@@ -0,0 +1,30 @@
+-- One row per request that read identifiable personal data, with the rationale given for it
+
+CREATE TYPE access_rationale AS ENUM ('automated', 'administrative', 'research', 'public_health');
+
+CREATE TYPE granularity AS ENUM ('aggregated', 'anonymized', 'identifiable');
+
+CREATE TABLE IF NOT EXISTS access_logs (
+    id UUID DEFAULT gen_random_uuid() PRIMARY KEY,
+
+    user_id UUID,
+    FOREIGN KEY(user_id)
+        REFERENCES users(id) ON DELETE SET NULL,
+
+    api_key_id UUID,
+    FOREIGN KEY(api_key_id)
+        REFERENCES api_keys(id) ON DELETE SET NULL,
+
+    rationale access_rationale NOT NULL,
+    granularity granularity NOT NULL,
+    operation_name VARCHAR(128),
+    -- Fields read, e.g. {"email", "languageData"}
+    fields TEXT[] NOT NULL,
+    -- Persons whose data was read
+    person_ids UUID[] NOT NULL,
+
+    accessed_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS access_logs_user_id_idx ON access_logs (user_id, accessed_at);
+CREATE INDEX IF NOT EXISTS access_logs_person_ids_idx ON access_logs USING GIN (person_ids);
@@ -0,0 +1,4 @@
+-- The most detailed view of personal data each user is granted.
+-- Existing users keep the identifiable access they had.
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS granularity granularity NOT NULL DEFAULT 'identifiable';
@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE IF EXISTS hr_state_periods;
+
+DROP TABLE IF EXISTS employee_datas;
+
+DROP TYPE IF EXISTS hr_state;
+
+DROP TYPE IF EXISTS classification_type;
@@ -0,0 +1,49 @@
+-- A person's substantive and acting classifications and their HR state over time
+
+CREATE TYPE classification_type AS ENUM ('substantive', 'acting');
+
+CREATE TYPE hr_state AS ENUM ('active', 'leave', 'secondment', 'departed');
+
+CREATE TABLE IF NOT EXISTS employee_datas (
+    id UUID DEFAULT gen_random_uuid() PRIMARY KEY,
+
+    person_id UUID NOT NULL,
+    FOREIGN KEY(person_id)
+        REFERENCES persons(id) ON DELETE RESTRICT,
+
+    title_en VARCHAR(256) NOT NULL,
+    title_fr VARCHAR(256) NOT NULL,
+    hr_group hr_group NOT NULL,
+    hr_level INT NOT NULL,
+    classification classification_type NOT NULL,
+
+    start_datestamp TIMESTAMP NOT NULL,
+    end_date TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS employee_datas_person_id_idx ON employee_datas (person_id);
+
+-- A person holds at most one open substantive and one open acting classification
+CREATE UNIQUE INDEX IF NOT EXISTS employee_datas_open_classification_idx
+    ON employee_datas (person_id, classification) WHERE end_date IS NULL;
+
+CREATE TABLE IF NOT EXISTS hr_state_periods (
+    id UUID DEFAULT gen_random_uuid() PRIMARY KEY,
+
+    person_id UUID NOT NULL,
+    FOREIGN KEY(person_id)
+        REFERENCES persons(id) ON DELETE RESTRICT,
+
+    hr_state hr_state NOT NULL,
+
+    start_datestamp TIMESTAMP NOT NULL,
+    end_date TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+-- Only the current state is open
+CREATE UNIQUE INDEX IF NOT EXISTS hr_state_periods_open_idx
+    ON hr_state_periods (person_id) WHERE end_date IS NULL;
@@ -0,0 +1,64 @@
+-- This file should undo anything in `up.sql`
+
+-- Lossy: persons only hold one set of details, so contacts other than each person's
+-- current primary (or most recent) one are dropped, along with contact types and dates.
+-- Missing or duplicate emails and phones are replaced with placeholders derived from
+-- the person's id so both can be unique again. Contact details redacted in person
+-- history stay redacted.
+
+ALTER TABLE persons
+    ADD COLUMN email VARCHAR(128),
+    ADD COLUMN phone VARCHAR(32),
+    ADD COLUMN work_address VARCHAR(256),
+    ADD COLUMN city VARCHAR(128),
+    ADD COLUMN province VARCHAR(128),
+    ADD COLUMN postal_code VARCHAR(16),
+    ADD COLUMN country VARCHAR(128);
+
+-- Restore each person's current primary contact, or their most recent one
+UPDATE persons SET
+    email = c.email,
+    phone = c.phone,
+    work_address = c.work_address,
+    city = c.city,
+    province = c.province,
+    postal_code = c.postal_code,
+    country = c.country
+FROM (
+    SELECT DISTINCT ON (person_id) *
+    FROM contact_datas
+    ORDER BY person_id, (is_primary AND end_date IS NULL) DESC, start_datestamp DESC
+) c
+WHERE c.person_id = persons.id;
+
+-- Contacts could share an email or phone, or have neither. The first person by id keeps a
+-- shared value and the rest get the id as a placeholder (without hyphens to fit a phone).
+UPDATE persons p SET email = p.id::text
+WHERE p.email IS NULL
+    OR EXISTS (SELECT 1 FROM persons o WHERE o.email = p.email AND o.id < p.id);
+
+UPDATE persons p SET phone = replace(p.id::text, '-', '')
+WHERE p.phone IS NULL
+    OR EXISTS (SELECT 1 FROM persons o WHERE o.phone = p.phone AND o.id < p.id);
+
+UPDATE persons SET
+    work_address = COALESCE(work_address, ''),
+    city = COALESCE(city, ''),
+    province = COALESCE(province, ''),
+    postal_code = COALESCE(postal_code, ''),
+    country = COALESCE(country, '');
+
+ALTER TABLE persons
+    ALTER COLUMN email SET NOT NULL,
+    ALTER COLUMN phone SET NOT NULL,
+    ALTER COLUMN work_address SET NOT NULL,
+    ALTER COLUMN city SET NOT NULL,
+    ALTER COLUMN province SET NOT NULL,
+    ALTER COLUMN postal_code SET NOT NULL,
+    ALTER COLUMN country SET NOT NULL,
+    ADD CONSTRAINT persons_email_key UNIQUE (email),
+    ADD CONSTRAINT persons_phone_key UNIQUE (phone);
+
+DROP TABLE IF EXISTS contact_datas;
+
+DROP TYPE IF EXISTS contact_type;
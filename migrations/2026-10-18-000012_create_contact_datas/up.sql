@@ -0,0 +1,63 @@
+-- Moves contact details off persons into typed contacts with validity periods
+
+CREATE TYPE contact_type AS ENUM ('work', 'mobile', 'alternate');
+
+CREATE TABLE IF NOT EXISTS contact_datas (
+    id UUID DEFAULT gen_random_uuid() PRIMARY KEY,
+
+    person_id UUID NOT NULL,
+    FOREIGN KEY(person_id)
+        REFERENCES persons(id) ON DELETE RESTRICT,
+
+    contact_type contact_type NOT NULL,
+
+    email VARCHAR(128),
+    phone VARCHAR(32),
+    work_address VARCHAR(256),
+    city VARCHAR(128),
+    province VARCHAR(128),
+    postal_code VARCHAR(16),
+    country VARCHAR(128),
+
+    is_primary BOOLEAN NOT NULL DEFAULT FALSE,
+
+    start_datestamp TIMESTAMP NOT NULL,
+    end_date TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS contact_datas_person_id_idx ON contact_datas (person_id);
+
+-- A person has at most one current primary contact
+CREATE UNIQUE INDEX IF NOT EXISTS contact_datas_open_primary_idx
+    ON contact_datas (person_id) WHERE is_primary AND end_date IS NULL;
+
+-- Existing details become each person's primary work contact
+INSERT INTO contact_datas (person_id, contact_type, email, phone, work_address, city, province,
+    postal_code, country, is_primary, start_datestamp)
+SELECT id, 'work', NULLIF(email, ''), NULLIF(phone, ''), NULLIF(work_address, ''), NULLIF(city, ''),
+    NULLIF(province, ''), NULLIF(postal_code, ''), NULLIF(country, ''), TRUE, created_at
+FROM persons;
+
+ALTER TABLE persons
+    DROP COLUMN email,
+    DROP COLUMN phone,
+    DROP COLUMN work_address,
+    DROP COLUMN city,
+    DROP COLUMN province,
+    DROP COLUMN postal_code,
+    DROP COLUMN country;
+
+-- Contact details in person history are redacted as they are in contact history
+UPDATE change_records
+SET changes = (
+    SELECT jsonb_object_agg(
+        field,
+        CASE WHEN field IN ('email', 'phone', 'work_address', 'city', 'province', 'postal_code', 'country')
+            THEN '{"before": "[redacted]", "after": "[redacted]"}'::jsonb
+            ELSE change
+        END)
+    FROM jsonb_each(changes) AS c(field, change)
+)
+WHERE entity_type = 'person' AND changes <> '{}'::jsonb;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+-- The dropped values can't be restored, so the column comes back empty
+ALTER TABLE capabilities ADD COLUMN validation_values BIGINT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,4 @@
+-- Drops validation_values, which was written on every validation but never read.
+-- Validated levels are scored from the validations table.
+
+ALTER TABLE capabilities DROP COLUMN validation_values;
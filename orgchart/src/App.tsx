@@ -3,7 +3,7 @@
 
 // https://codesandbox.io/s/corporate-hierarchy-1pbs0s used as a starting point.
 
-import React, { useCallback, useMemo } from "react";
+import React, { useCallback, useEffect, useMemo } from "react";
 import { Tree, TreeNode, TreeProps } from "react-organizational-chart";
 import _ from "lodash";
 import clsx from "clsx";
@@ -30,21 +30,30 @@ import { useDrag, useDrop } from "react-dnd";
 
 import { useQuery, gql } from "@apollo/client";
 
+// Pages are capped at MAX_PAGE_SIZE on the server, so the tiers are fetched in pages of that size
+const PAGE_SIZE = 500;
+
 const GET_PEOPLE = gql`
-  query {
-    allOrgTiers {
-      id
-      nameEn
-      tierLevel
-      owner {
-        givenName
-        familyName
-        activeRoles {
-          titleEnglish
-        }
+  query ($first: Int!, $after: String) {
+    allOrgTiers(first: $first, after: $after) {
+      pageInfo {
+        hasNextPage
+        endCursor
       }
-      parentOrganizationTier {
+      nodes {
         id
+        nameEn
+        tierLevel
+        owner {
+          givenName
+          familyName
+          activeRoles {
+            titleEnglish
+          }
+        }
+        parentOrganizationTier {
+          id
+        }
       }
     }
   }
@@ -234,16 +243,42 @@ function Node({ o, parent }) {
   );
 }
 export default function App(props: any) {
-  const { loading, error, data } = useQuery(GET_PEOPLE);
+  const signedIn = Boolean(sessionStorage.getItem("token"));
+  const { loading, error, data, fetchMore } = useQuery(GET_PEOPLE, {
+    skip: !signedIn,
+    variables: { first: PAGE_SIZE },
+  });
+
+  // Keep fetching until every tier is loaded, since a missing parent would break the tree
+  const pageInfo = data?.allOrgTiers.pageInfo;
+  const complete = Boolean(data) && !pageInfo.hasNextPage;
+
+  useEffect(() => {
+    if (!pageInfo?.hasNextPage) return;
+    fetchMore({
+      variables: { after: pageInfo.endCursor },
+      updateQuery: (prev, { fetchMoreResult }) => ({
+        allOrgTiers: {
+          ...fetchMoreResult.allOrgTiers,
+          nodes: [
+            ...prev.allOrgTiers.nodes,
+            ...fetchMoreResult.allOrgTiers.nodes,
+          ],
+        },
+      }),
+    });
+  }, [pageInfo, fetchMore]);
 
   const org = useMemo(() => {
     const d: any = {};
     let rootNode: any = {};
-    if (loading || error) return null;
-    data.allOrgTiers.forEach((r: any) => {
+    if (!signedIn || !complete || error) return null;
+    data.allOrgTiers.nodes.forEach((r: any) => {
       const obj = {
         ...r,
-        tradingName: `${r.owner.givenName} ${r.owner.familyName}`,
+        tradingName: r.owner
+          ? `${r.owner.givenName} ${r.owner.familyName}`
+          : "Vacant",
         account: [],
         organizationChildRelationship: [],
         collapsed: true,
@@ -275,10 +310,17 @@ export default function App(props: any) {
       }
     });
     return rootNode;
-  }, [loading, error, data]);
+  }, [signedIn, complete, error, data]);
 
-  if (loading) return <p>Loading data..</p>;
+  if (!signedIn)
+    return (
+      <p>
+        Sign in as an Analyst and store the bearer token with
+        sessionStorage.setItem("token", "...") to view the org chart.
+      </p>
+    );
   if (error) return <p>Error: {error.message}</p>;
+  if (loading || !complete) return <p>Loading data..</p>;
   return (
     <Box bgcolor="background" padding={4} height="80vh">
       <DndProvider backend={HTML5Backend}>
@@ -4,12 +4,30 @@ import "./index.css";
 import App from "./App";
 import reportWebVitals from "./reportWebVitals";
 
-import { ApolloClient, InMemoryCache, ApolloProvider } from "@apollo/client";
+import { ApolloClient, InMemoryCache, ApolloProvider, createHttpLink } from "@apollo/client";
+import { setContext } from "@apollo/client/link/context";
 
-const client = new ApolloClient({
+const httpLink = createHttpLink({
   // uri: "http://34.111.86.139/graphql",
   uri: "http://localhost:8080/graphql",
   // uri: "/graphql",
+});
+
+// Owner names need an Analyst bearer and an access rationale, both read from
+// sessionStorage (keys "token" and "rationale") so they aren't built into the bundle
+const authLink = setContext((_, { headers }) => {
+  const token = sessionStorage.getItem("token");
+  return {
+    headers: {
+      ...headers,
+      ...(token ? { Authorization: `Bearer ${token}` } : {}),
+      "X-Access-Rationale": sessionStorage.getItem("rationale") || "ADMINISTRATIVE",
+    },
+  };
+});
+
+const client = new ApolloClient({
+  link: authLink.concat(httpLink),
   cache: new InMemoryCache(),
 });
 
@@ -1,9 +1,16 @@
 use std::cmp::Ordering;
+use std::sync::Arc;
 
 use async_graphql::Guard;
 use async_graphql::*;
+use uuid::Uuid;
 
-#[derive(Eq, PartialEq, Display, EnumString, Copy, Clone, PartialOrd, Ord)]
+use crate::models::{AccessRecorder, AuthContext, AuthError};
+
+/// Written in place of redacted personal data, in responses, exports and change history
+pub const REDACTED: &str = "[redacted]";
+
+#[derive(Debug, Eq, PartialEq, Display, EnumString, Copy, Clone, PartialOrd, Ord)]
 #[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
 pub enum UserRole {
     User,
@@ -12,6 +19,9 @@ pub enum UserRole {
     Admin,
 }
 
+/// Passes for signed in users with user_role or a higher UserRole.
+/// Guards can be combined with `and` and `or`, e.g.
+/// `guard = "SelfGuard::new(self.user_id).or(RoleGuard::new(UserRole::Analyst))"`
 pub struct RoleGuard {
     pub user_role: UserRole,
 }
@@ -24,23 +34,133 @@ impl RoleGuard {
 
 impl Guard for RoleGuard {
     async fn check(&self, context: &Context<'_>) -> Result<(), async_graphql::Error> {
-        
-        if context.data_opt::<UserRole>() == Some(&UserRole::Admin) || context.data_opt::<UserRole>() == Some(&self.user_role) {
+
+        let auth = AuthContext::from_ctx(context)?;
+
+        if auth.role >= self.user_role {
+            Ok(())
+        } else {
+            Err(AuthError::Forbidden(self.user_role).extend())
+        }
+    }
+}
+
+/// Passes only for the signed in user with user_id, e.g. the user a Person belongs to
+pub struct SelfGuard {
+    pub user_id: Uuid,
+}
+
+impl SelfGuard {
+    pub fn new(user_id: Uuid) -> Self {
+        Self { user_id }
+    }
+}
+
+impl Guard for SelfGuard {
+    async fn check(&self, context: &Context<'_>) -> Result<(), async_graphql::Error> {
+
+        let auth = AuthContext::from_ctx(context)?;
+
+        if auth.user_id == self.user_id {
+            Ok(())
+        } else {
+            Err(AuthError::NotSelf.extend())
+        }
+    }
+}
+
+/// Passes for the user a record belongs to, or users with user_role or above.
+/// Shorthand for SelfGuard::new(user_id).or(RoleGuard::new(user_role))
+pub struct SelfOrRoleGuard {
+    pub user_id: Uuid,
+    pub user_role: UserRole,
+}
+
+impl SelfOrRoleGuard {
+    pub fn new(user_id: Uuid, user_role: UserRole) -> Self {
+        Self { user_id, user_role }
+    }
+}
+
+impl Guard for SelfOrRoleGuard {
+    async fn check(&self, context: &Context<'_>) -> Result<(), async_graphql::Error> {
+
+        let auth = AuthContext::from_ctx(context)?;
+
+        if auth.user_id == self.user_id || auth.role >= self.user_role {
             Ok(())
         } else {
-            let guard_error = context.data_opt::<jsonwebtoken::errors::Error>().clone();
-            match guard_error {
-                Some(e) => return Err(format!("{:?}", e.kind()).into()),
-                None => return Err(format!("Access denied: {} UserRole required", &self.user_role).into())
-            }
+            Err(AuthError::Forbidden(self.user_role).extend())
+        }
+    }
+}
+
+/// Guards identifiable personal data of the Person with person_id.
+/// Passes for signed in users who declared an X-Access-Rationale, and records the field and
+/// person so the request is written to the access log. People reading their own data are not logged.
+pub struct PersonalDataGuard {
+    pub person_id: Uuid,
+    pub user_id: Uuid,
+}
+
+impl PersonalDataGuard {
+    pub fn new(person_id: Uuid, user_id: Uuid) -> Self {
+        Self { person_id, user_id }
+    }
+}
+
+impl Guard for PersonalDataGuard {
+    async fn check(&self, context: &Context<'_>) -> Result<(), async_graphql::Error> {
+
+        let auth = AuthContext::from_ctx(context)?;
+
+        if auth.user_id == self.user_id {
+            return Ok(());
         }
+
+        match context.data_opt::<Arc<AccessRecorder>>() {
+            Some(recorder) if recorder.rationale.is_some() => {
+                recorder.record(context.field().name(), self.person_id);
+                Ok(())
+            },
+            _ => Err(AuthError::RationaleRequired.extend()),
+        }
+    }
+}
+
+/// PersonalDataGuard for records that may not name anyone, such as vacant
+/// org chart nodes. Takes the person and user ids of the person named, if any.
+pub struct HolderDataGuard(Option<PersonalDataGuard>);
+
+impl HolderDataGuard {
+    pub fn new(holder: Option<(Uuid, Uuid)>) -> Self {
+        Self(holder.map(|(person_id, user_id)| PersonalDataGuard::new(person_id, user_id)))
     }
 }
 
+impl Guard for HolderDataGuard {
+    async fn check(&self, context: &Context<'_>) -> Result<(), async_graphql::Error> {
+        match &self.0 {
+            Some(guard) => guard.check(context).await,
+            None => Ok(()),
+        }
+    }
+}
+
+/// Field will be visible to any signed in user
+pub fn is_signed_in(ctx: &Context<'_>) -> bool {
+    user_role(ctx).is_some()
+}
+
+/// UserRole of the signed in user, if any
+fn user_role(ctx: &Context<'_>) -> Option<UserRole> {
+    ctx.data_opt::<AuthContext>().map(|a| a.role)
+}
+
 /// Field will be visible to users with UserRole::Admin and
 /// UserRole::Analyst
 pub fn is_analyst(ctx: &Context<'_>) -> bool {
-    if let Some(role) = ctx.data_opt::<UserRole>() {
+    if let Some(role) = user_role(ctx) {
         let result = match role.cmp(&UserRole::Analyst) {
             Ordering::Less => false,
             Ordering::Equal => true,
@@ -55,7 +175,7 @@ pub fn is_analyst(ctx: &Context<'_>) -> bool {
 /// Field will be visible to users with UserRole::Admin and
 /// UserRole::Analyst
 pub fn is_operator(ctx: &Context<'_>) -> bool {
-    if let Some(role) = ctx.data_opt::<UserRole>() {
+    if let Some(role) = user_role(ctx) {
         let result = match role.cmp(&UserRole::Operator) {
             Ordering::Less => false,
             Ordering::Equal => true,
@@ -69,5 +189,5 @@ pub fn is_operator(ctx: &Context<'_>) -> bool {
 
 /// Field will only be visible to users with UserRole::Admin
 pub fn is_admin(ctx: &Context<'_>) -> bool {
-    ctx.data_opt::<UserRole>() == Some(&UserRole::Admin)
+    user_role(ctx) == Some(UserRole::Admin)
 }
\ No newline at end of file
@@ -1,4 +1,28 @@
 // Constants
 pub const DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
 pub const TOKEN_DURATION: i64 = 7200; // Duration for JWT sign-in in seconds
-pub const MANDATORY_TESTING_RATE: f64 = 0.01; // fraction of referrals to mandatory testing
\ No newline at end of file
+pub const PASSWORD_MIN_LENGTH: usize = 12; // Shortest password accepted by createUser, updateUser and changePassword
+pub const PASSWORD_MAX_LENGTH: usize = 128; // Longest password accepted, bounds the cost of hashing
+pub const PASSWORD_MIN_CHARACTER_CLASSES: usize = 3; // Of lowercase, uppercase, numbers and symbols
+pub const REFRESH_TOKEN_DURATION: i64 = 1_209_600; // Duration of a refresh token in seconds (14 days)
+pub const EMAIL_VERIFICATION_DURATION: i64 = 86_400; // Lifetime of an email verification code in seconds (24 hours)
+pub const PASSWORD_RESET_DURATION: i64 = 1_800; // Lifetime of a password reset token in seconds (30 minutes)
+pub const OIDC_JWKS_CACHE_SECONDS: u64 = 3600; // How long the OIDC provider's signing keys are cached
+pub const OIDC_JWKS_MIN_REFRESH_SECONDS: u64 = 60; // Minimum time between refetches of the keys when a token has an unknown kid
+pub const API_KEY_DEFAULT_DAYS: i64 = 90; // Lifetime of an API key minted without expires_at
+pub const API_KEY_MAX_DAYS: i64 = 365; // Longest lifetime an API key can be minted with
+pub const API_KEY_LAST_USED_INTERVAL: i64 = 60; // Seconds between updates of an API key's last_used_at
+pub const MANDATORY_TESTING_RATE: f64 = 0.01; // fraction of referrals to mandatory testing
+pub const DEFAULT_PAGE_SIZE: usize = 50; // Page size used when a connection query has no first or last
+pub const MAX_PAGE_SIZE: usize = 500; // Upper bound on first or last for connection queries
+pub const ORG_CHART_IMAGE_URL: &str = "https://raw.githubusercontent.com/bumbeishvili/Assets/master/Projects/D3/Organization%20Chart/general.jpg"; // Placeholder avatar for org chart nodes
+pub const ORG_CHART_PROFILE_URL: &str = "/person"; // Base for org chart profile links, the person id is appended
+pub const VALIDATION_HALF_LIFE_DAYS: f64 = 730.0; // Age at which a validation counts for half its weight
+pub const VALIDATION_QUORUM: usize = 3; // Validations required before a capability gets a validated_level
+pub const VALIDATION_CONFIDENCE_PRIOR: f64 = 6.0; // Total validation weight at which evidence gives 50% confidence
+pub const MOBILIZATION_COOLDOWN_DAYS: i64 = 90; // People whose last deployment ended more recently than this are excluded from mobilization searches
+pub const MOBILIZATION_FIT_WEIGHT: f64 = 0.7; // Share of the mobilization score from skill fit, the rest is availability
+pub const MOBILIZATION_WORK_SCALE: f64 = 10.0; // Open work effort at which work availability is halved
+pub const DEPLOYMENT_WINDOW_MONTHS: i32 = 12; // Default look-back window for deployment metrics
+pub const DEFAULT_MIN_CELL_SIZE: i64 = 5; // Smallest count aggregate queries publish unless MIN_CELL_SIZE is set
+pub const DEFAULT_CELL_ROUNDING_BASE: i64 = 1; // Aggregate counts are rounded to a multiple of this unless CELL_ROUNDING_BASE is set, 1 turns rounding off
@@ -360,8 +360,6 @@ pub fn create_validations() -> Result<(), Error> {
             print!(".")
         }
 
-        let mut validated_levels: Vec<CapabilityLevel> = Vec::new();
-
         for validator in validators {
 
             let assessment = match rng.gen_range(0..10) {
@@ -371,8 +369,6 @@ pub fn create_validations() -> Result<(), Error> {
                 _ => c.self_identified_level.step_up(),
             };
 
-            validated_levels.push(assessment);
-    
             let v = NewValidation::new(
                 validator,
                 c.id,
@@ -383,7 +379,7 @@ pub fn create_validations() -> Result<(), Error> {
             
         }
         let _r = Validation::batch_create(validations)?;
-        c.update_from_batch_validations(&validated_levels)?;
+        c.rescore()?;
         progress.increment();
     }
     progress.done();
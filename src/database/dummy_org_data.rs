@@ -1,4 +1,6 @@
 
+use std::collections::HashMap;
+
 use rand::Rng;
 use rand::{seq::SliceRandom};
 use async_graphql::Error;
@@ -9,7 +11,7 @@ use crate::database::{create_validations, generate_requirement};
 use crate::models::{Person, Organization, NewPerson, NewOrganization, 
     Role, NewRole, Team, NewTeam, OrgTier, NewOrgTier, OrgOwnership, NewOrgOwnership,
     TeamOwnership, NewTeamOwnership, HrGroup, SkillDomain, Skill, NewWork, CapabilityLevel, WorkStatus, Work,
-    NewRequirement, Requirement,
+    NewRequirement, Requirement, ContactData, NewContactData, ContactType,
 };
 
 use super::{create_fake_capabilities, generate_dummy_publications_and_contributors, generate_tasks};
@@ -217,6 +219,9 @@ pub fn pre_populate_db_schema() -> Result<(), Error> {
 
     let mut new_people: Vec<NewPerson> = Vec::new();
 
+    // Work contacts by user_id, saved once the people have ids
+    let mut contacts: HashMap<Uuid, (String, &Vec<String>)> = HashMap::new();
+
     let path = "seeds/names.csv";
 
     let mut reader = csv::Reader::from_path(path).unwrap();
@@ -234,17 +239,14 @@ pub fn pre_populate_db_schema() -> Result<(), Error> {
             uuid::Uuid::new_v4(),
             famn.to_owned(),
             gn.to_owned(),
-            format!("{}.{}_{}@phac-aspc.gc.ca", &gn, &famn, rng.gen_range(0..9999)).to_lowercase(),
-            gen_rand_number(),
-            addr[0].to_owned(),
-            addr[1].to_owned(),
-            addr[2].to_owned(),
-            addr[3].to_owned(),
-            "Canada".to_string(),
             org.id,
             gen_rand_number(),
             gen_rand_number(),
         );
+
+        let email = format!("{}.{}_{}@phac-aspc.gc.ca", &gn, &famn, rng.gen_range(0..9999)).to_lowercase();
+        contacts.insert(p.user_id, (email, addr));
+
         new_people.push(p);
     }
 
@@ -255,6 +257,26 @@ pub fn pre_populate_db_schema() -> Result<(), Error> {
     println!("Inserted {} people.", r);
     progress_people.done();
 
+    let new_contacts: Vec<NewContactData> = Person::get_all()?
+        .into_iter()
+        .filter_map(|p| contacts.remove(&p.user_id).map(|(email, addr)| NewContactData {
+            person_id: p.id,
+            contact_type: ContactType::Work,
+            email: Some(email),
+            phone: Some(gen_rand_number()),
+            work_address: Some(addr[0].to_owned()),
+            city: Some(addr[1].to_owned()),
+            province: Some(addr[2].to_owned()),
+            postal_code: Some(addr[3].to_owned()),
+            country: Some("Canada".to_string()),
+            is_primary: true,
+            start_datestamp: p.created_at,
+            end_date: None,
+        }))
+        .collect();
+
+    let _r = ContactData::batch_create(new_contacts)?;
+
     let mut people_ids = Person::get_all_ids()?;
 
     let mut progress_cap = ProgressLogger::new("Inserting Capabilities".to_owned(),people_ids.len());
@@ -0,0 +1,81 @@
+use std::sync::Arc;
+
+use async_graphql::extensions::{Extension, ExtensionContext, ExtensionFactory, NextParseQuery};
+use async_graphql::parser::types::{ExecutableDocument, OperationType, Selection, SelectionSet};
+use async_graphql::*;
+
+use crate::models::{ApiKeyScopes, ApiScope, AuthError};
+
+/// Limits requests made with an API key to its scopes.
+/// Queries need ApiScope::Read and each root mutation field needs the scope from
+/// ApiScope::for_mutation. Requests made with a bearer token are not affected.
+pub struct ApiKeyScopeCheck;
+
+impl ExtensionFactory for ApiKeyScopeCheck {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(ApiKeyScopeCheckExtension)
+    }
+}
+
+struct ApiKeyScopeCheckExtension;
+
+/// Collects the root field names of a selection set, following fragments
+fn root_fields<'a>(document: &'a ExecutableDocument, selection_set: &'a SelectionSet, fields: &mut Vec<(&'a str, Pos)>) {
+    for selection in &selection_set.items {
+        match &selection.node {
+            Selection::Field(f) => fields.push((f.node.name.node.as_str(), f.pos)),
+            Selection::InlineFragment(f) => root_fields(document, &f.node.selection_set.node, fields),
+            Selection::FragmentSpread(s) => {
+                if let Some(f) = document.fragments.get(&s.node.fragment_name.node) {
+                    root_fields(document, &f.node.selection_set.node, fields);
+                }
+            },
+        }
+    }
+}
+
+/// The first root field of an operation the scopes don't allow
+fn out_of_scope(document: &ExecutableDocument, scopes: &ApiKeyScopes) -> Option<(String, Pos)> {
+    for (_, operation) in document.operations.iter() {
+        let mut fields = Vec::new();
+        root_fields(document, &operation.node.selection_set.node, &mut fields);
+
+        for (field, pos) in fields {
+            if field == "__typename" {
+                continue;
+            }
+
+            let required = match operation.node.ty {
+                OperationType::Query => Some(ApiScope::Read),
+                OperationType::Mutation => ApiScope::for_mutation(field),
+                OperationType::Subscription => None,
+            };
+
+            if !required.is_some_and(|s| scopes.0.contains(&s)) {
+                return Some((field.to_string(), pos));
+            }
+        }
+    }
+
+    None
+}
+
+#[async_trait::async_trait]
+impl Extension for ApiKeyScopeCheckExtension {
+    async fn parse_query(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        query: &str,
+        variables: &Variables,
+        next: NextParseQuery<'_>,
+    ) -> ServerResult<ExecutableDocument> {
+        let document = next.run(ctx, query, variables).await?;
+
+        if let Some(scopes) = ctx.data_opt::<ApiKeyScopes>()
+            && let Some((field, pos)) = out_of_scope(&document, scopes) {
+            return Err(AuthError::OutOfScope(field).extend().into_server_error(pos));
+        }
+
+        Ok(document)
+    }
+}
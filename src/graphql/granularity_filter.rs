@@ -0,0 +1,144 @@
+use std::sync::Arc;
+
+use async_graphql::extensions::{Extension, ExtensionContext, ExtensionFactory, NextResolve, ResolveInfo};
+use async_graphql::registry::{MetaDirectiveInvocation, MetaType, Registry};
+use async_graphql::*;
+use uuid::Uuid;
+
+use crate::common_utils::REDACTED;
+use crate::models::{pseudonym, AuthContext, AuthError, Granularity};
+
+/// Fields through which a type wraps the records it is made of:
+/// Relay connections and edges, and MatchResult
+const WRAPPING_FIELDS: [&str; 3] = ["edges", "node", "candidate"];
+
+#[TypeDirective(name = "identifying", location = "FieldDefinition")]
+/// Names, locates or identifies a person. Redacted below Granularity::Identifiable
+pub fn identifying_field() {}
+
+#[TypeDirective(name = "personId", location = "FieldDefinition")]
+/// Holds the id of a Person or of their User. Pseudonymized below Granularity::Identifiable
+pub fn person_id_field() {}
+
+#[TypeDirective(name = "personRecord", location = "Object")]
+/// A person's record. Refused below Granularity::Anonymized
+pub fn person_record_type() {}
+
+/// Shapes responses to the Granularity the signed in user is granted.
+/// Below Granularity::Identifiable, @identifying fields are redacted and @personId fields
+/// are replaced with pseudonyms that are stable for the session. Below Granularity::Anonymized,
+/// fields returning @personRecord types are refused in favour of aggregate queries.
+pub struct GranularityFilter;
+
+impl ExtensionFactory for GranularityFilter {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(GranularityFilterExtension)
+    }
+}
+
+struct GranularityFilterExtension;
+
+/// Strips list and non-null markers, e.g. "[Person!]!" to "Person"
+fn base_type(return_type: &str) -> &str {
+    return_type.trim_matches(|c| c == '[' || c == ']' || c == '!')
+}
+
+fn has_directive(invocations: &[MetaDirectiveInvocation], directive: impl TypeDirective) -> bool {
+    let name = directive.name();
+    invocations.iter().any(|d| d.name == name)
+}
+
+/// Whether the field is marked with directive where it is declared
+fn field_has(registry: &Registry, parent_type: &str, field: &str, directive: impl TypeDirective) -> bool {
+    registry.types.get(parent_type)
+        .and_then(|t| t.field_by_name(field))
+        .is_some_and(|f| has_directive(&f.directive_invocations, directive))
+}
+
+/// Whether a type is a @personRecord, or wraps them
+fn is_person_record(registry: &Registry, type_name: &str) -> bool {
+    match registry.types.get(type_name) {
+        Some(MetaType::Object { directive_invocations, fields, .. }) =>
+            has_directive(directive_invocations, person_record_type)
+                || WRAPPING_FIELDS.iter().any(|w| fields.get(*w)
+                    .is_some_and(|f| is_person_record(registry, base_type(&f.ty)))),
+        _ => false,
+    }
+}
+
+/// Replaces uuids in a resolved id field, or list of them, with their pseudonyms
+fn pseudonymize(value: Value, session_id: Uuid) -> Value {
+    match value {
+        Value::String(s) => match Uuid::parse_str(&s) {
+            Ok(id) => Value::String(pseudonym(session_id, id).to_string()),
+            Err(_) => Value::String(s),
+        },
+        Value::List(items) => Value::List(items.into_iter().map(|v| pseudonymize(v, session_id)).collect()),
+        other => other,
+    }
+}
+
+/// A value of the field's type standing in for what was redacted
+fn redacted(return_type: &str) -> Value {
+    if return_type.starts_with('[') {
+        Value::List(Vec::new())
+    } else if return_type.ends_with('!') {
+        Value::String(REDACTED.to_string())
+    } else {
+        Value::Null
+    }
+}
+
+fn path_of(info: &ResolveInfo<'_>) -> Vec<PathSegment> {
+    let mut path: Vec<PathSegment> = std::iter::once(info.path_node)
+        .chain(info.path_node.parents())
+        .map(|node| match node.segment {
+            QueryPathSegment::Index(i) => PathSegment::Index(i),
+            QueryPathSegment::Name(name) => PathSegment::Field(name.to_string()),
+        })
+        .collect();
+
+    path.reverse();
+
+    path
+}
+
+#[async_trait::async_trait]
+impl Extension for GranularityFilterExtension {
+    async fn resolve(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        info: ResolveInfo<'_>,
+        next: NextResolve<'_>,
+    ) -> ServerResult<Option<Value>> {
+
+        let auth = match ctx.data_opt::<AuthContext>() {
+            Some(a) if a.granularity < Granularity::Identifiable && !info.is_for_introspection => *a,
+            _ => return next.run(ctx, info).await,
+        };
+
+        let registry = &ctx.schema_env.registry;
+
+        if auth.granularity < Granularity::Anonymized && is_person_record(registry, base_type(info.return_type)) {
+            let mut error = AuthError::InsufficientGranularity(Granularity::Anonymized)
+                .extend()
+                .into_server_error(Default::default());
+            error.locations = Vec::new();
+            error.path = path_of(&info);
+
+            return Err(error);
+        }
+
+        // Redacted fields aren't resolved, so they don't reach PersonalDataGuard or the access log
+        if field_has(registry, info.parent_type, info.name, identifying_field) {
+            return Ok(Some(redacted(info.return_type)));
+        }
+
+        if field_has(registry, info.parent_type, info.name, person_id_field) {
+            let value = next.run(ctx, info).await?;
+            return Ok(value.map(|v| pseudonymize(v, auth.session_id)));
+        }
+
+        next.run(ctx, info).await
+    }
+}
@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+
+use async_graphql::*;
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use dataloader::BatchFn;
+use dataloader::non_cached::Loader;
+use uuid::Uuid;
+
+use crate::models::{AsOfKey, Capability, ContactData, Deployment, EmployeeData, HrStatePeriod, OrgOwnership, OrgTier, Organization, Person, Requirement,
+    Role, Skill, Team, TeamOwnership};
+
+/// Batches single-row lookups: every key maps to at most one row.
+/// `load` runs one query for all keys and `key` tells us which key a row answers.
+pub struct OneBatcher<T> {
+    load: fn(&[Uuid]) -> Result<Vec<T>>,
+    key: fn(&T) -> Uuid,
+}
+
+/// Batches one-to-many lookups: every key maps to all rows whose `key` matches it.
+pub struct ManyBatcher<T> {
+    load: fn(&[Uuid]) -> Result<Vec<T>>,
+    key: fn(&T) -> Uuid,
+}
+
+#[async_trait]
+impl<T: Clone + Send> BatchFn<Uuid, Result<Option<T>>> for OneBatcher<T> {
+    async fn load(&mut self, keys: &[Uuid]) -> HashMap<Uuid, Result<Option<T>>> {
+        // The loader expects an entry for every key, so errors and misses are stored per key
+        match (self.load)(keys) {
+            Ok(rows) => {
+                let mut found: HashMap<Uuid, T> = rows.into_iter()
+                    .map(|row| ((self.key)(&row), row))
+                    .collect();
+
+                keys.iter()
+                    .map(|k| (*k, Ok(found.remove(k))))
+                    .collect()
+            },
+            Err(e) => keys.iter().map(|k| (*k, Err(e.clone()))).collect(),
+        }
+    }
+}
+
+#[async_trait]
+impl<T: Clone + Send> BatchFn<Uuid, Result<Vec<T>>> for ManyBatcher<T> {
+    async fn load(&mut self, keys: &[Uuid]) -> HashMap<Uuid, Result<Vec<T>>> {
+        match (self.load)(keys) {
+            Ok(rows) => {
+                let mut grouped: HashMap<Uuid, Result<Vec<T>>> = keys.iter()
+                    .map(|k| (*k, Ok(Vec::new())))
+                    .collect();
+
+                for row in rows {
+                    if let Some(Ok(v)) = grouped.get_mut(&(self.key)(&row)) {
+                        v.push(row);
+                    }
+                }
+
+                grouped
+            },
+            Err(e) => keys.iter().map(|k| (*k, Err(e.clone()))).collect(),
+        }
+    }
+}
+
+/// Batches lookups of rows as they stood on a date: every (id, as_of) key maps to the rows
+/// `load` pairs it with, so the rows can differ between dates for the same id.
+pub struct AsOfBatcher<T> {
+    load: AsOfLoadFn<T>,
+}
+
+type AsOfLoadFn<T> = fn(&[AsOfKey]) -> Result<Vec<(AsOfKey, T)>>;
+
+#[async_trait]
+impl<T: Clone + Send> BatchFn<AsOfKey, Result<Vec<T>>> for AsOfBatcher<T> {
+    async fn load(&mut self, keys: &[AsOfKey]) -> HashMap<AsOfKey, Result<Vec<T>>> {
+        match (self.load)(keys) {
+            Ok(rows) => {
+                let mut grouped: HashMap<AsOfKey, Result<Vec<T>>> = keys.iter()
+                    .map(|k| (*k, Ok(Vec::new())))
+                    .collect();
+
+                for (key, row) in rows {
+                    if let Some(Ok(v)) = grouped.get_mut(&key) {
+                        v.push(row);
+                    }
+                }
+
+                grouped
+            },
+            Err(e) => keys.iter().map(|k| (*k, Err(e.clone()))).collect(),
+        }
+    }
+}
+
+type OneLoader<T> = Loader<Uuid, Result<Option<T>>, OneBatcher<T>>;
+type ManyLoader<T> = Loader<Uuid, Result<Vec<T>>, ManyBatcher<T>>;
+type AsOfLoader<T> = Loader<AsOfKey, Result<Vec<T>>, AsOfBatcher<T>>;
+
+fn one<T: Clone + Send>(load: fn(&[Uuid]) -> Result<Vec<T>>, key: fn(&T) -> Uuid) -> OneLoader<T> {
+    Loader::new(OneBatcher { load, key })
+}
+
+fn many<T: Clone + Send>(load: fn(&[Uuid]) -> Result<Vec<T>>, key: fn(&T) -> Uuid) -> ManyLoader<T> {
+    Loader::new(ManyBatcher { load, key })
+}
+
+fn as_of<T: Clone + Send>(load: AsOfLoadFn<T>) -> AsOfLoader<T> {
+    Loader::new(AsOfBatcher { load })
+}
+
+/// Batched loaders shared by the ComplexObject resolvers.
+/// Loads requested in the same tick of the executor are collected into one `eq_any` query,
+/// so resolving a list of 500 roles with their people and teams issues a handful of statements.
+/// The loaders are non-caching, so it is safe to share them between requests.
+pub struct Loaders {
+    persons: OneLoader<Person>,
+    organizations: OneLoader<Organization>,
+    teams: OneLoader<Team>,
+    org_tiers: OneLoader<OrgTier>,
+    skills: OneLoader<Skill>,
+    roles: OneLoader<Role>,
+    team_ownerships: OneLoader<TeamOwnership>,
+    org_ownerships: OneLoader<OrgOwnership>,
+    roles_by_team: ManyLoader<Role>,
+    roles_by_person: ManyLoader<Role>,
+    roles_by_person_as_of: AsOfLoader<Role>,
+    team_ownerships_as_of: AsOfLoader<TeamOwnership>,
+    org_ownerships_as_of: AsOfLoader<OrgOwnership>,
+    capabilities_by_person: ManyLoader<Capability>,
+    requirements_by_role: ManyLoader<Requirement>,
+    deployments_by_person: ManyLoader<Deployment>,
+    employee_data_by_person: ManyLoader<EmployeeData>,
+    contacts_by_person: ManyLoader<ContactData>,
+    hr_states_by_person: ManyLoader<HrStatePeriod>,
+}
+
+impl Loaders {
+    pub fn new() -> Self {
+        Loaders {
+            persons: one(|ids| Person::get_by_ids(&ids.to_vec()), |p| p.id),
+            organizations: one(Organization::get_by_ids, |o| o.id),
+            teams: one(|ids| Team::get_by_ids(&ids.to_vec()), |t| t.id),
+            org_tiers: one(|ids| OrgTier::get_by_ids(&ids.to_vec()), |o| o.id),
+            skills: one(|ids| Skill::get_by_ids(&ids.to_vec()), |s| s.id),
+            roles: one(Role::get_by_ids, |r| r.id),
+            team_ownerships: one(TeamOwnership::get_current_by_team_ids, |t| t.team_id),
+            org_ownerships: one(OrgOwnership::get_current_by_org_tier_ids, |o| o.org_tier_id),
+            roles_by_team: many(Role::get_by_team_ids, |r| r.team_id),
+            // Roles without a person are never returned by get_by_person_ids
+            roles_by_person: many(Role::get_by_person_ids, |r| r.person_id.unwrap_or_default()),
+            roles_by_person_as_of: as_of(Role::get_by_person_ids_as_of),
+            team_ownerships_as_of: as_of(TeamOwnership::get_by_team_ids_as_of),
+            org_ownerships_as_of: as_of(OrgOwnership::get_by_org_tier_ids_as_of),
+            capabilities_by_person: many(Capability::get_by_person_ids, |c| c.person_id),
+            requirements_by_role: many(Requirement::get_by_role_ids, |r| r.role_id),
+            deployments_by_person: many(Deployment::get_by_person_ids, |d| d.person_id),
+            employee_data_by_person: many(EmployeeData::get_by_person_ids, |e| e.person_id),
+            hr_states_by_person: many(HrStatePeriod::get_by_person_ids, |h| h.person_id),
+            contacts_by_person: many(ContactData::get_by_person_ids, |c| c.person_id),
+        }
+    }
+
+    pub async fn person(&self, id: Uuid) -> Result<Person> {
+        found(self.persons.load(id).await, "Person", id)
+    }
+
+    pub async fn organization(&self, id: Uuid) -> Result<Organization> {
+        found(self.organizations.load(id).await, "Organization", id)
+    }
+
+    pub async fn team(&self, id: Uuid) -> Result<Team> {
+        found(self.teams.load(id).await, "Team", id)
+    }
+
+    pub async fn org_tier(&self, id: Uuid) -> Result<OrgTier> {
+        found(self.org_tiers.load(id).await, "OrgTier", id)
+    }
+
+    pub async fn skill(&self, id: Uuid) -> Result<Skill> {
+        found(self.skills.load(id).await, "Skill", id)
+    }
+
+    pub async fn role(&self, id: Uuid) -> Result<Role> {
+        found(self.roles.load(id).await, "Role", id)
+    }
+
+    /// Current owner of a team, if the team has an open ownership
+    pub async fn team_owner(&self, team_id: Uuid) -> Result<Option<Person>> {
+        match self.team_ownerships.load(team_id).await? {
+            Some(ownership) => Ok(Some(self.person(ownership.person_id).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Current owner of an org tier, if the tier has an unretired ownership
+    pub async fn org_tier_owner(&self, org_tier_id: Uuid) -> Result<Option<Person>> {
+        match self.org_ownerships.load(org_tier_id).await? {
+            Some(ownership) => Ok(Some(self.person(ownership.owner_id).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Owner of a team on as_of, if the team had an ownership in effect then
+    pub async fn team_owner_as_of(&self, team_id: Uuid, as_of: NaiveDateTime) -> Result<Option<Person>> {
+        match self.team_ownerships_as_of.load((team_id, as_of)).await?.into_iter().next() {
+            Some(ownership) => Ok(Some(self.person(ownership.person_id).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Owner of an org tier on as_of, if the tier had an ownership in effect then
+    pub async fn org_tier_owner_as_of(&self, org_tier_id: Uuid, as_of: NaiveDateTime) -> Result<Option<Person>> {
+        match self.org_ownerships_as_of.load((org_tier_id, as_of)).await?.into_iter().next() {
+            Some(ownership) => Ok(Some(self.person(ownership.owner_id).await?)),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn roles_by_team(&self, team_id: Uuid) -> Result<Vec<Role>> {
+        self.roles_by_team.load(team_id).await
+    }
+
+    /// Roles held by a person, filtered to active or inactive
+    pub async fn roles_by_person(&self, person_id: Uuid, active: bool) -> Result<Vec<Role>> {
+        let roles = self.roles_by_person.load(person_id).await?;
+
+        Ok(roles.into_iter().filter(|r| r.active == active).collect())
+    }
+
+    /// Roles a person held at as_of, going by the role history and dates rather than the active flag
+    pub async fn roles_by_person_as_of(&self, person_id: Uuid, as_of: NaiveDateTime) -> Result<Vec<Role>> {
+        self.roles_by_person_as_of.load((person_id, as_of)).await
+    }
+
+    pub async fn capabilities_by_person(&self, person_id: Uuid) -> Result<Vec<Capability>> {
+        self.capabilities_by_person.load(person_id).await
+    }
+
+    pub async fn requirements_by_role(&self, role_id: Uuid) -> Result<Vec<Requirement>> {
+        self.requirements_by_role.load(role_id).await
+    }
+
+    pub async fn deployments_by_person(&self, person_id: Uuid) -> Result<Vec<Deployment>> {
+        self.deployments_by_person.load(person_id).await
+    }
+
+    pub async fn employee_data_by_person(&self, person_id: Uuid) -> Result<Vec<EmployeeData>> {
+        self.employee_data_by_person.load(person_id).await
+    }
+
+    pub async fn hr_states_by_person(&self, person_id: Uuid) -> Result<Vec<HrStatePeriod>> {
+        self.hr_states_by_person.load(person_id).await
+    }
+
+    pub async fn contacts_by_person(&self, person_id: Uuid) -> Result<Vec<ContactData>> {
+        self.contacts_by_person.load(person_id).await
+    }
+}
+
+impl Default for Loaders {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn found<T>(res: Result<Option<T>>, entity: &str, id: Uuid) -> Result<T> {
+    res?.ok_or_else(|| Error::new(format!("{} {} not found", entity, id)))
+}
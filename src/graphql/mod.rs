@@ -1,9 +1,17 @@
 mod query;
 mod mutation;
 mod utilities;
+mod pagination;
+mod loaders;
+mod api_key_scopes;
+mod granularity_filter;
 // mod subscription;
 
 pub use self::query::*;
 pub use self::mutation::*;
 pub use self::utilities::*;
+pub use self::pagination::*;
+pub use self::loaders::*;
+pub use self::api_key_scopes::*;
+pub use self::granularity_filter::*;
 // pub use self::subscription::*;
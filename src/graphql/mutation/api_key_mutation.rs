@@ -0,0 +1,63 @@
+use async_graphql::*;
+use uuid::Uuid;
+
+use crate::models::{ApiKey, ApiKeyData, MintedApiKey, NewApiKey, User, ChangeRecord, acting_user};
+use crate::common_utils::{UserRole,
+    is_admin, RoleGuard};
+
+#[derive(Default)]
+pub struct ApiKeyMutation;
+
+#[Object]
+impl ApiKeyMutation {
+
+    #[graphql(
+        name = "mintApiKey",
+        guard = "RoleGuard::new(UserRole::Admin)",
+        visible = "is_admin",
+    )]
+    /// Creates an API key for an integration account. The key is only returned here,
+    /// only its hash is stored.
+    pub async fn mint_api_key(
+        &self,
+        context: &Context<'_>,
+        api_key_data: ApiKeyData,
+    ) -> Result<MintedApiKey> {
+
+        User::get_by_id(&api_key_data.user_id)?;
+
+        let actor = acting_user(context);
+
+        let (new_key, key) = NewApiKey::new(api_key_data, actor)?;
+
+        let api_key = ApiKey::create(&new_key)?;
+
+        ChangeRecord::log_create(&api_key, actor)?;
+
+        Ok(MintedApiKey { api_key, key })
+    }
+
+    #[graphql(
+        name = "revokeApiKey",
+        guard = "RoleGuard::new(UserRole::Admin)",
+        visible = "is_admin",
+    )]
+    pub async fn revoke_api_key(
+        &self,
+        context: &Context<'_>,
+        id: Uuid,
+    ) -> Result<ApiKey> {
+
+        let before = ApiKey::get_by_id(&id)?;
+
+        if before.revoked_at.is_some() {
+            return Err(Error::new("API key is already revoked"));
+        }
+
+        let api_key = ApiKey::revoke(&id)?;
+
+        ChangeRecord::log_update(&before, &api_key, acting_user(context))?;
+
+        Ok(api_key)
+    }
+}
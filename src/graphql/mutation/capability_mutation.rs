@@ -3,7 +3,8 @@ use chrono::NaiveDateTime;
 use serde::{Serialize, Deserialize};
 use uuid::Uuid;
 
-use crate::models::{Capability, NewCapability, CapabilityLevel};
+use crate::models::{Capability, NewCapability, CapabilityLevel, Validation, ValidatedLevel,
+    ChangeRecord, AuthContext, Person, acting_user};
 use crate::common_utils::{UserRole,
     is_operator, RoleGuard};
 // use rdkafka::producer::FutureProducer;
@@ -24,12 +25,14 @@ impl CapabilityMutation {
     )]
     pub async fn create_capability(
         &self,
-        _context: &Context<'_>,
+        context: &Context<'_>,
         data: NewCapability,
     ) -> Result<Capability> {
         
         let capability = Capability::create(&data)?;
 
+        ChangeRecord::log_create(&capability, acting_user(context))?;
+
         Ok(capability)
     }
 
@@ -38,33 +41,61 @@ impl CapabilityMutation {
         guard = "RoleGuard::new(UserRole::Operator)",
         visible = "is_operator",
     )]
-    /// An operator may update a capability.
-    /// To update a validated_level, you must include the Uuid of the validator and their 
-    /// current validated level. If the validator's level is equal or greater than the 
-    /// level they are validating, the system will update validated_level.
-    /// Need to update Capability to also track the validator_uuid 
+    /// An operator may update a capability's self identified level or retire it.
+    /// validated_level can only change through validateCapability.
     pub async fn update_capability(
         &self,
-        _context: &Context<'_>,
+        context: &Context<'_>,
         data: CapabilityData,
     ) -> Result<Capability> {
         
-        let mut capability = Capability::get_by_id(&data.id)?;
+        let before = Capability::get_by_id(&data.id)?;
+        let mut capability = before.clone();
 
         if let Some(s) = data.self_identified_level {
             capability.self_identified_level = s;
         };
 
-        if let Some(s) = data.validated_level {
-            capability.validated_level = Some(s);
-        }
-
         if let Some(s) = data.retired_at {
             capability.retired_at = Some(s);
         };
         
-        capability.update()
+        let capability = capability.update()?;
+
+        ChangeRecord::log_update(&before, &capability, acting_user(context))?;
+
+        Ok(capability)
+    }
+
+    #[graphql(
+        name = "validateCapability", 
+        guard = "RoleGuard::new(UserRole::Operator)",
+        visible = "is_operator",
+    )]
+    /// Records the signed in user's validation of a capability and returns the
+    /// recomputed validated level. The user validates as their Person, who must hold the
+    /// same skill at or above validated_level and cannot validate their own capability.
+    pub async fn validate_capability(
+        &self,
+        context: &Context<'_>,
+        capability_id: Uuid,
+        validated_level: CapabilityLevel,
+    ) -> Result<ValidatedLevel> {
+
+        let auth = AuthContext::from_ctx(context)?;
+
+        let validator = Person::get_by_user_id(&auth.user_id)?
+            .ok_or_else(|| Error::new("Only users with a person record can validate capabilities"))?;
+
+        let before = Capability::get_by_id(&capability_id)?;
+
+        let level = Validation::validate(capability_id, validator.id, validated_level)?;
+
+        let after = Capability::get_by_id(&capability_id)?;
+
+        ChangeRecord::log_update(&before, &after, acting_user(context))?;
 
+        Ok(level)
     }
 }
 
@@ -76,6 +107,5 @@ impl CapabilityMutation {
 pub struct CapabilityData {
     pub id: Uuid,
     pub self_identified_level: Option<CapabilityLevel>,
-    pub validated_level: Option<CapabilityLevel>,
     pub retired_at: Option<NaiveDateTime>,
 }
\ No newline at end of file
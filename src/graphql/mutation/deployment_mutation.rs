@@ -0,0 +1,126 @@
+use async_graphql::*;
+use chrono::NaiveDateTime;
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+
+use crate::models::{Deployment, NewDeployment, Person, Role, ChangeRecord, acting_user};
+use crate::common_utils::{UserRole,
+    is_operator, RoleGuard};
+
+#[derive(Default)]
+pub struct DeploymentMutation;
+
+/// The originating role must be one the person holds or has held
+fn check_role(person_id: Uuid, role_id: Option<Uuid>) -> Result<()> {
+    if let Some(id) = role_id {
+        let role = Role::get_by_id(&id)?;
+
+        if role.person_id != Some(person_id) {
+            return Err(Error::new("Originating role must belong to the deployed person"));
+        }
+    }
+
+    Ok(())
+}
+
+#[Object]
+impl DeploymentMutation {
+
+    #[graphql(
+        name = "createDeployment",
+        guard = "RoleGuard::new(UserRole::Operator)",
+        visible = "is_operator",
+    )]
+    pub async fn create_deployment(
+        &self,
+        context: &Context<'_>,
+        deployment_data: NewDeployment,
+    ) -> Result<Deployment> {
+
+        Person::get_by_id(&deployment_data.person_id)?;
+
+        check_role(deployment_data.person_id, deployment_data.role_id)?;
+
+        Deployment::check(deployment_data.start_datestamp, deployment_data.end_date, deployment_data.effort)?;
+
+        let deployment = Deployment::create(&deployment_data)?;
+
+        ChangeRecord::log_create(&deployment, acting_user(context))?;
+
+        Ok(deployment)
+    }
+
+    #[graphql(
+        name = "updateDeployment",
+        guard = "RoleGuard::new(UserRole::Operator)",
+        visible = "is_operator",
+    )]
+    pub async fn update_deployment(
+        &self,
+        context: &Context<'_>,
+        deployment_data: DeploymentData,
+    ) -> Result<Deployment> {
+
+        let before = Deployment::get_by_id(&deployment_data.id)?;
+        let mut deployment = before.clone();
+
+        if let Some(id) = deployment_data.role_id {
+            check_role(deployment.person_id, Some(id))?;
+            deployment.role_id = Some(id);
+        };
+
+        if let Some(s) = deployment_data.event_name {
+            deployment.event_name = s;
+        };
+
+        if let Some(e) = deployment_data.effort {
+            deployment.effort = e;
+        };
+
+        if let Some(d) = deployment_data.start_datestamp {
+            deployment.start_datestamp = d;
+        };
+
+        if let Some(d) = deployment_data.end_date {
+            deployment.end_date = Some(d);
+        };
+
+        Deployment::check(deployment.start_datestamp, deployment.end_date, deployment.effort)?;
+
+        let deployment = deployment.update()?;
+
+        ChangeRecord::log_update(&before, &deployment, acting_user(context))?;
+
+        Ok(deployment)
+    }
+
+    #[graphql(
+        name = "deleteDeployment",
+        guard = "RoleGuard::new(UserRole::Operator)",
+        visible = "is_operator",
+    )]
+    /// Removes a deployment entered in error. Set end_date to close a deployment instead.
+    pub async fn delete_deployment(
+        &self,
+        context: &Context<'_>,
+        id: Uuid,
+    ) -> Result<Deployment> {
+
+        let deployment = Deployment::delete(id)?;
+
+        ChangeRecord::log_delete(&deployment, acting_user(context))?;
+
+        Ok(deployment)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, InputObject)]
+/// InputObject for Deployment with Option fields - only include the ones you want to update
+pub struct DeploymentData {
+    pub id: Uuid,
+    pub role_id: Option<Uuid>,
+    pub event_name: Option<String>,
+    pub effort: Option<f64>,
+    pub start_datestamp: Option<NaiveDateTime>,
+    pub end_date: Option<NaiveDateTime>,
+}
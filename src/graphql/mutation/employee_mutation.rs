@@ -0,0 +1,144 @@
+use async_graphql::*;
+use chrono::NaiveDateTime;
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+
+use crate::models::{EmployeeData, NewEmployeeData, HrGroup, HrStatePeriod, RecordedHrState, NewHrStatePeriod,
+    Person, ChangeRecord, acting_user};
+use crate::common_utils::{UserRole,
+    is_operator, RoleGuard};
+
+#[derive(Default)]
+pub struct EmployeeMutation;
+
+#[Object]
+impl EmployeeMutation {
+
+    #[graphql(
+        name = "createEmployeeData",
+        guard = "RoleGuard::new(UserRole::Operator)",
+        visible = "is_operator",
+    )]
+    /// Records a substantive classification or an acting assignment for a person
+    pub async fn create_employee_data(
+        &self,
+        context: &Context<'_>,
+        employee_data: NewEmployeeData,
+    ) -> Result<EmployeeData> {
+
+        Person::get_by_id(&employee_data.person_id)?;
+
+        EmployeeData::check(
+            None,
+            employee_data.person_id,
+            employee_data.classification,
+            employee_data.start_datestamp,
+            employee_data.end_date,
+        )?;
+
+        let created = EmployeeData::create(&employee_data)?;
+
+        ChangeRecord::log_create(&created, acting_user(context))?;
+
+        Ok(created)
+    }
+
+    #[graphql(
+        name = "updateEmployeeData",
+        guard = "RoleGuard::new(UserRole::Operator)",
+        visible = "is_operator",
+    )]
+    /// Corrects a classification, or ends it by setting end_date
+    pub async fn update_employee_data(
+        &self,
+        context: &Context<'_>,
+        employee_data: EmployeeDataUpdate,
+    ) -> Result<EmployeeData> {
+
+        let before = EmployeeData::get_by_id(&employee_data.id)?;
+        let mut updated = before.clone();
+
+        if let Some(s) = employee_data.title_en {
+            updated.title_en = s;
+        };
+
+        if let Some(s) = employee_data.title_fr {
+            updated.title_fr = s;
+        };
+
+        if let Some(g) = employee_data.hr_group {
+            updated.hr_group = g;
+        };
+
+        if let Some(l) = employee_data.hr_level {
+            updated.hr_level = l;
+        };
+
+        if let Some(d) = employee_data.start_datestamp {
+            updated.start_datestamp = d;
+        };
+
+        if let Some(d) = employee_data.end_date {
+            updated.end_date = Some(d);
+        };
+
+        EmployeeData::check(
+            Some(updated.id),
+            updated.person_id,
+            updated.classification,
+            updated.start_datestamp,
+            updated.end_date,
+        )?;
+
+        let updated = updated.update()?;
+
+        ChangeRecord::log_update(&before, &updated, acting_user(context))?;
+
+        Ok(updated)
+    }
+
+    #[graphql(
+        name = "recordHrState",
+        guard = "RoleGuard::new(UserRole::Operator)",
+        visible = "is_operator",
+    )]
+    /// Moves a person into a new HR state from start_datestamp, ending the current one.
+    /// Recording HrState::Departed also ends the person's open classifications, and is refused
+    /// if one of their classifications starts on or after the departure.
+    pub async fn record_hr_state(
+        &self,
+        context: &Context<'_>,
+        hr_state_data: NewHrStatePeriod,
+    ) -> Result<HrStatePeriod> {
+
+        Person::get_by_id(&hr_state_data.person_id)?;
+
+        let before = HrStatePeriod::get_current_by_person_id(hr_state_data.person_id)?;
+
+        let RecordedHrState { closed, created, ended } = HrStatePeriod::record(&hr_state_data)?;
+
+        if let (Some(b), Some(c)) = (&before, &closed) {
+            ChangeRecord::log_update(b, c, acting_user(context))?;
+        }
+
+        ChangeRecord::log_create(&created, acting_user(context))?;
+
+        for (b, a) in &ended {
+            ChangeRecord::log_update(b, a, acting_user(context))?;
+        }
+
+        Ok(created)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, InputObject)]
+/// InputObject for EmployeeData with Option fields - only include the ones you want to update
+pub struct EmployeeDataUpdate {
+    pub id: Uuid,
+    pub title_en: Option<String>,
+    pub title_fr: Option<String>,
+    pub hr_group: Option<HrGroup>,
+    pub hr_level: Option<i32>,
+    pub start_datestamp: Option<NaiveDateTime>,
+    pub end_date: Option<NaiveDateTime>,
+}
@@ -3,9 +3,19 @@ mod person_mutation;
 mod user_mutation;
 mod role_mutation;
 mod capability_mutation;
+mod team_mutation;
+mod org_tier_mutation;
+mod deployment_mutation;
+mod api_key_mutation;
+mod employee_mutation;
 
 pub use self::mutation::*;
 pub use self::person_mutation::*;
 pub use self::user_mutation::*;
 pub use self::role_mutation::*;
-pub use self::capability_mutation::*;
\ No newline at end of file
+pub use self::capability_mutation::*;
+pub use self::team_mutation::*;
+pub use self::org_tier_mutation::*;
+pub use self::deployment_mutation::*;
+pub use self::api_key_mutation::*;
+pub use self::employee_mutation::*;
\ No newline at end of file
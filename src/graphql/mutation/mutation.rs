@@ -4,7 +4,8 @@ use async_graphql::*;
 // use crate::kafka::send_message;
 
 use crate::graphql::mutation::{UserMutation, PersonMutation, 
-    RoleMutation, CapabilityMutation};
+    RoleMutation, CapabilityMutation, TeamMutation, OrgTierMutation, DeploymentMutation, ApiKeyMutation,
+    EmployeeMutation};
 
 #[derive(MergedObject, Default)]
 pub struct Mutation(
@@ -12,4 +13,9 @@ pub struct Mutation(
     PersonMutation,
     RoleMutation,
     CapabilityMutation,
+    TeamMutation,
+    OrgTierMutation,
+    DeploymentMutation,
+    ApiKeyMutation,
+    EmployeeMutation,
 );
\ No newline at end of file
@@ -0,0 +1,102 @@
+use async_graphql::*;
+use uuid::Uuid;
+
+use crate::models::{OrgTier, NewOrgTier, OrgOwnership, Person, ChangeRecord, acting_user};
+use crate::common_utils::{UserRole,
+    is_operator, RoleGuard};
+
+#[derive(Default)]
+pub struct OrgTierMutation;
+
+#[Object]
+impl OrgTierMutation {
+
+    #[graphql(
+        name = "createOrgTier",
+        guard = "RoleGuard::new(UserRole::Operator)",
+        visible = "is_operator",
+    )]
+    /// Creates an org tier. tier_level must be one deeper than parent_tier,
+    /// or the top tier level if there is no parent.
+    pub async fn create_org_tier(
+        &self,
+        context: &Context<'_>,
+        org_tier_data: NewOrgTier,
+    ) -> Result<OrgTier> {
+
+        let level = OrgTier::level_under(&org_tier_data.organization_id, org_tier_data.parent_tier)?;
+
+        if org_tier_data.tier_level != level {
+            return Err(Error::new(format!("tier_level must be {} for this parent_tier", level)));
+        }
+
+        let org_tier = OrgTier::create(&org_tier_data)?;
+
+        ChangeRecord::log_create(&org_tier, acting_user(context))?;
+
+        Ok(org_tier)
+    }
+
+    #[graphql(
+        name = "moveOrgTier",
+        guard = "RoleGuard::new(UserRole::Operator)",
+        visible = "is_operator",
+    )]
+    /// Re-parents an org tier and its descendants under parent_tier_id.
+    /// Leave parent_tier_id empty to make it a top tier.
+    /// History records the move on the tier itself; descendants' tier_level shifts follow from it.
+    pub async fn move_org_tier(
+        &self,
+        context: &Context<'_>,
+        id: Uuid,
+        parent_tier_id: Option<Uuid>,
+    ) -> Result<OrgTier> {
+
+        let before = OrgTier::get_by_id(&id)?;
+
+        let org_tier = OrgTier::move_to(id, parent_tier_id)?;
+
+        ChangeRecord::log_update(&before, &org_tier, acting_user(context))?;
+
+        Ok(org_tier)
+    }
+
+    #[graphql(
+        name = "assignOrgTierOwner",
+        guard = "RoleGuard::new(UserRole::Operator)",
+        visible = "is_operator",
+    )]
+    /// Makes person_id the owner of the org tier.
+    /// The previous ownership is retired rather than overwritten.
+    pub async fn assign_org_tier_owner(
+        &self,
+        context: &Context<'_>,
+        org_tier_id: Uuid,
+        person_id: Uuid,
+    ) -> Result<OrgOwnership> {
+
+        let org_tier = OrgTier::get_by_id(&org_tier_id)?;
+
+        if org_tier.retired_at.is_some() {
+            return Err(Error::new("Cannot assign an owner to a retired org tier"));
+        }
+
+        Person::get_by_id(&person_id)?;
+
+        let actor = acting_user(context);
+
+        let current = OrgOwnership::get_current_by_org_tier_ids(&[org_tier_id])?;
+
+        let ownership = OrgOwnership::transfer(org_tier_id, person_id)?;
+
+        for before in current {
+            let after = OrgOwnership::get_by_id(before.id)?;
+
+            ChangeRecord::log_update(&before, &after, actor)?;
+        }
+
+        ChangeRecord::log_create(&ownership, actor)?;
+
+        Ok(ownership)
+    }
+}
@@ -3,9 +3,9 @@ use chrono::NaiveDateTime;
 use serde::{Serialize, Deserialize};
 use uuid::Uuid;
 
-use crate::models::{Person, NewPerson};
+use crate::models::{Person, NewPerson, ContactData, NewContactData, ContactType, ChangeRecord, acting_user};
 use crate::common_utils::{UserRole,
-    is_operator, RoleGuard};
+    is_operator, RoleGuard, PersonalDataGuard};
 use crate::schema::persons;
 // use rdkafka::producer::FutureProducer;
 // use crate::kafka::send_message;
@@ -25,12 +25,14 @@ impl PersonMutation {
     )]
     pub async fn create_person(
         &self,
-        _context: &Context<'_>,
+        context: &Context<'_>,
         data: NewPerson,
     ) -> Result<Person> {
         
         let person = Person::create(&data)?;
 
+        ChangeRecord::log_create(&person, acting_user(context))?;
+
         Ok(person)
     }
 
@@ -41,11 +43,12 @@ impl PersonMutation {
     )]
     pub async fn update_person(
         &self,
-        _context: &Context<'_>,
+        context: &Context<'_>,
         data: PersonData,
     ) -> Result<Person> {
         
-        let mut person = Person::get_by_id(&data.id)?;
+        let before = Person::get_by_id(&data.id)?;
+        let mut person = before.clone();
 
         if let Some(id) = data.user_id {
             person.user_id = id;
@@ -59,51 +62,169 @@ impl PersonMutation {
             person.given_name = s;
         };
 
-        if let Some(s) = data.email {
-            person.email = s;
+        if let Some(s) = data.organization_id {
+            person.organization_id = s;
         };
 
-        if let Some(s) = data.phone {
-            person.phone = s;
+        if let Some(s) = data.peoplesoft_id {
+            person.peoplesoft_id = s;
         };
 
-        if let Some(s) = data.work_address {
-            person.work_address = s;
+        if let Some(s) = data.orcid_id {
+            person.orcid_id = s;
         };
 
-        if let Some(s) = data.city {
-            person.city = s;
+        
+        if let Some(s) = data.retired_at {
+            person.retired_at = Some(s);
         };
 
-        if let Some(s) = data.province {
-            person.province = s;
+        let person = person.update()?;
+
+        ChangeRecord::log_update(&before, &person, acting_user(context))?;
+
+        Ok(person)
+    }
+
+    #[graphql(
+        name = "createContactData",
+        guard = "RoleGuard::new(UserRole::Operator)",
+        visible = "is_operator",
+    )]
+    /// Adds a contact for a person. A current primary contact replaces the person's existing one.
+    /// Returns contact details, so needs an access rationale as reading them does.
+    pub async fn create_contact_data(
+        &self,
+        context: &Context<'_>,
+        contact_data: NewContactData,
+    ) -> Result<ContactData> {
+
+        let person = Person::get_by_id(&contact_data.person_id)?;
+
+        PersonalDataGuard::new(person.id, person.user_id).check(context).await?;
+
+        ContactData::check(
+            contact_data.email.as_deref(),
+            contact_data.phone.as_deref(),
+            contact_data.work_address.as_deref(),
+            contact_data.start_datestamp,
+            contact_data.end_date,
+        )?;
+
+        let replaced = ContactData::get_primary_by_person_ids(&[contact_data.person_id])?;
+
+        let created = ContactData::create(&contact_data)?;
+
+        if created.is_primary && created.end_date.is_none() {
+            log_primary_replaced(context, replaced)?;
+        }
+
+        ChangeRecord::log_create(&created, acting_user(context))?;
+
+        Ok(created)
+    }
+
+    #[graphql(
+        name = "updateContactData",
+        guard = "RoleGuard::new(UserRole::Operator)",
+        visible = "is_operator",
+    )]
+    /// Corrects a contact, ends it by setting end_date or makes it the person's primary contact.
+    /// Returns contact details, so needs an access rationale as reading them does.
+    pub async fn update_contact_data(
+        &self,
+        context: &Context<'_>,
+        contact_data: ContactDataUpdate,
+    ) -> Result<ContactData> {
+
+        let before = ContactData::get_by_id(&contact_data.id)?;
+
+        let person = Person::get_by_id(&before.person_id)?;
+
+        PersonalDataGuard::new(person.id, person.user_id).check(context).await?;
+        let mut updated = before.clone();
+
+        if let Some(t) = contact_data.contact_type {
+            updated.contact_type = t;
         };
 
-        if let Some(s) = data.postal_code {
-            person.postal_code = s;
+        if let Some(s) = contact_data.email {
+            updated.email = Some(s);
         };
 
-        if let Some(s) = data.organization_id {
-            person.organization_id = s;
+        if let Some(s) = contact_data.phone {
+            updated.phone = Some(s);
         };
 
-        if let Some(s) = data.peoplesoft_id {
-            person.peoplesoft_id = s;
+        if let Some(s) = contact_data.work_address {
+            updated.work_address = Some(s);
         };
 
-        if let Some(s) = data.orcid_id {
-            person.orcid_id = s;
+        if let Some(s) = contact_data.city {
+            updated.city = Some(s);
         };
 
-        
-        if let Some(s) = data.retired_at {
-            person.retired_at = Some(s);
+        if let Some(s) = contact_data.province {
+            updated.province = Some(s);
         };
 
-        Ok(person)
+        if let Some(s) = contact_data.postal_code {
+            updated.postal_code = Some(s);
+        };
+
+        if let Some(s) = contact_data.country {
+            updated.country = Some(s);
+        };
+
+        if let Some(b) = contact_data.is_primary {
+            updated.is_primary = b;
+        };
+
+        if let Some(d) = contact_data.start_datestamp {
+            updated.start_datestamp = d;
+        };
+
+        if let Some(d) = contact_data.end_date {
+            updated.end_date = Some(d);
+        };
+
+        ContactData::check(
+            updated.email.as_deref(),
+            updated.phone.as_deref(),
+            updated.work_address.as_deref(),
+            updated.start_datestamp,
+            updated.end_date,
+        )?;
+
+        let replaced: Vec<ContactData> = ContactData::get_primary_by_person_ids(&[updated.person_id])?
+            .into_iter()
+            .filter(|c| c.id != updated.id)
+            .collect();
+
+        let updated = updated.update()?;
+
+        if updated.is_primary && updated.end_date.is_none() {
+            log_primary_replaced(context, replaced)?;
+        }
+
+        ChangeRecord::log_update(&before, &updated, acting_user(context))?;
+
+        Ok(updated)
     }
 }
 
+/// Records that contacts stopped being primary when another took their place
+fn log_primary_replaced(context: &Context<'_>, replaced: Vec<ContactData>) -> Result<()> {
+    for before in replaced {
+        let mut after = before.clone();
+        after.is_primary = false;
+
+        ChangeRecord::log_update(&before, &after, acting_user(context))?;
+    }
+
+    Ok(())
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize, Queryable, Identifiable, Insertable, AsChangeset, InputObject)]
 #[graphql(complex)]
 #[diesel(table_name = persons)]
@@ -114,6 +235,20 @@ pub struct PersonData {
     pub family_name: Option<String>,
     pub given_name: Option<String>,
 
+    pub organization_id: Option<Uuid>, // Organization 
+    pub peoplesoft_id: Option<String>,
+    pub orcid_id: Option<String>,
+
+    pub updated_at: Option<NaiveDateTime>,
+    pub retired_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, InputObject)]
+/// InputObject for ContactData with Option fields - only include the ones you want to update
+pub struct ContactDataUpdate {
+    pub id: Uuid,
+    pub contact_type: Option<ContactType>,
+
     pub email: Option<String>,
     pub phone: Option<String>,
     pub work_address: Option<String>,
@@ -122,10 +257,7 @@ pub struct PersonData {
     pub postal_code: Option<String>,
     pub country: Option<String>,
 
-    pub organization_id: Option<Uuid>, // Organization 
-    pub peoplesoft_id: Option<String>,
-    pub orcid_id: Option<String>,
-
-    pub updated_at: Option<NaiveDateTime>,
-    pub retired_at: Option<NaiveDateTime>,
+    pub is_primary: Option<bool>,
+    pub start_datestamp: Option<NaiveDateTime>,
+    pub end_date: Option<NaiveDateTime>,
 }
\ No newline at end of file
@@ -3,7 +3,7 @@ use chrono::NaiveDateTime;
 use serde::{Serialize, Deserialize};
 use uuid::Uuid;
 
-use crate::models::{Role, NewRole};
+use crate::models::{Role, NewRole, ChangeRecord, acting_user};
 use crate::common_utils::{UserRole,
     is_operator, RoleGuard};
 use crate::schema::roles;
@@ -25,12 +25,14 @@ impl RoleMutation {
     )]
     pub async fn create_role(
         &self,
-        _context: &Context<'_>,
+        context: &Context<'_>,
         role_data: NewRole,
     ) -> Result<Role> {
         
         let role = Role::create(&role_data)?;
 
+        ChangeRecord::log_create(&role, acting_user(context))?;
+
         Ok(role)
     }
 
@@ -41,11 +43,12 @@ impl RoleMutation {
     )]
     pub async fn update_role(
         &self,
-        _context: &Context<'_>,
+        context: &Context<'_>,
         role_data: RoleData,
     ) -> Result<Role> {
         
-        let mut role = Role::get_by_id(&role_data.id)?;
+        let before = Role::get_by_id(&role_data.id)?;
+        let mut role = before.clone();
 
         if let Some(id) = role_data.active {
             role.active = id;
@@ -59,6 +62,10 @@ impl RoleMutation {
             role.end_date = Some(s);
         };
 
+        let role = role.update()?;
+
+        ChangeRecord::log_update(&before, &role, acting_user(context))?;
+
         Ok(role)
     }
 }
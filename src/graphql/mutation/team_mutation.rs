@@ -0,0 +1,188 @@
+use async_graphql::*;
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+
+use crate::models::{Team, NewTeam, TeamOwnership, OrgTier, Person, SkillDomain,
+    ChangeRecord, acting_user};
+use crate::common_utils::{UserRole,
+    is_operator, RoleGuard};
+
+#[derive(Default)]
+pub struct TeamMutation;
+
+#[Object]
+impl TeamMutation {
+
+    #[graphql(
+        name = "createTeam",
+        guard = "RoleGuard::new(UserRole::Operator)",
+        visible = "is_operator",
+    )]
+    pub async fn create_team(
+        &self,
+        context: &Context<'_>,
+        team_data: NewTeam,
+    ) -> Result<Team> {
+
+        let org_tier = OrgTier::get_by_id(&team_data.org_tier_id)?;
+
+        if org_tier.organization_id != team_data.organization_id {
+            return Err(Error::new("Org tier must belong to the team's organization"));
+        }
+
+        let team = Team::create(&team_data)?;
+
+        ChangeRecord::log_create(&team, acting_user(context))?;
+
+        Ok(team)
+    }
+
+    #[graphql(
+        name = "updateTeam",
+        guard = "RoleGuard::new(UserRole::Operator)",
+        visible = "is_operator",
+    )]
+    pub async fn update_team(
+        &self,
+        context: &Context<'_>,
+        team_data: TeamData,
+    ) -> Result<Team> {
+
+        let before = Team::get_by_id(&team_data.id)?;
+        let mut team = before.clone();
+
+        if let Some(s) = team_data.name_en {
+            team.name_en = s;
+        };
+
+        if let Some(s) = team_data.name_fr {
+            team.name_fr = s;
+        };
+
+        if let Some(s) = team_data.description_en {
+            team.description_en = s;
+        };
+
+        if let Some(s) = team_data.description_fr {
+            team.description_fr = s;
+        };
+
+        if let Some(d) = team_data.primary_domain {
+            team.primary_domain = d;
+        };
+
+        if let Some(id) = team_data.org_tier_id {
+            let org_tier = OrgTier::get_by_id(&id)?;
+
+            if org_tier.organization_id != team.organization_id {
+                return Err(Error::new("Org tier must belong to the team's organization"));
+            }
+
+            team.org_tier_id = id;
+        };
+
+        team.updated_at = chrono::Utc::now().naive_utc();
+
+        let team = team.update()?;
+
+        ChangeRecord::log_update(&before, &team, acting_user(context))?;
+
+        Ok(team)
+    }
+
+    #[graphql(
+        name = "retireTeam",
+        guard = "RoleGuard::new(UserRole::Operator)",
+        visible = "is_operator",
+    )]
+    /// Sets retired_at on the team and closes its current ownership
+    pub async fn retire_team(
+        &self,
+        context: &Context<'_>,
+        id: Uuid,
+    ) -> Result<Team> {
+
+        let before = Team::get_by_id(&id)?;
+
+        if before.retired_at.is_some() {
+            return Err(Error::new("Team is already retired"));
+        }
+
+        let open = TeamOwnership::get_current_by_team_ids(&[before.id])?;
+
+        let (team, closed) = before.retire()?;
+
+        let actor = acting_user(context);
+
+        ChangeRecord::log_update(&before, &team, actor)?;
+
+        for after in &closed {
+            if let Some(b) = open.iter().find(|o| o.id == after.id) {
+                ChangeRecord::log_update(b, after, actor)?;
+            }
+        }
+
+        Ok(team)
+    }
+
+    #[graphql(
+        name = "assignTeamOwner",
+        guard = "RoleGuard::new(UserRole::Operator)",
+        visible = "is_operator",
+    )]
+    /// Makes person_id the owner of the team.
+    /// The previous ownership is closed with an end_date rather than overwritten.
+    pub async fn assign_team_owner(
+        &self,
+        context: &Context<'_>,
+        team_id: Uuid,
+        person_id: Uuid,
+    ) -> Result<TeamOwnership> {
+
+        let team = Team::get_by_id(&team_id)?;
+
+        if team.retired_at.is_some() {
+            return Err(Error::new("Cannot assign an owner to a retired team"));
+        }
+
+        // Confirm the person exists before closing the current ownership
+        Person::get_by_id(&person_id)?;
+
+        let actor = acting_user(context);
+
+        let open = TeamOwnership::get_current_by_team_ids(&[team_id])?;
+
+        let ownership = TeamOwnership::transfer(team_id, person_id)?;
+
+        log_closed_ownerships(open, actor)?;
+
+        ChangeRecord::log_create(&ownership, actor)?;
+
+        Ok(ownership)
+    }
+}
+
+/// Records the end_date set on ownerships that were open before a transfer or retirement
+fn log_closed_ownerships(open: Vec<TeamOwnership>, actor: Option<Uuid>) -> Result<()> {
+    for before in open {
+        let after = TeamOwnership::get_by_id(&before.id)?;
+
+        ChangeRecord::log_update(&before, &after, actor)?;
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, InputObject)]
+/// InputObject for Team with Option fields - only include the ones you want to update
+pub struct TeamData {
+    pub id: Uuid,
+    pub org_tier_id: Option<Uuid>,
+    pub primary_domain: Option<SkillDomain>,
+
+    pub name_en: Option<String>,
+    pub name_fr: Option<String>,
+
+    pub description_en: Option<String>,
+    pub description_fr: Option<String>,
+}
@@ -1,10 +1,15 @@
 use std::str::FromStr;
+use std::sync::Arc;
 
 use async_graphql::*;
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 use crate::models::{InsertableUser, LoginQuery,
-    User, UserData, create_token, decode_token,
-    verify_password, UserUpdate, hash_password};
+    User, UserData, create_token,
+    verify_password, UserUpdate, hash_password, ChangeRecord, acting_user,
+    RefreshToken, RevokedToken, AuthContext, PasswordChange, check_password_policy,
+    needs_rehash, EmailVerification, PasswordResetToken, ExternalIdentity, SignUpData, Granularity};
+use crate::mailer::Mailer;
 use crate::common_utils::{UserRole,
     is_admin, RoleGuard};
 // use rdkafka::producer::FutureProducer;
@@ -16,10 +21,34 @@ pub struct UserMutation;
 #[derive(Debug, Serialize, Deserialize, SimpleObject)]
 pub struct UserResponse {
     bearer: String,
+    /// Single use token exchanged for a new bearer through refreshToken
+    refresh_token: String,
     role: String,
     email: String,
 }
 
+impl UserResponse {
+    /// Issues a new bearer for the user's session along with the given refresh token
+    fn new(user: User, session_id: Uuid, refresh_token: String) -> Result<Self> {
+        let role = UserRole::from_str(user.role.as_str())
+            .map_err(|_| Error::new("Cannot convert &str to UserRole"))?;
+
+        Ok(UserResponse {
+            bearer: create_token(user.id.to_string(), role, user.granularity, session_id),
+            refresh_token,
+            role: user.role,
+            email: user.email,
+        })
+    }
+}
+
+/// Mail failures are logged rather than returned so the change that triggered the email still succeeds
+fn send_or_log(sent: Result<()>) {
+    if let Err(e) = sent {
+        log::error!("Unable to send email: {}", e.message);
+    }
+}
+
 // Mutation Example
 
 #[Object]
@@ -82,14 +111,21 @@ impl UserMutation {
     )]
     pub async fn create_user(
         &self,
-        _context: &Context<'_>,
+        context: &Context<'_>,
         user_data: UserData,
     ) -> FieldResult<User> {
-        let new_user = InsertableUser::from(user_data);
+        check_password_policy(&user_data.password, &user_data.email)?;
+
+        let mut new_user = InsertableUser::from(user_data);
+        new_user.approved_by_user_uid = acting_user(context);
+
+        let created_user = User::create(new_user)?;
 
-        let created_user = User::create(new_user);
+        ChangeRecord::log_create(&created_user, acting_user(context))?;
 
-        created_user
+        send_or_log(EmailVerification::send(context.data::<Arc<dyn Mailer>>()?.clone(), &created_user).await);
+
+        Ok(created_user)
     }
 
     #[graphql(
@@ -99,20 +135,25 @@ impl UserMutation {
     )]
     pub async fn update_user(
         &self,
-        _context: &Context<'_>,
+        context: &Context<'_>,
         user_data: UserUpdate,
     ) -> FieldResult<User> {
-        let mut target_user = User::get_by_id(&user_data.id)?;
+        let before = User::get_by_id(&user_data.id)?;
+        let mut target_user = before.clone();
 
         if let Some(s) = user_data.name {
             target_user.name = s;
         };
 
         if let Some(s) = user_data.email {
+            if s != target_user.email {
+                target_user.email_verified_at = None;
+            }
             target_user.email = s;
         };
 
         if let Some(s) = user_data.password {
+            check_password_policy(&s, &target_user.email)?;
             target_user.hash = hash_password(&s)?.to_string();
         };
 
@@ -120,9 +161,99 @@ impl UserMutation {
             target_user.role = s;
         };
 
-        let updated_user = target_user.update();
+        if let Some(g) = user_data.granularity {
+            target_user.granularity = g;
+        };
+
+        let updated_user = target_user.update()?;
+
+        ChangeRecord::log_update(&before, &updated_user, acting_user(context))?;
+
+        // Role and granularity are carried in access tokens, so sign the user out everywhere
+        // rather than let existing tokens keep the old grant until they are refreshed
+        if updated_user.role != before.role
+            || updated_user.granularity != before.granularity
+            || updated_user.hash != before.hash {
+            RefreshToken::revoke_all_for_user(updated_user.id)?;
+            RevokedToken::revoke_all_access_tokens(updated_user.id)?;
+        }
+
+        if updated_user.email != before.email {
+            send_or_log(EmailVerification::send(context.data::<Arc<dyn Mailer>>()?.clone(), &updated_user).await);
+        }
+
+        Ok(updated_user)
+    }
+
+    #[graphql(name = "signUp")]
+    /// Registers a new user with UserRole::User and emails them a verification code.
+    /// The user can't sign in until an Administrator approves them with approveUser.
+    /// Always returns true so the response doesn't reveal which addresses have accounts.
+    pub async fn sign_up(
+        &self,
+        context: &Context<'_>,
+        input: SignUpData,
+    ) -> Result<bool> {
+        check_password_policy(&input.password, &input.email)?;
+
+        if User::get_by_email(&input.email).is_ok() {
+            return Ok(true);
+        }
+
+        let created_user = User::create(InsertableUser::from(input))?;
+
+        ChangeRecord::log_create(&created_user, Some(created_user.id))?;
+
+        send_or_log(EmailVerification::send(context.data::<Arc<dyn Mailer>>()?.clone(), &created_user).await);
 
-        updated_user
+        Ok(true)
+    }
+
+    #[graphql(
+        name = "approveUser",
+        guard = "RoleGuard::new(UserRole::Admin)",
+        visible = "is_admin",
+    )]
+    /// Approves a self-registered user so they can sign in, optionally granting
+    /// more or less than the Granularity::Anonymized they registered with
+    pub async fn approve_user(
+        &self,
+        context: &Context<'_>,
+        id: Uuid,
+        granularity: Option<Granularity>,
+    ) -> Result<User> {
+
+        let before = User::get_by_id(&id)?;
+
+        let mut approved = User::approve(&id, acting_user(context))?;
+
+        if let Some(g) = granularity {
+            approved.granularity = g;
+            approved = approved.update()?;
+        }
+
+        ChangeRecord::log_update(&before, &approved, acting_user(context))?;
+
+        Ok(approved)
+    }
+
+    #[graphql(
+        name = "rejectUser",
+        guard = "RoleGuard::new(UserRole::Admin)",
+        visible = "is_admin",
+    )]
+    /// Removes a self-registered user waiting for approval. Approved users can't be rejected.
+    pub async fn reject_user(
+        &self,
+        context: &Context<'_>,
+        id: Uuid,
+    ) -> Result<User> {
+
+        let rejected = User::delete_pending(&id)?;
+
+        ChangeRecord::log_delete(&rejected, acting_user(context))?;
+
+        Ok(rejected)
     }
 
     pub async fn sign_in(
@@ -136,27 +267,302 @@ impl UserMutation {
 
             if let Ok(matching) = verify_password(user.hash.to_string(), &input.password) {
                 if matching {
-                    let role = UserRole::from_str(user.role.as_str())
-                        .expect("Cannot convert &str to UserRole");
+                    if !user.is_approved() {
+                        return Err(Error::new("This account is waiting for approval by an Administrator"));
+                    }
+
+                    // Upgrade hashes made with the shared legacy salt or old parameters
+                    if needs_rehash(&user.hash) {
+                        User::set_hash(user.id, hash_password(&input.password)?.as_str())?;
+                    }
 
                     // Return the token which would be accepted by the Epicenter 
                     // app and used to authenticate actions
-                    let token = create_token(user.id.to_string(), role);
+                    let (issued, refresh_token) = RefreshToken::issue(user.id)?;
 
-                    let res = UserResponse {
-                        email: user.email.to_owned(),
-                        bearer: token.to_owned(),
-                        role: user.role,
-                    };
+                    return UserResponse::new(user, issued.family_id, refresh_token);
+                }
+            }
+        }
 
+        Err(Error::new("Can't authenticate a user"))
+    }
 
-                    println!("JWT: {}\nData{:?}", &token, decode_token(&token));
+    #[graphql(name = "signInWithOidc")]
+    /// Signs in with an RS256 ID or access token from the OIDC provider.
+    /// Creates a User on first sign in, or links an existing one with the same verified email,
+    /// and keeps the user's role in step with the provider's role claim.
+    pub async fn sign_in_with_oidc(
+        &self,
+        _context: &Context<'_>,
+        token: String,
+    ) -> Result<UserResponse> {
+
+        let identity = ExternalIdentity::from_token(&token).await?;
+        let external_id = identity.external_id();
+        let now = chrono::Utc::now().naive_utc();
+
+        let user = match User::get_by_external_id(&external_id)? {
+            Some(user) => user,
+            None => {
+                let email = identity.email.clone()
+                    .ok_or_else(|| Error::new("Token has no email claim"))?;
+
+                match User::get_by_email(&email).ok() {
+                    Some(existing) => {
+                        // Only link to an account the provider has proven the user controls,
+                        // and not to a self-registration nobody has approved yet
+                        if !identity.email_verified || existing.external_id.is_some() || !existing.is_approved() {
+                            return Err(Error::new("A user with this email already exists and can't be linked"));
+                        }
+
+                        let mut linked = existing.clone();
+                        linked.external_id = Some(external_id);
+                        linked.email_verified_at = linked.email_verified_at.or(Some(now));
+
+                        let linked = linked.update()?;
+
+                        ChangeRecord::log_update(&existing, &linked, Some(linked.id))?;
+
+                        linked
+                    },
+                    None => {
+                        let created = User::create(InsertableUser {
+                            // No password, the user can only sign in through the provider
+                            hash: String::new(),
+                            name: identity.name.clone().unwrap_or_else(|| email.clone()),
+                            email,
+                            role: identity.role.to_string(),
+                            access_level: "detailed".to_owned(),
+                            created_at: now,
+                            updated_at: now,
+                            access_key: "".to_owned(),
+                            approved_by_user_uid: None,
+                            email_verified_at: identity.email_verified.then_some(now),
+                            external_id: Some(external_id),
+                            // The provider vouches for its users
+                            approved_at: Some(now),
+                            granularity: Granularity::Identifiable,
+                        })?;
+
+                        ChangeRecord::log_create(&created, Some(created.id))?;
 
-                    return Ok(res);
+                        created
+                    },
                 }
+            },
+        };
+
+        let role = identity.role.to_string();
+
+        // The provider's role claim wins over roles assigned in the app unless OIDC_SYNC_ROLE=false
+        let user = if identity.sync_role && user.role != role {
+            let before = user.clone();
+            let mut updated = user;
+            updated.role = role;
+
+            let updated = updated.update()?;
+
+            ChangeRecord::log_update(&before, &updated, Some(updated.id))?;
+
+            updated
+        } else {
+            user
+        };
+
+        let (issued, refresh_token) = RefreshToken::issue(user.id)?;
+
+        UserResponse::new(user, issued.family_id, refresh_token)
+    }
+
+    #[graphql(name = "changePassword")]
+    /// Changes the signed in user's password. Signs out every other session and returns
+    /// a new bearer and refresh token for this one.
+    pub async fn change_password(
+        &self,
+        context: &Context<'_>,
+        input: PasswordChange,
+    ) -> Result<UserResponse> {
+
+        let user_id = AuthContext::from_ctx(context)?.user_id;
+
+        let before = User::get_by_id(&user_id)?;
+
+        if !verify_password(before.hash.to_string(), &input.current_password)? {
+            return Err(Error::new("Current password is incorrect"));
+        }
+
+        if input.new_password == input.current_password {
+            return Err(Error::new("New password must be different from the current password"));
+        }
+
+        check_password_policy(&input.new_password, &before.email)?;
+
+        let mut user = before.clone();
+        user.hash = hash_password(&input.new_password)?.to_string();
+
+        let user = user.update()?;
+
+        ChangeRecord::log_update(&before, &user, Some(user_id))?;
+
+        RefreshToken::revoke_all_for_user(user_id)?;
+        RevokedToken::revoke_all_access_tokens(user_id)?;
+
+        let (issued, refresh_token) = RefreshToken::issue(user_id)?;
+
+        UserResponse::new(user, issued.family_id, refresh_token)
+    }
+
+    #[graphql(name = "requestPasswordReset")]
+    /// Emails a password reset token if a user has this email address.
+    /// Always returns true so the response doesn't reveal which addresses have accounts.
+    pub async fn request_password_reset(
+        &self,
+        context: &Context<'_>,
+        email: String,
+    ) -> Result<bool> {
+
+        // Users who sign in through the OIDC provider manage their password there
+        if let Some(user) = User::get_by_email(&email).ok().filter(|u| u.external_id.is_none()) {
+            send_or_log(PasswordResetToken::send(context.data::<Arc<dyn Mailer>>()?.clone(), &user).await);
+        }
+
+        Ok(true)
+    }
+
+    #[graphql(name = "resetPassword")]
+    /// Sets a new password using a token from requestPasswordReset and signs out every session.
+    /// Also confirms the email address, since the token was sent to it.
+    pub async fn reset_password(
+        &self,
+        _context: &Context<'_>,
+        token: String,
+        new_password: String,
+    ) -> Result<bool> {
+
+        let (reset, before) = PasswordResetToken::check(&token)?;
+
+        check_password_policy(&new_password, &before.email)?;
+
+        // A concurrent reset with the same token finds nothing to delete
+        if PasswordResetToken::delete(reset.id)? == 0 {
+            return Err(Error::new("Invalid password reset token"));
+        }
+
+        let mut user = before.clone();
+        user.hash = hash_password(&new_password)?.to_string();
+        user.email_verified_at = user.email_verified_at.or(Some(chrono::Utc::now().naive_utc()));
+
+        let user = user.update()?;
+
+        ChangeRecord::log_update(&before, &user, Some(user.id))?;
+
+        RefreshToken::revoke_all_for_user(user.id)?;
+        RevokedToken::revoke_all_access_tokens(user.id)?;
+
+        Ok(true)
+    }
+
+    #[graphql(name = "verifyEmail")]
+    /// Confirms a user's email address with the code sent to it
+    pub async fn verify_email(
+        &self,
+        _context: &Context<'_>,
+        code: String,
+    ) -> Result<bool> {
+
+        let before = EmailVerification::redeem(&code)?;
+
+        let mut user = before.clone();
+        user.email_verified_at = Some(chrono::Utc::now().naive_utc());
+
+        let user = user.update()?;
+
+        ChangeRecord::log_update(&before, &user, Some(user.id))?;
+
+        Ok(true)
+    }
+
+    #[graphql(name = "requestEmailVerification")]
+    /// Sends the signed in user a new email verification code, replacing any earlier one
+    pub async fn request_email_verification(
+        &self,
+        context: &Context<'_>,
+    ) -> Result<bool> {
+
+        let user_id = AuthContext::from_ctx(context)?.user_id;
+
+        let user = User::get_by_id(&user_id)?;
+
+        if user.email_verified_at.is_some() {
+            return Err(Error::new("Email address is already verified"));
+        }
+
+        EmailVerification::send(context.data::<Arc<dyn Mailer>>()?.clone(), &user).await?;
+
+        Ok(true)
+    }
+
+    #[graphql(name = "refreshToken")]
+    /// Exchanges a refresh token for a new bearer and refresh token.
+    /// Each refresh token can be used once. Reusing one signs out every session started
+    /// from the same sign in.
+    pub async fn refresh_token(
+        &self,
+        _context: &Context<'_>,
+        refresh_token: String,
+    ) -> Result<UserResponse> {
+
+        let (rotated, refresh_token) = RefreshToken::rotate(&refresh_token)?;
+
+        let user = User::get_by_id(&rotated.user_id)?;
+
+        UserResponse::new(user, rotated.family_id, refresh_token)
+    }
+
+    #[graphql(name = "signOut")]
+    /// Revokes the bearer on the request and, if given, the refresh token issued with it
+    pub async fn sign_out(
+        &self,
+        context: &Context<'_>,
+        refresh_token: Option<String>,
+    ) -> Result<bool> {
+
+        let AuthContext { user_id, exp, jti, .. } = AuthContext::from_ctx(context)?;
+
+        RevokedToken::revoke_access_token(jti, user_id, exp)?;
+
+        if let Some(token) = refresh_token {
+            match RefreshToken::get_by_token(&token)? {
+                Some(r) if r.user_id == user_id => {
+                    RefreshToken::revoke_family(r.family_id)?;
+                },
+                _ => return Err(Error::new("Invalid refresh token")),
             }
         }
 
-        Err(Error::new("Can't authenticate a user"))
+        Ok(true)
+    }
+
+    #[graphql(
+        name = "revokeUserSessions",
+        guard = "RoleGuard::new(UserRole::Admin)",
+        visible = "is_admin",
+    )]
+    /// Signs a user out everywhere by revoking their refresh tokens and every bearer
+    /// issued to them so far. Returns the number of refresh tokens revoked.
+    pub async fn revoke_user_sessions(
+        &self,
+        _context: &Context<'_>,
+        user_id: Uuid,
+    ) -> Result<i32> {
+
+        User::get_by_id(&user_id)?;
+
+        let revoked = RefreshToken::revoke_all_for_user(user_id)?;
+
+        RevokedToken::revoke_all_access_tokens(user_id)?;
+
+        Ok(revoked as i32)
     }
 }
\ No newline at end of file
@@ -0,0 +1,335 @@
+use async_graphql::*;
+use async_graphql::connection::{self, Connection, Edge, OpaqueCursor};
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::config_variables::{DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+
+/// Position of a row in the (created_at, id) ordering shared by every paginated list.
+/// The id breaks ties between rows created in the same instant so cursors stay stable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Keyset {
+    pub created_at: NaiveDateTime,
+    pub id: Uuid,
+}
+
+/// Opaque (base64) cursor wrapping a Keyset
+pub type KeysetCursor = OpaqueCursor<Keyset>;
+
+/// Relay connection ordered by (created_at, id) with a totalCount field
+pub type KeysetConnection<T> = Connection<KeysetCursor, T, ConnectionFields>;
+
+#[derive(Debug, Clone, SimpleObject)]
+/// Additional fields added to every connection
+pub struct ConnectionFields {
+    /// Total number of rows in the list, independent of the page requested
+    pub total_count: i64,
+}
+
+/// Implemented by models that can be paginated by Keyset
+pub trait Keyed {
+    fn keyset(&self) -> Keyset;
+}
+
+/// Describes the slice of rows the model layer should load.
+/// When backward is true, rows are returned newest first and the caller reverses them.
+#[derive(Debug, Clone, Copy)]
+pub struct PageRequest {
+    pub after: Option<Keyset>,
+    pub before: Option<Keyset>,
+    pub limit: i64,
+    pub backward: bool,
+}
+
+/// Resolves first/after/last/before arguments into a KeysetConnection.
+/// `load` receives a PageRequest asking for one more row than the page size so
+/// we can tell whether there is another page without a second query.
+pub async fn paginate<T, L, C>(
+    after: Option<String>,
+    before: Option<String>,
+    first: Option<i32>,
+    last: Option<i32>,
+    count: C,
+    load: L,
+) -> Result<KeysetConnection<T>>
+where
+    T: OutputType + Keyed,
+    L: FnOnce(&PageRequest) -> Result<Vec<T>>,
+    C: FnOnce() -> Result<i64>,
+{
+    connection::query(
+        after,
+        before,
+        first,
+        last,
+        |after: Option<KeysetCursor>, before: Option<KeysetCursor>, first, last| async move {
+            let backward = first.is_none() && last.is_some();
+
+            let page_size = first
+                .or(last)
+                .unwrap_or(DEFAULT_PAGE_SIZE)
+                .min(MAX_PAGE_SIZE);
+
+            let page = PageRequest {
+                after: after.map(|c| c.0),
+                before: before.map(|c| c.0),
+                limit: page_size as i64 + 1,
+                backward,
+            };
+
+            let mut rows = load(&page)?;
+
+            let has_more = rows.len() > page_size;
+            rows.truncate(page_size);
+
+            if backward {
+                rows.reverse();
+            }
+
+            let (has_previous_page, has_next_page) = if backward {
+                (has_more, page.before.is_some())
+            } else {
+                (page.after.is_some(), has_more)
+            };
+
+            let mut connection = Connection::with_additional_fields(
+                has_previous_page,
+                has_next_page,
+                ConnectionFields { total_count: count()? },
+            );
+
+            connection.edges.extend(
+                rows.into_iter()
+                    .map(|row| Edge::new(OpaqueCursor(row.keyset()), row)),
+            );
+
+            Ok::<_, Error>(connection)
+        },
+    )
+    .await
+}
+
+/// Builds the query for the rows of `$table` (optionally filtered) in the slice described
+/// by a PageRequest. Rows after and before the cursors are compared on (created_at, id),
+/// so rows created in the same instant are ordered by id.
+/// Each table's boxed query is its own type, so this is a macro rather than a generic fn.
+macro_rules! keyset_query {
+    ($table:ident, $page:expr $(, $filter:expr)?) => {{
+        use diesel::prelude::*;
+
+        let page: &$crate::graphql::PageRequest = $page;
+
+        let mut query: $table::BoxedQuery<'_, diesel::pg::Pg> = $table::table.into_boxed();
+
+        $( query = query.filter($filter); )?
+
+        if let Some(k) = page.after {
+            query = query.filter($table::created_at.gt(k.created_at)
+                .or($table::created_at.eq(k.created_at).and($table::id.gt(k.id))));
+        }
+
+        if let Some(k) = page.before {
+            query = query.filter($table::created_at.lt(k.created_at)
+                .or($table::created_at.eq(k.created_at).and($table::id.lt(k.id))));
+        }
+
+        query = if page.backward {
+            query.order_by(($table::created_at.desc(), $table::id.desc()))
+        } else {
+            query.order_by(($table::created_at.asc(), $table::id.asc()))
+        };
+
+        query.limit(page.limit)
+    }};
+}
+
+/// Loads the rows of keyset_query!. Expands to a `Result<Vec<_>>` expression and
+/// expects to be the body of a model's page fn.
+macro_rules! keyset_page {
+    ($table:ident, $page:expr $(, $filter:expr)?) => {{
+        use diesel::prelude::*;
+
+        let mut conn = $crate::database::connection()?;
+
+        let res = $crate::graphql::keyset_query!($table, $page $(, $filter)?)
+            .load(&mut conn)?;
+
+        Ok(res)
+    }};
+}
+
+pub(crate) use keyset_page;
+pub(crate) use keyset_query;
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::connection::CursorType;
+    use chrono::NaiveDate;
+    use diesel::debug_query;
+    use diesel::pg::Pg;
+
+    use super::*;
+    use crate::schema::*;
+
+    #[derive(Debug, Clone, SimpleObject)]
+    struct Row {
+        id: Uuid,
+        created_at: NaiveDateTime,
+    }
+
+    impl Keyed for Row {
+        fn keyset(&self) -> Keyset {
+            Keyset { created_at: self.created_at, id: self.id }
+        }
+    }
+
+    fn at(minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 1, 1).unwrap().and_hms_opt(0, minute, 0).unwrap()
+    }
+
+    fn row(id: u128, minute: u32) -> Row {
+        Row { id: Uuid::from_u128(id), created_at: at(minute) }
+    }
+
+    /// What keyset_page! asks the database for, over rows in memory
+    fn load(rows: &[Row], page: &PageRequest) -> Vec<Row> {
+        let key = |r: &Row| (r.created_at, r.id);
+
+        let mut res: Vec<Row> = rows.iter()
+            .filter(|r| page.after.is_none_or(|k| key(r) > (k.created_at, k.id)))
+            .filter(|r| page.before.is_none_or(|k| key(r) < (k.created_at, k.id)))
+            .cloned()
+            .collect();
+
+        res.sort_by_key(key);
+
+        if page.backward {
+            res.reverse();
+        }
+
+        res.truncate(page.limit as usize);
+        res
+    }
+
+    async fn page(
+        rows: &[Row],
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<KeysetConnection<Row>> {
+        paginate(after, before, first, last, || Ok(rows.len() as i64), |page| Ok(load(rows, page))).await
+    }
+
+    fn ids(connection: &KeysetConnection<Row>) -> Vec<u128> {
+        connection.edges.iter().map(|e| e.node.id.as_u128()).collect()
+    }
+
+    fn cursor(connection: &KeysetConnection<Row>, index: usize) -> Option<String> {
+        Some(connection.edges[index].cursor.encode_cursor())
+    }
+
+    fn rows() -> Vec<Row> {
+        (1..=5).map(|i| row(i, i as u32)).collect()
+    }
+
+    #[actix_rt::test]
+    async fn pages_forward() {
+        let rows = rows();
+
+        let first = page(&rows, None, None, Some(2), None).await.unwrap();
+        assert_eq!(ids(&first), vec![1, 2]);
+        assert!(!first.has_previous_page && first.has_next_page);
+        assert_eq!(first.additional_fields.total_count, 5);
+
+        let second = page(&rows, cursor(&first, 1), None, Some(2), None).await.unwrap();
+        assert_eq!(ids(&second), vec![3, 4]);
+        assert!(second.has_previous_page && second.has_next_page);
+
+        let last = page(&rows, cursor(&second, 1), None, Some(2), None).await.unwrap();
+        assert_eq!(ids(&last), vec![5]);
+        assert!(last.has_previous_page && !last.has_next_page);
+    }
+
+    #[actix_rt::test]
+    async fn pages_backward() {
+        let rows = rows();
+
+        let last = page(&rows, None, None, None, Some(2)).await.unwrap();
+        assert_eq!(ids(&last), vec![4, 5]);
+        assert!(last.has_previous_page && !last.has_next_page);
+
+        let middle = page(&rows, None, cursor(&last, 0), None, Some(2)).await.unwrap();
+        assert_eq!(ids(&middle), vec![2, 3]);
+        assert!(middle.has_previous_page && middle.has_next_page);
+
+        let first = page(&rows, None, cursor(&middle, 0), None, Some(2)).await.unwrap();
+        assert_eq!(ids(&first), vec![1]);
+        assert!(!first.has_previous_page && first.has_next_page);
+    }
+
+    #[actix_rt::test]
+    async fn breaks_created_at_ties_by_id() {
+        // All created in the same instant, listed out of id order
+        let rows = vec![row(3, 0), row(1, 0), row(4, 0), row(2, 0)];
+
+        let mut seen = Vec::new();
+        let mut after = None;
+
+        loop {
+            let connection = page(&rows, after, None, Some(1), None).await.unwrap();
+            seen.extend(ids(&connection));
+
+            if !connection.has_next_page {
+                break;
+            }
+
+            after = cursor(&connection, 0);
+        }
+
+        assert_eq!(seen, vec![1, 2, 3, 4]);
+    }
+
+    #[actix_rt::test]
+    async fn caps_the_page_size() {
+        let mut limit = 0;
+
+        paginate::<Row, _, _>(None, None, Some(MAX_PAGE_SIZE as i32 * 2), None, || Ok(0), |page| {
+            limit = page.limit;
+            Ok(Vec::new())
+        }).await.unwrap();
+
+        assert_eq!(limit, MAX_PAGE_SIZE as i64 + 1);
+    }
+
+    #[actix_rt::test]
+    async fn refuses_invalid_cursors() {
+        let rows = rows();
+
+        assert!(page(&rows, Some("not a cursor".to_string()), None, Some(2), None).await.is_err());
+        assert!(page(&rows, None, Some("bm90IGpzb24".to_string()), None, Some(2)).await.is_err());
+    }
+
+    #[test]
+    fn queries_rows_after_a_cursor_in_ascending_order() {
+        let k = Keyset { created_at: at(0), id: Uuid::nil() };
+        let page = PageRequest { after: Some(k), before: None, limit: 3, backward: false };
+
+        let sql = debug_query::<Pg, _>(&keyset_query!(teams, &page)).to_string();
+
+        assert!(sql.contains(r#"WHERE (("teams"."created_at" > $1) OR (("teams"."created_at" = $2) AND ("teams"."id" > $3)))"#), "{}", sql);
+        assert!(sql.contains(r#"ORDER BY "teams"."created_at" ASC, "teams"."id" ASC LIMIT $4"#), "{}", sql);
+    }
+
+    #[test]
+    fn queries_rows_before_a_cursor_in_descending_order_with_a_filter() {
+        let k = Keyset { created_at: at(0), id: Uuid::nil() };
+        let page = PageRequest { after: None, before: Some(k), limit: 3, backward: true };
+
+        let sql = debug_query::<Pg, _>(&keyset_query!(roles, &page, roles::active.eq(true))).to_string();
+
+        assert!(sql.contains(r#"WHERE (("roles"."active" = $1) AND (("roles"."created_at" < $2) OR (("roles"."created_at" = $3) AND ("roles"."id" < $4))))"#), "{}", sql);
+        assert!(sql.contains(r#"ORDER BY "roles"."created_at" DESC, "roles"."id" DESC LIMIT $5"#), "{}", sql);
+    }
+}
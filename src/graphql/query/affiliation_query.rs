@@ -1,6 +1,7 @@
 use async_graphql::*;
 
 use crate::models::{Affiliation};
+use crate::graphql::{paginate, KeysetConnection};
 use uuid::Uuid;
 
 /*
@@ -16,10 +17,17 @@ impl AffiliationQuery {
     // Affiliations
 
     #[graphql(name = "allAffiliations")]
-    /// Returns a vector of all affiliations
-    pub async fn all_affiliations(&self, _context: &Context<'_>) -> Result<Vec<Affiliation>> {
+    /// Returns a connection of affiliations ordered by creation date
+    pub async fn all_affiliations(
+        &self,
+        _context: &Context<'_>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<KeysetConnection<Affiliation>> {
         
-        Affiliation::get_all()
+        paginate(after, before, first, last, Affiliation::count, Affiliation::get_page).await
     }
 
     #[graphql(name = "affiliationById")]
@@ -1,6 +1,7 @@
 use async_graphql::*;
 
-use crate::models::{Capability, Skill, CapabilityCount, SkillDomain, CapabilityLevel};
+use crate::models::{Capability, Skill, CapabilityCount, SkillDomain, CapabilityLevel, DeploymentExclusion};
+use crate::graphql::{paginate, KeysetConnection};
 use uuid::Uuid;
 
 //use crate::common_utils::{RoleGuard, is_admin, UserRole};
@@ -12,6 +13,20 @@ pub struct CapabilityQuery;
 impl CapabilityQuery {
 
     // Capabilities
+    /// Returns a connection of capabilities ordered by creation date
+    pub async fn all_capabilities(
+        &self, 
+        _context: &Context<'_>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<KeysetConnection<Capability>> {
+
+        paginate(after, before, first, last, Capability::count, Capability::get_page).await
+    }
+
+    #[graphql(deprecation = "Use allCapabilities with first and after")]
     /// Returns count number of Capabilities in the system
     pub async fn capabilities(
         &self, 
@@ -33,25 +48,33 @@ impl CapabilityQuery {
     }
 
     /// Accepts a String "name" and returns a vector of capabilities that 
-    /// match in EN or FR against it
+    /// match in EN or FR against it.
+    /// Use exclude_deployed to leave out people who are deployed or have been deployed heavily.
     pub async fn capabilities_by_name(
         &self, 
         _context: &Context<'_>,
         name: String,
+        exclude_deployed: Option<DeploymentExclusion>,
     ) -> Result<Vec<Capability>> {
 
-        Capability::get_by_name(&name)
+        let capabilities = Capability::get_by_name(&name)?;
+
+        Capability::exclude_deployed(capabilities, exclude_deployed.as_ref())
     }
 
-    /// Accepts a String "name" and a CapabilityLevel and returns matches against both
+    /// Accepts a String "name" and a CapabilityLevel and returns matches against both.
+    /// Use exclude_deployed to leave out people who are deployed or have been deployed heavily.
     pub async fn capabilities_by_name_and_level(
         &self, 
         _context: &Context<'_>,
         name: String,
         level: CapabilityLevel,
+        exclude_deployed: Option<DeploymentExclusion>,
     ) -> Result<Vec<Capability>> {
 
-        Capability::get_by_name_and_level(&name, level)
+        let capabilities = Capability::get_by_name_and_level(&name, level)?;
+
+        Capability::exclude_deployed(capabilities, exclude_deployed.as_ref())
     }
        
     /// Return a count of the number of people who have a capability at each level of the capability
@@ -76,13 +99,17 @@ impl CapabilityQuery {
 
     // Skills
 
-    /// Returns vector of all skills
+    /// Returns a connection of skills ordered by creation date
     pub async fn skills(
         &self, 
         _context: &Context<'_>,
-    ) -> Result<Vec<Skill>> {
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<KeysetConnection<Skill>> {
 
-        Skill::get_all()
+        paginate(after, before, first, last, Skill::count, Skill::get_page).await
     }
 
     /// Returns a specific skill by ID
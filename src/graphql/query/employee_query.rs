@@ -0,0 +1,58 @@
+use async_graphql::*;
+use chrono::NaiveDateTime;
+use uuid::Uuid;
+
+use crate::models::{EmployeeData, HrStatePeriod};
+use crate::common_utils::{RoleGuard, is_analyst, UserRole};
+
+#[derive(Default)]
+pub struct EmployeeQuery;
+
+#[Object]
+impl EmployeeQuery {
+
+    #[graphql(
+        name = "employeeDataByPersonId",
+        guard = "RoleGuard::new(UserRole::Analyst)",
+        visible = "is_analyst",
+    )]
+    /// Returns a person's substantive and acting classifications, most recent first
+    pub async fn employee_data_by_person_id(
+        &self,
+        _context: &Context<'_>,
+        person_id: Uuid,
+    ) -> Result<Vec<EmployeeData>> {
+
+        EmployeeData::get_by_person_id(person_id)
+    }
+
+    #[graphql(
+        name = "actingAssignments",
+        guard = "RoleGuard::new(UserRole::Analyst)",
+        visible = "is_analyst",
+    )]
+    /// Returns the acting assignments in effect today, or on as_of if it is given
+    pub async fn acting_assignments(
+        &self,
+        _context: &Context<'_>,
+        as_of: Option<NaiveDateTime>,
+    ) -> Result<Vec<EmployeeData>> {
+
+        EmployeeData::get_acting_on(as_of.unwrap_or_else(|| chrono::Utc::now().naive_utc()))
+    }
+
+    #[graphql(
+        name = "hrStateHistoryByPersonId",
+        guard = "RoleGuard::new(UserRole::Analyst)",
+        visible = "is_analyst",
+    )]
+    /// Returns the HR states a person has been in, most recent first
+    pub async fn hr_state_history_by_person_id(
+        &self,
+        _context: &Context<'_>,
+        person_id: Uuid,
+    ) -> Result<Vec<HrStatePeriod>> {
+
+        HrStatePeriod::get_by_person_id(person_id)
+    }
+}
@@ -0,0 +1,50 @@
+use async_graphql::*;
+use uuid::Uuid;
+
+use chrono::NaiveDateTime;
+
+use crate::models::{AccessLog, ChangeRecord};
+use crate::common_utils::{RoleGuard, is_analyst, is_admin, UserRole};
+
+#[derive(Default)]
+pub struct HistoryQuery;
+
+#[Object]
+impl HistoryQuery {
+
+    #[graphql(
+        name = "history",
+        guard = "RoleGuard::new(UserRole::Analyst)",
+        visible = "is_analyst",
+    )]
+    /// Returns the recorded changes to any entity (person, role, capability, team...)
+    /// by its id, most recent first
+    pub async fn history(
+        &self,
+        _context: &Context<'_>,
+        entity_id: Uuid,
+        #[graphql(default = 100)] limit: i32,
+    ) -> Result<Vec<ChangeRecord>> {
+
+        ChangeRecord::get_by_entity_id(entity_id, limit.max(0) as i64)
+    }
+
+    #[graphql(
+        name = "accessLogs",
+        guard = "RoleGuard::new(UserRole::Admin)",
+        visible = "is_admin",
+    )]
+    /// Returns who read identifiable personal data, whose and why, most recent first.
+    /// Filter by the user who read it, the person it belongs to, or a start time.
+    pub async fn access_logs(
+        &self,
+        _context: &Context<'_>,
+        user_id: Option<Uuid>,
+        person_id: Option<Uuid>,
+        since: Option<NaiveDateTime>,
+        #[graphql(default = 100)] limit: i32,
+    ) -> Result<Vec<AccessLog>> {
+
+        AccessLog::get_filtered(user_id, person_id, since, limit.max(0) as i64)
+    }
+}
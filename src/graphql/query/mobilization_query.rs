@@ -0,0 +1,28 @@
+use async_graphql::*;
+
+use crate::models::{MobilizationCandidate, MobilizationSearch};
+use crate::common_utils::{RoleGuard, is_analyst, UserRole};
+
+#[derive(Default)]
+pub struct MobilizationQuery;
+
+#[Object]
+impl MobilizationQuery {
+
+    #[graphql(
+        name = "mobilizationCandidates",
+        guard = "RoleGuard::new(UserRole::Analyst)",
+        visible = "is_analyst",
+    )]
+    /// Returns people to mobilize for a public health event, ranked by skill fit and availability.
+    /// People mobilized recently are left out so the same staff are not tapped repeatedly.
+    pub async fn mobilization_candidates(
+        &self,
+        _context: &Context<'_>,
+        search: MobilizationSearch,
+        #[graphql(default = 50)] limit: i32,
+    ) -> Result<Vec<MobilizationCandidate>> {
+
+        MobilizationCandidate::search(&search, limit.max(0) as usize)
+    }
+}
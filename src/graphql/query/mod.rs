@@ -9,6 +9,9 @@ mod user_query;
 mod publication_query;
 mod task;
 mod work;
+mod mobilization_query;
+mod history_query;
+mod employee_query;
 
 pub use self::query::*;
 pub use self::person_query::*;
@@ -21,4 +24,7 @@ pub use self::user_query::*;
 pub use self::publication_query::*;
 pub use self::task::*;
 pub use self::work::*;
+pub use self::mobilization_query::*;
+pub use self::history_query::*;
+pub use self::employee_query::*;
 
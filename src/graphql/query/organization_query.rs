@@ -1,7 +1,11 @@
 use async_graphql::*;
+use chrono::NaiveDateTime;
 use uuid::Uuid;
 
-use crate::models::{Organization, OrgTier};
+use crate::models::{Organization, OrgTier, OrgChartNode, OrgChartOptions, AsOf,
+    OrgDiff, OrgDiffOptions, acting_user};
+use crate::common_utils::{RoleGuard, is_analyst, UserRole};
+use crate::graphql::{paginate, KeysetConnection};
 
 /*
 use crate::common_utils::{RoleGuard, is_admin, UserRole};
@@ -16,13 +20,20 @@ impl OrganizationQuery {
     // Organizations
 
     #[graphql(name = "allOrganizations")]
-    /// Returns a vector of all organizations
-    pub async fn all_organizations(&self, _context: &Context<'_>) -> Result<Vec<Organization>> {
+    /// Returns a connection of organizations ordered by creation date
+    pub async fn all_organizations(
+        &self,
+        _context: &Context<'_>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<KeysetConnection<Organization>> {
         
-        Organization::get_all()
+        paginate(after, before, first, last, Organization::count, Organization::get_page).await
     }
 
-    #[graphql(name = "organizations")]
+    #[graphql(name = "organizations", deprecation = "Use allOrganizations with first and after")]
     /// Accepts argument "count" and returns a vector of {count} organizations
     pub async fn get_count_organizations(&self, _context: &Context<'_>, count: i64) -> Result<Vec<Organization>> {
         
@@ -52,10 +63,17 @@ impl OrganizationQuery {
     // OrgTiers
 
     #[graphql(name = "allOrgTiers")]
-    /// Returns a vector of all  org tiers
-    pub async fn all_org_tiers(&self, _context: &Context<'_>) -> Result<Vec<OrgTier>> {
+    /// Returns a connection of org tiers ordered by creation date
+    pub async fn all_org_tiers(
+        &self,
+        _context: &Context<'_>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<KeysetConnection<OrgTier>> {
 
-        OrgTier::get_all()
+        paginate(after, before, first, last, OrgTier::count, OrgTier::get_page).await
     }
 
     #[graphql(name = "orgTiersByOrgId")]
@@ -65,20 +83,30 @@ impl OrganizationQuery {
         OrgTier::get_by_org_id(&id)
     }
 
-    #[graphql(name = "OrgTiers")]
+    #[graphql(name = "OrgTiers", deprecation = "Use allOrgTiers with first and after")]
     /// Accepts argument "count" and returns a vector of {count} org tiers
     pub async fn get_org_tiers(&self, _context: &Context<'_>, count: i64) -> Result<Vec<OrgTier>> {
         OrgTier::get_count(count)
     }
 
     #[graphql(name = "orgTierById")]
+    /// Returns an org tier by id. With as_of, errors if the tier did not exist on that date.
+    /// Pass the same as_of to nested fields (owner, teams, roles) to see the org as it was.
     pub async fn org_tier_by_id(
         &self, 
         _context: &Context<'_>,
         id: Uuid,
+        as_of: Option<NaiveDateTime>,
     ) -> Result<OrgTier> {
 
-        OrgTier::get_by_id(&id)
+        let org_tier = OrgTier::get_by_id(&id)?;
+
+        match as_of {
+            Some(date) if !org_tier.in_effect_on(date) => {
+                Err(Error::new(format!("OrgTier {} did not exist on {}", id, date)))
+            },
+            _ => Ok(org_tier),
+        }
     }
 
     #[graphql(name = "orgTierByName")]
@@ -91,41 +119,37 @@ impl OrganizationQuery {
         OrgTier::get_by_name(&name)
     }
 
-    // Not done this yet
-    #[graphql(name = "orgChart")]
+    #[graphql(
+        name = "orgChart",
+        guard = "RoleGuard::new(UserRole::Analyst)",
+        visible = "is_analyst",
+    )]
+    /// Returns the org chart as rows of the d3-org-chart data model,
+    /// walking OrgTiers down from the root tier or the organization's top tiers.
+    /// Node names need an access rationale, as for Person names.
     pub async fn org_chart(
         &self,
-        _context: &Context<'_>,
-        id: Uuid
-    ) -> Result<Vec<String>> {
-
-        // Data format: name,imageUrl,area,profileUrl,office,tags,isLoggedUser,positionName,id,parentId,size
-
-        let data = Vec::new();
-
-        let _org_tiers = OrgTier::get_by_org_id(&id)?;
-
-        let _query = "query {
-            allOrgTiers {
-              nameEn
-              owner {
-                id
-                givenName
-                familyName
-                activeRoles {
-                  titleEnglish
-                }
-              }
-              parentOrganizationTier {
-                owner {
-                  id
-                }
-              }
-            }
-          }";
-
-
-        Ok(data)
+        context: &Context<'_>,
+        options: OrgChartOptions,
+    ) -> Result<Vec<OrgChartNode>> {
+
+        // Mark the signed in user's own nodes
+        OrgChartNode::build(&options, acting_user(context))
     }
 
-}
\ No newline at end of file
+    #[graphql(
+        name = "orgDiff",
+        guard = "RoleGuard::new(UserRole::Analyst)",
+        visible = "is_analyst",
+    )]
+    /// Reports changes to an organization between two dates: teams, roles, moves,
+    /// ownership and capability levels per domain. Also available as /api/org_diff.csv
+    pub async fn org_diff(
+        &self,
+        _context: &Context<'_>,
+        options: OrgDiffOptions,
+    ) -> Result<OrgDiff> {
+
+        OrgDiff::build(&options)
+    }
+}
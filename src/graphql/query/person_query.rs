@@ -1,6 +1,7 @@
 use async_graphql::*;
 
 use crate::models::{Person};
+use crate::graphql::{paginate, KeysetConnection};
 use uuid::Uuid;
 
 /*
@@ -15,17 +16,21 @@ impl PersonQuery {
 
     // People 
     #[graphql(name = "allPeople")]
-    /// Accepts argument of "count" and returns a vector of {count} persons ordered by
-    /// family name.D
+    /// Returns a connection of persons ordered by creation date.
+    /// Accepts "first" and "after" to page forward or "last" and "before" to page back.
     pub async fn all_people(
         &self, 
         _context: &Context<'_>,
-    ) -> Result<Vec<Person>> {
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<KeysetConnection<Person>> {
 
-        Person::get_all()
+        paginate(after, before, first, last, Person::count, Person::get_page).await
     }
 
-    #[graphql(name = "People")]
+    #[graphql(name = "People", deprecation = "Use allPeople with first and after")]
     /// Accepts argument of "count" and returns a vector of {count} persons ordered by
     /// family name
     pub async fn get_people(
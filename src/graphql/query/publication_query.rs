@@ -1,6 +1,7 @@
 use async_graphql::*;
 
 use crate::models::{Publication};
+use crate::graphql::{paginate, KeysetConnection};
 use uuid::Uuid;
 
 /*
@@ -16,13 +17,20 @@ impl PublicationQuery {
     // Publications
 
     #[graphql(name = "allPublications")]
-    /// Returns a vector of all publications
-    pub async fn all_publications(&self, _context: &Context<'_>) -> Result<Vec<Publication>> {
+    /// Returns a connection of publications ordered by creation date
+    pub async fn all_publications(
+        &self,
+        _context: &Context<'_>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<KeysetConnection<Publication>> {
         
-        Publication::get_all()
+        paginate(after, before, first, last, Publication::count, Publication::get_page).await
     }
 
-    #[graphql(name = "publicationCount")]
+    #[graphql(name = "publicationCount", deprecation = "Use allPublications with first and after")]
     /// Accepts argument "count" and returns a vector of {count} publications
     pub async fn get_count_publications(&self, _context: &Context<'_>, count: i64) -> Result<Vec<Publication>> {
         
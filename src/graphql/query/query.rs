@@ -2,7 +2,7 @@ use async_graphql::*;
 
 use crate::graphql::query::{CapabilityQuery, PersonQuery, TeamQuery, OrganizationQuery, UserQuery, RoleQuery};
 
-use super::{PublicationQuery, TaskQuery, WorkQuery};
+use super::{PublicationQuery, TaskQuery, WorkQuery, MobilizationQuery, HistoryQuery, EmployeeQuery};
 
 #[derive(Default, MergedObject)]
 pub struct Query(
@@ -15,4 +15,7 @@ pub struct Query(
     PublicationQuery,
     TaskQuery,
     WorkQuery,
+    MobilizationQuery,
+    HistoryQuery,
+    EmployeeQuery,
 );
\ No newline at end of file
@@ -1,6 +1,7 @@
 use async_graphql::*;
 
 use crate::models::{Role};
+use crate::graphql::{paginate, KeysetConnection};
 use uuid::Uuid;
 
 #[derive(Default)]
@@ -11,7 +12,7 @@ impl RoleQuery {
 
     // Roles
 
-    #[graphql(name = "activeRoles")]
+    #[graphql(name = "activeRoles", deprecation = "Use allRoles with first and after")]
     /// Accepts an argument of "count" and returns a vector of {count} active role
     pub async fn get_active_role(&self, _context: &Context<'_>, count: i64) -> Result<Vec<Role>> {
         
@@ -26,12 +27,17 @@ impl RoleQuery {
     }
 
     #[graphql(name = "allRoles")]
-    /// Returns a vector of all persons ordered by family name
+    /// Returns a connection of active roles ordered by creation date
     pub async fn all_roles(
         &self, 
-        _context: &Context<'_>,) -> Result<Vec<Role>> {
+        _context: &Context<'_>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<KeysetConnection<Role>> {
 
-        Role::get_all_active()
+        paginate(after, before, first, last, Role::count_active, Role::get_active_page).await
     }
 
     #[graphql(name = "roleById")]
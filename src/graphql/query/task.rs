@@ -1,6 +1,7 @@
 use async_graphql::*;
 
 use crate::models::{Task};
+use crate::graphql::{paginate, KeysetConnection};
 use uuid::Uuid;
 
 /*
@@ -15,17 +16,20 @@ impl TaskQuery {
 
     // Task 
     #[graphql(name = "allTasks")]
-    /// Accepts argument of "count" and returns a vector of {count} tasks ordered by
-    /// family name.D
+    /// Returns a connection of tasks ordered by creation date
     pub async fn all_tasks(
         &self, 
         _context: &Context<'_>,
-    ) -> Result<Vec<Task>> {
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<KeysetConnection<Task>> {
 
-        Task::get_all()
+        paginate(after, before, first, last, Task::count, Task::get_page).await
     }
 
-    #[graphql(name = "Tasks")]
+    #[graphql(name = "Tasks", deprecation = "Use allTasks with first and after")]
     /// Accepts argument of "count" and returns a vector of {count} tasks ordered by
     /// family name
     pub async fn get_tasks(
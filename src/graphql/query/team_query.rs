@@ -1,6 +1,9 @@
 use async_graphql::*;
 
-use crate::models::{Team};
+use chrono::NaiveDateTime;
+
+use crate::models::{Team, AsOf};
+use crate::graphql::{paginate, KeysetConnection};
 use uuid::Uuid;
 
 /*
@@ -15,24 +18,37 @@ impl TeamQuery {
 
     // Teams
     #[graphql(name = "allTeams")]
-    /// Returns a vector of all travel groups
+    /// Returns a connection of teams ordered by creation date
     pub async fn all_teams(
         &self, 
         _context: &Context<'_>,
-    ) -> Result<Vec<Team>> {
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<KeysetConnection<Team>> {
 
-        Team::get_all()
+        paginate(after, before, first, last, Team::count, Team::get_page).await
     }
 
     #[graphql(name = "teamByID")]
-    /// Returns a specific travel group by its UUID
+    /// Returns a specific team by its UUID. With as_of, errors if the team did not exist on that date.
+    /// Pass the same as_of to nested fields (owner, roles) to see the team as it was.
     pub async fn team_by_id(
         &self, 
         _context: &Context<'_>,
-        id: Uuid
+        id: Uuid,
+        as_of: Option<NaiveDateTime>,
     ) -> Result<Team> {
 
-        Team::get_by_id(&id)
+        let team = Team::get_by_id(&id)?;
+
+        match as_of {
+            Some(date) if !team.in_effect_on(date) => {
+                Err(Error::new(format!("Team {} did not exist on {}", id, date)))
+            },
+            _ => Ok(team),
+        }
     }
 
     #[graphql(name = "teamByName")]
@@ -1,12 +1,9 @@
-use diesel::{RunQueryDsl};
-use crate::schema::*;
-
 use async_graphql::*;
 
-use crate::models::{User};
+use crate::models::{User, ApiKey};
 use uuid::Uuid;
 
-use crate::graphql::{get_connection_from_context};
+use crate::graphql::{paginate, KeysetConnection};
 use crate::common_utils::{RoleGuard, is_admin, UserRole};
 
 #[derive(Default)]
@@ -22,13 +19,17 @@ impl UserQuery {
         guard = "RoleGuard::new(UserRole::Admin)",
         visible = "is_admin",
     )]
-    /// Returns a vector of all users
-    pub async fn all_users(&self, context: &Context<'_>) -> Result<Vec<User>> {
-        let mut conn = get_connection_from_context(context);
-
-        let res = users::table.load::<User>(&mut conn)?;
+    /// Returns a connection of users ordered by creation date
+    pub async fn all_users(
+        &self,
+        _context: &Context<'_>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<KeysetConnection<User>> {
 
-        Ok(res)
+        paginate(after, before, first, last, User::count, User::get_page).await
     }
 
     #[graphql(
@@ -56,4 +57,26 @@ impl UserQuery {
 
         Ok(res)
     }
-}
\ No newline at end of file
+
+    #[graphql(
+        name = "pendingUsers",
+        guard = "RoleGuard::new(UserRole::Admin)",
+        visible = "is_admin",
+    )]
+    /// Returns self-registered users waiting for approval, oldest first
+    pub async fn pending_users(&self, _context: &Context<'_>) -> Result<Vec<User>> {
+
+        User::get_pending()
+    }
+
+    #[graphql(
+        name = "apiKeysByUserId",
+        guard = "RoleGuard::new(UserRole::Admin)",
+        visible = "is_admin",
+    )]
+    /// Returns the API keys minted for a user, including revoked and expired ones
+    pub async fn api_keys_by_user_id(&self, _context: &Context<'_>, user_id: Uuid) -> Result<Vec<ApiKey>> {
+
+        ApiKey::get_by_user_id(&user_id)
+    }
+}
@@ -1,6 +1,7 @@
 use async_graphql::*;
 
 use crate::models::{Work};
+use crate::graphql::{paginate, KeysetConnection};
 use uuid::Uuid;
 
 #[derive(Default)]
@@ -11,7 +12,7 @@ impl WorkQuery {
 
     // Works
 
-    #[graphql(name = "work")]
+    #[graphql(name = "work", deprecation = "Use allWork with first and after")]
     /// Accepts an argument of "count" and returns a vector of {count} work
     pub async fn get_count_work(&self, _context: &Context<'_>, count: i64) -> Result<Vec<Work>> {
         
@@ -19,12 +20,17 @@ impl WorkQuery {
     }
 
     #[graphql(name = "allWork")]
-    /// Returns a vector of all persons ordered by family name
+    /// Returns a connection of work ordered by creation date
     pub async fn all_works(
         &self, 
-        _context: &Context<'_>,) -> Result<Vec<Work>> {
+        _context: &Context<'_>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+    ) -> Result<KeysetConnection<Work>> {
 
-        Work::get_all()
+        paginate(after, before, first, last, Work::count, Work::get_page).await
     }
 
     #[graphql(name = "workById")]
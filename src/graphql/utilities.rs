@@ -6,7 +6,8 @@ use diesel::{PgConnection};
 use diesel::r2d2::ConnectionManager;
 use r2d2::PooledConnection;
 
-use crate::graphql::{Mutation, query::Query}; // Removed Subscription
+use crate::graphql::{Mutation, query::Query, Loaders, ApiKeyScopeCheck, GranularityFilter}; // Removed Subscription
+use crate::mailer::create_mailer;
 
 // use crate::kafka::{create_producer};
 
@@ -37,6 +38,10 @@ pub fn create_schema_with_context(pg_pool: PostgresPool) -> async_graphql::Schem
     Schema::build(Query::default(), Mutation::default(), EmptySubscription)
         // Database connection
         .data(arc_pool)
+        // Batched loaders for relationships between models
+        .data(Loaders::new())
+        // Outgoing email for verification and password resets
+        .data(create_mailer())
         // Live cached data -> may want to remove once dataloaders in place
         /*
         .data(countries)
@@ -47,6 +52,10 @@ pub fn create_schema_with_context(pg_pool: PostgresPool) -> async_graphql::Schem
         // Kafka
         // .data(create_producer())
         .data(kafka_consumer_counter)
+        // Keeps API keys to their scopes
+        .extension(ApiKeyScopeCheck)
+        // Redacts or refuses personal data below each user's granted Granularity
+        .extension(GranularityFilter)
         .finish()
 }
 
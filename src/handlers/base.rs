@@ -1,10 +1,12 @@
-use actix_web::{web, get, HttpResponse, HttpRequest, Responder};
+use actix_web::{web, get, HttpResponse, HttpRequest, Responder, ResponseError};
 //use actix_identity::Identity;
 
 use tera::Context;
 
 use crate::AppData;
 use crate::database::PostgresPool;
+use crate::models::{AccessRationale, AccessRecorder, AuthContext, AuthError, Granularity, OrgChartNode, OrgChartOptions, OrgDiff, OrgDiffOptions};
+use crate::common_utils::UserRole;
 
 #[get("/")]
 pub async fn index(data: web::Data<AppData>, _req:HttpRequest) -> impl Responder {
@@ -22,6 +24,109 @@ pub async fn org_chart(data: web::Data<AppData>, _req:HttpRequest) -> impl Respo
     HttpResponse::Ok().content_type("text/html; charset=utf-8").body(rendered)
 }
 
+#[get("/api/org_chart.{format}")]
+/// Org chart data as CSV or JSON for d3-org-chart.
+/// Accepts the same options as the orgChart query as query string parameters
+/// and needs an Analyst token or above. Names are redacted below Granularity::Identifiable
+/// and otherwise need an X-Access-Rationale header, with the read recorded in the AccessLog.
+pub async fn org_chart_data(
+    req: HttpRequest,
+    format: web::Path<String>,
+    options: web::Query<OrgChartOptions>,
+    auth: AuthContext,
+) -> impl Responder {
+
+    if auth.role < UserRole::Analyst {
+        return AuthError::Forbidden(UserRole::Analyst).error_response();
+    }
+
+    // Mark the signed in user's own nodes
+    let mut nodes = match OrgChartNode::build(&options, Some(auth.user_id)) {
+        Ok(n) => n,
+        Err(e) => return HttpResponse::BadRequest().body(e.message),
+    };
+
+    if auth.granularity < Granularity::Identifiable {
+        OrgChartNode::redact(&mut nodes);
+    } else {
+        let recorder = AccessRecorder::new(AccessRationale::from_request(&req));
+
+        if recorder.rationale.is_none() {
+            return AuthError::RationaleRequired.error_response();
+        }
+
+        // As with PersonalDataGuard, reading your own name isn't recorded
+        for (person_id, _) in nodes.iter()
+            .filter_map(|n| n.holder)
+            .filter(|(_, user_id)| *user_id != auth.user_id) {
+            recorder.record("name", person_id);
+        }
+
+        if let Err(e) = recorder.save(Some(&auth), Some("org_chart_data")) {
+            return HttpResponse::InternalServerError().body(e.message);
+        }
+    }
+
+    match format.as_str() {
+        "csv" => match OrgChartNode::to_csv(&nodes) {
+            Ok(body) => HttpResponse::Ok().content_type("text/csv; charset=utf-8").body(body),
+            Err(e) => HttpResponse::InternalServerError().body(e.message),
+        },
+        "json" => HttpResponse::Ok().json(nodes),
+        _ => HttpResponse::NotFound().body("Format must be csv or json"),
+    }
+}
+
+#[get("/api/org_diff.csv")]
+/// CSV export of the orgDiff query for quarterly HR reviews.
+/// Takes organization_id, from, to and optionally level_source as query string parameters
+/// and needs an Analyst token or above granted Granularity::Identifiable,
+/// as the CSV names the people who moved. As with org_chart_data, an X-Access-Rationale
+/// header is required and each person named is recorded in the access log.
+pub async fn org_diff_csv(
+    req: HttpRequest,
+    options: web::Query<OrgDiffOptions>,
+    auth: AuthContext,
+) -> impl Responder {
+
+    if auth.role < UserRole::Analyst {
+        return AuthError::Forbidden(UserRole::Analyst).error_response();
+    }
+
+    if auth.granularity < Granularity::Identifiable {
+        return AuthError::InsufficientGranularity(Granularity::Identifiable).error_response();
+    }
+
+    let recorder = AccessRecorder::new(AccessRationale::from_request(&req));
+
+    if recorder.rationale.is_none() {
+        return AuthError::RationaleRequired.error_response();
+    }
+
+    let diff = match OrgDiff::build(&options) {
+        Ok(d) => d,
+        Err(e) => return HttpResponse::BadRequest().body(e.message),
+    };
+
+    // As with PersonalDataGuard, reading your own name isn't recorded
+    for (field, person_id, _) in diff.named().into_iter()
+        .filter(|(_, _, user_id)| *user_id != auth.user_id) {
+        recorder.record(field, person_id);
+    }
+
+    if let Err(e) = recorder.save(Some(&auth), Some("org_diff_csv")) {
+        return HttpResponse::InternalServerError().body(e.message);
+    }
+
+    match diff.to_csv() {
+        Ok(body) => HttpResponse::Ok()
+            .content_type("text/csv; charset=utf-8")
+            .insert_header(("Content-Disposition", "attachment; filename=\"org_diff.csv\""))
+            .body(body),
+        Err(e) => HttpResponse::InternalServerError().body(e.message),
+    }
+}
+
 #[get("/{lang}/api")]
 pub async fn api_base(
     data: web::Data<AppData>,
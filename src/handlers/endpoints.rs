@@ -1,11 +1,13 @@
+use std::sync::Arc;
+
 use actix_web::{web, HttpResponse, HttpRequest, Result};
 use async_graphql::http::{GraphiQLSource};
-use async_graphql::Schema;
+use async_graphql::{Schema, ServerError};
 
 use async_graphql_actix_web::{GraphQLSubscription,
     GraphQLRequest, GraphQLResponse};
 
-use crate::models;
+use crate::models::{AccessRationale, AccessRecorder, AuthContext};
 use crate::graphql::{AppSchema};
 
 
@@ -23,21 +25,43 @@ pub async fn graphql(
     
     let mut query = req.into_inner();
 
-    let maybe_role_id = models::get_claim(http_request);
+    // An X-API-Key header takes the place of a bearer token
+    let auth = match AuthContext::from_api_key(&http_request) {
+        Some(Ok((a, scopes))) => {
+            query = query.data(scopes);
+            Ok(a)
+        },
+        Some(Err(e)) => Err(e),
+        None => AuthContext::from_request(&http_request),
+    };
+
+    let signed_in = auth.as_ref().ok().copied();
 
-    // insert claim data into query or error for response
-    match maybe_role_id {
-        Ok((role, uuid, exp_time)) => {
-            query = query.data(role);
-            query = query.data(uuid);
-            query = query.data(exp_time)
+    // insert the signed in user into query, or why there isn't one for guards to report
+    match auth {
+        Ok(a) => {
+            query = query.data(a);
         },
         Err(e) => {
             query = query.data(e);
         }
     };
 
-    schema.execute(query).await.into()
+    // Filled in by PersonalDataGuard with the personal data the request reads
+    let recorder = Arc::new(AccessRecorder::new(AccessRationale::from_request(&http_request)));
+    query = query.data(recorder.clone());
+
+    let operation_name = query.operation_name.clone();
+
+    let response = schema.execute(query).await;
+
+    // Personal data that can't be logged isn't returned
+    match recorder.save(signed_in.as_ref(), operation_name.as_deref()) {
+        Ok(_) => response.into(),
+        Err(e) => async_graphql::Response::from_errors(vec![
+            ServerError::new(format!("Unable to write access log: {}", e.message), None)
+        ]).into(),
+    }
 }
 
 pub async fn graphql_ws(
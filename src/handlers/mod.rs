@@ -4,5 +4,5 @@ mod endpoints;
 
 pub use self::routes::configure_services;
 
-pub use self::base::{index, api_base, org_chart};
+pub use self::base::{index, api_base, org_chart, org_chart_data, org_diff_csv};
 pub use self::endpoints::*;
\ No newline at end of file
@@ -5,6 +5,8 @@ use crate::handlers::{
     index,
     api_base,
     org_chart,
+    org_chart_data,
+    org_diff_csv,
     playground_handler,
     graphql,
     graphql_ws,
@@ -14,6 +16,8 @@ pub fn configure_services(config: &mut web::ServiceConfig) {
     config.service(index);
     config.service(api_base);
     config.service(org_chart);
+    config.service(org_chart_data);
+    config.service(org_diff_csv);
     config.service(Files::new("/static", std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("static")));
     // API use
     // Playground
@@ -24,6 +24,7 @@ pub mod database;
 pub mod graphql;
 pub mod common_utils;
 pub mod config_variables;
+pub mod mailer;
 //pub mod kafka;
 
 pub struct AppData {
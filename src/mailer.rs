@@ -0,0 +1,118 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use async_graphql::*;
+use lettre::message::{header::ContentType, Mailbox};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+use uuid::Uuid;
+
+/// A plain text email
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Sends outgoing email. Added to the GraphQL context as Arc<dyn Mailer>.
+/// `send` may wait on the network, so resolvers go through `send_email`.
+pub trait Mailer: Send + Sync {
+    fn send(&self, email: &Email) -> Result<()>;
+}
+
+/// Sends an email on the blocking thread pool so resolvers don't hold up the executor
+pub async fn send_email(mailer: Arc<dyn Mailer>, email: Email) -> Result<()> {
+    actix_web::web::block(move || mailer.send(&email))
+        .await
+        .map_err(|e| Error::new(e.to_string()))?
+}
+
+/// Sends email through an SMTP relay over TLS
+pub struct SmtpMailer {
+    transport: SmtpTransport,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    pub fn new(host: &str, credentials: Option<(String, String)>, from: &str) -> Result<Self> {
+        let mut builder = SmtpTransport::relay(host)?;
+
+        if let Some((username, password)) = credentials {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        Ok(SmtpMailer {
+            transport: builder.build(),
+            from: from.parse()?,
+        })
+    }
+}
+
+impl Mailer for SmtpMailer {
+    fn send(&self, email: &Email) -> Result<()> {
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(email.to.parse()?)
+            .subject(&email.subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(email.body.clone())?;
+
+        self.transport.send(&message)?;
+
+        Ok(())
+    }
+}
+
+/// Writes email to a directory, one file per message, or logs it at info level if there is no directory.
+/// For local development and testing.
+pub struct LogMailer {
+    dir: Option<PathBuf>,
+}
+
+impl LogMailer {
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        LogMailer { dir }
+    }
+}
+
+impl Mailer for LogMailer {
+    fn send(&self, email: &Email) -> Result<()> {
+        let text = format!("To: {}\nSubject: {}\n\n{}\n", email.to, email.subject, email.body);
+
+        match &self.dir {
+            Some(dir) => {
+                fs::create_dir_all(dir)?;
+
+                let name = format!("{}-{}.eml", chrono::Utc::now().format("%Y%m%d%H%M%S"), Uuid::new_v4());
+                fs::write(dir.join(name), text)?;
+            },
+            None => log::info!("MAIL\n{}", text),
+        };
+
+        Ok(())
+    }
+}
+
+/// Builds the mailer chosen by MAIL_TRANSPORT.
+/// "smtp" uses SMTP_HOST, SMTP_USERNAME, SMTP_PASSWORD and MAIL_FROM.
+/// "log" writes mail, including verification codes and reset tokens, to MAIL_DIR if set, or logs it.
+/// Panics at startup if MAIL_TRANSPORT is anything else, so codes aren't logged by accident.
+pub fn create_mailer() -> Arc<dyn Mailer> {
+    match std::env::var("MAIL_TRANSPORT").as_deref() {
+        Ok("smtp") => {
+            let host = std::env::var("SMTP_HOST").expect("Can't read SMTP_HOST");
+            let from = std::env::var("MAIL_FROM").expect("Can't read MAIL_FROM");
+
+            let credentials = match (std::env::var("SMTP_USERNAME"), std::env::var("SMTP_PASSWORD")) {
+                (Ok(u), Ok(p)) => Some((u, p)),
+                _ => None,
+            };
+
+            Arc::new(SmtpMailer::new(&host, credentials, &from).expect("Unable to create SMTP mailer"))
+        },
+        Ok("log") => Arc::new(LogMailer::new(std::env::var("MAIL_DIR").ok().map(PathBuf::from))),
+        _ => panic!("MAIL_TRANSPORT must be smtp or log"),
+    }
+}
@@ -8,6 +8,7 @@ use tera_text_filters::snake_case;
 use people_data_api::database::{self, POOL};
 use people_data_api::graphql::create_schema_with_context;
 use people_data_api::handlers;
+use people_data_api::models;
 use people_data_api::AppData;
 
 #[actix_rt::main]
@@ -29,6 +30,8 @@ async fn main() -> std::io::Result<()> {
 
     let _secret_key = env::var("SECRET_KEY").expect("Unable to find secret key");
 
+    models::check_oidc_config().expect("Invalid OIDC configuration");
+
     let (host, port) = if environment == "production" {
         let p: u16 = env::var("PORT")
             .unwrap()
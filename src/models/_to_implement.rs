@@ -1,27 +1,3 @@
-pub struct EmployeeData {
-    pub id: Uuid,
-    pub person_id: Uuid,
-    pub title_en: String,
-    pub title_fr: String,
-    pub group: String,
-    pub level: u32,
-    pub hr_state: String,
-    pub created_at: NaiveDate,
-    pub updated_at: NaiveDate,
-    // salary from a separate API call
-}
-
-pub struct ContactData {
-    pub id: Uuid,
-    pub person_id: Uuid,
-    pub email: String,
-    pub phone: String,
-    pub work_address: String,
-    pub city: String,
-    pub territory: String,
-    pub country: String,
-}
-
 pub struct DataAccess {
     pub id: Uuid,
     pub person_id: Uuid,
@@ -1,30 +1,85 @@
+use std::collections::BTreeSet;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use actix_web::HttpRequest;
 use chrono::NaiveDateTime;
+use diesel_derive_enum::DbEnum;
 use serde::{Serialize, Deserialize};
+use diesel::{self, Insertable, Queryable, ExpressionMethods, PgArrayExpressionMethods};
+use diesel::{RunQueryDsl, QueryDsl};
 use uuid::Uuid;
+use async_graphql::*;
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+use crate::database::connection;
+use crate::schema::*;
+use crate::graphql::person_id_field;
+
+use super::AuthContext;
+
+#[derive(Debug, Clone, Deserialize, Serialize, Queryable, SimpleObject)]
+#[diesel(table_name = access_logs)]
+/// A request that read identifiable personal data, who made it and why
 pub struct AccessLog {
-    id: Uuid,
-    person_id: Uuid,
-    access_key: String,
+    pub id: Uuid,
+    #[graphql(directive = person_id_field::apply())]
+    /// The user who read the data
+    pub user_id: Option<Uuid>,
+    /// Set if the request was made with an API key
+    pub api_key_id: Option<Uuid>,
+    pub rationale: AccessRationale,
+    pub granularity: Granularity,
+    pub operation_name: Option<String>,
+    /// Fields read, e.g. email or languageData
+    pub fields: Vec<String>,
+    #[graphql(directive = person_id_field::apply())]
+    /// Persons whose data was read
+    pub person_ids: Vec<Uuid>,
+    pub accessed_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = access_logs)]
+struct NewAccessLog {
+    user_id: Option<Uuid>,
+    api_key_id: Option<Uuid>,
     rationale: AccessRationale,
     granularity: Granularity,
-    requested_on: NaiveDateTime,
-    decision_on: NaiveDateTime,
-    approval_type: ApprovalType,
-    approved: bool,
+    operation_name: Option<String>,
+    fields: Vec<String>,
+    person_ids: Vec<Uuid>,
 }
-#[derive(Debug, Clone, Deserialize, Serialize)]
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, DbEnum, Serialize, Deserialize, Enum, EnumString)]
+#[ExistingTypePath = "crate::schema::sql_types::AccessRationale"]
+#[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
+/// Why personal data is being read, declared in the X-Access-Rationale header
 pub enum AccessRationale {
     Automated,
     Administrative,
     Research,
     PublicHealth,
 }
-#[derive(Debug, Clone, Deserialize, Serialize)]
+
+impl AccessRationale {
+    /// Reads the X-Access-Rationale header of a request. None if it is missing or not a rationale.
+    pub fn from_request(req: &HttpRequest) -> Option<Self> {
+        req.headers()
+            .get("X-Access-Rationale")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|s| AccessRationale::from_str(&s.trim().to_uppercase()).ok())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, DbEnum, Serialize, Deserialize, Enum)]
+#[ExistingTypePath = "crate::schema::sql_types::Granularity"]
+/// The most detailed view of personal data a user is granted, from least to most detailed
 pub enum Granularity {
+    /// Counts and other aggregates only. Person records are refused.
     Aggregated,
+    /// Person records with names and contact details redacted and ids pseudonymized
     Anonymized,
+    /// Person records as stored
     Identifiable,
 }
 
@@ -47,3 +102,102 @@ pub enum AccessLevel {
 
 }
 
+#[derive(Debug, Default)]
+struct Accessed {
+    fields: BTreeSet<String>,
+    person_ids: BTreeSet<Uuid>,
+}
+
+/// Collects the personal data read while resolving one GraphQL request.
+/// Added to the request's data by the graphql endpoint and filled in by PersonalDataGuard.
+#[derive(Debug)]
+pub struct AccessRecorder {
+    pub rationale: Option<AccessRationale>,
+    accessed: Mutex<Accessed>,
+}
+
+impl AccessRecorder {
+    pub fn new(rationale: Option<AccessRationale>) -> Self {
+        AccessRecorder {
+            rationale,
+            accessed: Mutex::new(Accessed::default()),
+        }
+    }
+
+    pub fn record(&self, field: &str, person_id: Uuid) {
+        if let Ok(mut accessed) = self.accessed.lock() {
+            accessed.fields.insert(field.to_string());
+            accessed.person_ids.insert(person_id);
+        }
+    }
+
+    /// Writes an AccessLog for the request if any personal data was read
+    pub fn save(&self, auth: Option<&AuthContext>, operation_name: Option<&str>) -> Result<Option<AccessLog>> {
+        let accessed = self.accessed.lock()
+            .map_err(|_| Error::new("Unable to read accessed fields"))?;
+
+        if accessed.person_ids.is_empty() {
+            return Ok(None);
+        }
+
+        // PersonalDataGuard only records data when there is a rationale
+        let rationale = self.rationale
+            .ok_or_else(|| Error::new("Personal data was read without a rationale"))?;
+
+        let log = AccessLog::create(&NewAccessLog {
+            user_id: auth.map(|a| a.user_id),
+            api_key_id: auth.and_then(|a| a.api_key_id),
+            rationale,
+            granularity: auth.map(|a| a.granularity).unwrap_or(Granularity::Identifiable),
+            operation_name: operation_name.map(|s| s.chars().take(128).collect()),
+            fields: accessed.fields.iter().cloned().collect(),
+            person_ids: accessed.person_ids.iter().cloned().collect(),
+        })?;
+
+        Ok(Some(log))
+    }
+}
+
+impl AccessLog {
+    fn create(log: &NewAccessLog) -> Result<Self> {
+        let mut conn = connection()?;
+
+        let res = diesel::insert_into(access_logs::table)
+            .values(log)
+            .get_result(&mut conn)?;
+
+        Ok(res)
+    }
+
+    /// Returns access logs, most recent first, optionally limited to a user who read data,
+    /// a person whose data was read or a start time
+    pub fn get_filtered(
+        user_id: Option<Uuid>,
+        person_id: Option<Uuid>,
+        since: Option<NaiveDateTime>,
+        limit: i64,
+    ) -> Result<Vec<Self>> {
+        let mut conn = connection()?;
+
+        let mut query = access_logs::table.into_boxed();
+
+        if let Some(id) = user_id {
+            query = query.filter(access_logs::user_id.eq(id));
+        }
+
+        if let Some(id) = person_id {
+            query = query.filter(access_logs::person_ids.contains(vec![id]));
+        }
+
+        if let Some(date) = since {
+            query = query.filter(access_logs::accessed_at.ge(date));
+        }
+
+        let res = query
+            .order_by(access_logs::accessed_at.desc())
+            .limit(limit)
+            .load::<AccessLog>(&mut conn)?;
+
+        Ok(res)
+    }
+}
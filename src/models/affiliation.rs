@@ -9,12 +9,14 @@ use async_graphql::*;
 
 use crate::{schema::*, database};
 use crate::models::{Person, Organization};
+use crate::graphql::{Keyed, Keyset, Loaders, PageRequest, keyset_page, person_id_field};
 
 #[derive(Debug, Clone, Deserialize, Serialize, Queryable, Insertable, AsChangeset, SimpleObject)]
 #[graphql(complex)]
 #[table_name = "affiliations"]
 pub struct Affiliation {
     pub id: Uuid,
+    #[graphql(directive = person_id_field::apply())]
     pub person_id: Uuid,
     pub organization_id: Uuid,
     pub home_org_id: Uuid,
@@ -28,16 +30,22 @@ pub struct Affiliation {
 
 #[ComplexObject]
 impl Affiliation {
-    pub async fn person(&self) -> Result<Person> {
-        Person::get_by_id(&self.person_id)
+    pub async fn person(&self, ctx: &Context<'_>) -> Result<Person> {
+        ctx.data::<Loaders>()?.person(self.person_id).await
     }
 
-    pub async fn organization(&self) -> Result<Organization> {
-        Organization::get_by_id(&self.organization_id)
+    pub async fn organization(&self, ctx: &Context<'_>) -> Result<Organization> {
+        ctx.data::<Loaders>()?.organization(self.organization_id).await
     }
 
-    pub async fn home_organization(&self) -> Result<Organization> {
-        Organization::get_by_id(&self.home_org_id)
+    pub async fn home_organization(&self, ctx: &Context<'_>) -> Result<Organization> {
+        ctx.data::<Loaders>()?.organization(self.home_org_id).await
+    }
+}
+
+impl Keyed for Affiliation {
+    fn keyset(&self) -> Keyset {
+        Keyset { created_at: self.created_at, id: self.id }
     }
 }
 
@@ -79,6 +87,22 @@ impl Affiliation {
         Ok(res)
     }
 
+    /// Returns a page of affiliations ordered by (created_at, id) for cursor pagination
+    pub fn get_page(page: &PageRequest) -> Result<Vec<Self>> {
+        keyset_page!(affiliations, page)
+    }
+
+    /// Returns the number of affiliations in the system
+    pub fn count() -> Result<i64> {
+        let mut conn = database::connection()?;
+
+        let res = affiliations::table
+            .count()
+            .get_result(&mut conn)?;
+
+        Ok(res)
+    }
+
     pub fn get_by_id(id: &Uuid) -> Result<Self> {
         let mut conn = database::connection()?;
         let res = affiliations::table.filter(affiliations::id.eq(id))
@@ -0,0 +1,274 @@
+use chrono::{prelude::*, Duration};
+use diesel_derive_enum::DbEnum;
+use serde::{Deserialize, Serialize};
+use diesel::{self, Insertable, Queryable, ExpressionMethods, BoolExpressionMethods, OptionalExtension};
+use diesel::{RunQueryDsl, QueryDsl};
+use uuid::Uuid;
+use async_graphql::*;
+
+use crate::config_variables::{API_KEY_DEFAULT_DAYS, API_KEY_MAX_DAYS, API_KEY_LAST_USED_INTERVAL};
+use crate::database::connection;
+use crate::schema::*;
+
+use super::{generate_token, hash_token};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, DbEnum, Serialize, Deserialize, Enum)]
+#[ExistingTypePath = "crate::schema::sql_types::ApiScope"]
+/// What an API key may do. Keys act as their user, so guards still apply on top of scopes.
+pub enum ApiScope {
+    /// Queries
+    Read,
+    /// Person, Role, employee data and contact mutations
+    ManagePeople,
+    /// Capability mutations
+    ManageCapabilities,
+    /// Team and OrgTier mutations
+    ManageOrganization,
+    /// Deployment mutations
+    ManageDeployments,
+}
+
+impl ApiScope {
+    /// The scope needed to call a mutation with an API key.
+    /// None for mutations API keys can never call, such as user and session management.
+    pub fn for_mutation(field: &str) -> Option<ApiScope> {
+        match field {
+            "createPerson" | "updatePerson" | "createRole" | "updateRole"
+                | "createEmployeeData" | "updateEmployeeData" | "recordHrState"
+                | "createContactData" | "updateContactData" => Some(ApiScope::ManagePeople),
+            "createCapability" | "updateCapability" | "validateCapability" => Some(ApiScope::ManageCapabilities),
+            "createTeam" | "updateTeam" | "retireTeam" | "assignTeamOwner"
+                | "createOrgTier" | "moveOrgTier" | "assignOrgTierOwner" => Some(ApiScope::ManageOrganization),
+            "createDeployment" | "updateDeployment" | "deleteDeployment" => Some(ApiScope::ManageDeployments),
+            _ => None,
+        }
+    }
+}
+
+/// The scopes of the API key a GraphQL request was made with, added to the request's data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiKeyScopes(pub Vec<ApiScope>);
+
+#[derive(Debug, Clone, Deserialize, Serialize, Queryable, SimpleObject)]
+#[diesel(table_name = api_keys)]
+/// A key an integration account sends in the X-API-Key header instead of a bearer token
+pub struct ApiKey {
+    pub id: Uuid,
+    /// The user the key acts as
+    pub user_id: Uuid,
+    pub name: String,
+    /// First characters of the key, to tell keys apart
+    pub key_prefix: String,
+    #[graphql(skip)]
+    pub key_hash: String,
+    pub scopes: Vec<ApiScope>,
+    pub expires_at: NaiveDateTime,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub revoked_at: Option<NaiveDateTime>,
+    pub created_by: Option<Uuid>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = api_keys)]
+pub struct NewApiKey {
+    pub user_id: Uuid,
+    pub name: String,
+    pub key_prefix: String,
+    pub key_hash: String,
+    pub scopes: Vec<ApiScope>,
+    pub expires_at: NaiveDateTime,
+    pub created_by: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Deserialize, InputObject)]
+/// InputObject to mint an API key. Only accessible by Administrators.
+pub struct ApiKeyData {
+    /// The integration account the key acts as
+    pub user_id: Uuid,
+    pub name: String,
+    pub scopes: Vec<ApiScope>,
+    /// Defaults to API_KEY_DEFAULT_DAYS from now, and can be at most API_KEY_MAX_DAYS away
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, SimpleObject)]
+/// A newly minted API key. The key is only ever returned here.
+pub struct MintedApiKey {
+    pub api_key: ApiKey,
+    pub key: String,
+}
+
+impl NewApiKey {
+    /// Checks the request and returns the row to store and the key to hand out
+    pub fn new(data: ApiKeyData, created_by: Option<Uuid>) -> Result<(Self, String)> {
+        let now = chrono::Utc::now().naive_utc();
+
+        let expires_at = data.expires_at.unwrap_or(now + Duration::days(API_KEY_DEFAULT_DAYS));
+
+        if expires_at <= now || expires_at > now + Duration::days(API_KEY_MAX_DAYS) {
+            return Err(Error::new(format!("expires_at must be in the next {} days", API_KEY_MAX_DAYS)));
+        }
+
+        if data.scopes.is_empty() {
+            return Err(Error::new("An API key needs at least one scope"));
+        }
+
+        let mut scopes = data.scopes;
+        scopes.sort_by_key(|s| *s as i32);
+        scopes.dedup();
+
+        let (key, key_hash) = generate_token();
+
+        let new_key = NewApiKey {
+            user_id: data.user_id,
+            name: data.name,
+            key_prefix: key[..8].to_string(),
+            key_hash,
+            scopes,
+            expires_at,
+            created_by,
+        };
+
+        Ok((new_key, key))
+    }
+}
+
+impl ApiKey {
+    pub fn create(api_key: &NewApiKey) -> Result<Self> {
+        let mut conn = connection()?;
+
+        let res = diesel::insert_into(api_keys::table)
+            .values(api_key)
+            .get_result(&mut conn)?;
+
+        Ok(res)
+    }
+
+    pub fn get_by_id(id: &Uuid) -> Result<Self> {
+        let mut conn = connection()?;
+
+        let res = api_keys::table
+            .filter(api_keys::id.eq(id))
+            .first(&mut conn)?;
+
+        Ok(res)
+    }
+
+    pub fn get_by_user_id(user_id: &Uuid) -> Result<Vec<Self>> {
+        let mut conn = connection()?;
+
+        let res = api_keys::table
+            .filter(api_keys::user_id.eq(user_id))
+            .order_by(api_keys::created_at.desc())
+            .load::<ApiKey>(&mut conn)?;
+
+        Ok(res)
+    }
+
+    /// Returns the key if it exists, isn't revoked and hasn't expired, and records its use
+    pub fn authenticate(key: &str) -> Result<Option<Self>> {
+        let mut conn = connection()?;
+
+        let now = chrono::Utc::now().naive_utc();
+
+        let api_key: Option<ApiKey> = api_keys::table
+            .filter(api_keys::key_hash.eq(hash_token(key)))
+            .filter(api_keys::revoked_at.is_null())
+            .filter(api_keys::expires_at.gt(now))
+            .first(&mut conn)
+            .optional()?;
+
+        if let Some(k) = &api_key {
+            // Written at most once per interval to keep busy keys from writing on every request
+            diesel::update(api_keys::table)
+                .filter(api_keys::id.eq(k.id))
+                .filter(api_keys::last_used_at.is_null()
+                    .or(api_keys::last_used_at.lt(now - Duration::seconds(API_KEY_LAST_USED_INTERVAL))))
+                .set(api_keys::last_used_at.eq(now))
+                .execute(&mut conn)?;
+        }
+
+        Ok(api_key)
+    }
+
+    pub fn revoke(id: &Uuid) -> Result<Self> {
+        let mut conn = connection()?;
+
+        let res = diesel::update(api_keys::table)
+            .filter(api_keys::id.eq(id))
+            .set(api_keys::revoked_at.eq(chrono::Utc::now().naive_utc()))
+            .get_result(&mut conn)?;
+
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::parser::parse_schema;
+    use async_graphql::parser::types::{TypeKind, TypeSystemDefinition};
+
+    use super::*;
+    use crate::graphql::{Mutation, Query};
+
+    /// User, session and API key management stays with bearer tokens
+    const KEYLESS: [&str; 17] = [
+        "mintApiKey", "revokeApiKey", "createUser", "updateUser", "signUp",
+        "approveUser", "rejectUser", "signIn", "signInWithOidc", "changePassword", "requestPasswordReset",
+        "resetPassword", "verifyEmail", "requestEmailVerification", "refreshToken", "signOut", "revokeUserSessions",
+    ];
+
+    fn mutation_fields() -> Vec<String> {
+        let sdl = Schema::build(Query::default(), Mutation::default(), EmptySubscription)
+            .finish()
+            .sdl();
+
+        parse_schema(sdl).expect("SDL parses")
+            .definitions
+            .into_iter()
+            .filter_map(|d| match d {
+                TypeSystemDefinition::Type(t) if t.node.name.node == "Mutation" => Some(t.node.kind),
+                _ => None,
+            })
+            .flat_map(|kind| match kind {
+                TypeKind::Object(o) => o.fields.into_iter().map(|f| f.node.name.node.to_string()).collect(),
+                _ => Vec::new(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn maps_mutations_to_their_scope() {
+        assert_eq!(ApiScope::for_mutation("updatePerson"), Some(ApiScope::ManagePeople));
+        assert_eq!(ApiScope::for_mutation("recordHrState"), Some(ApiScope::ManagePeople));
+        assert_eq!(ApiScope::for_mutation("validateCapability"), Some(ApiScope::ManageCapabilities));
+        assert_eq!(ApiScope::for_mutation("moveOrgTier"), Some(ApiScope::ManageOrganization));
+        assert_eq!(ApiScope::for_mutation("deleteDeployment"), Some(ApiScope::ManageDeployments));
+    }
+
+    #[test]
+    fn no_scope_for_keyless_or_unknown_mutations() {
+        for field in KEYLESS.iter().chain(&["", "UpdatePerson", "update_person"]) {
+            assert_eq!(ApiScope::for_mutation(field), None, "{}", field);
+        }
+    }
+
+    #[test]
+    fn every_mutation_has_a_scope_or_is_keyless() {
+        let fields = mutation_fields();
+
+        assert!(!fields.is_empty());
+
+        for field in &fields {
+            assert!(
+                ApiScope::for_mutation(field).is_some() || KEYLESS.contains(&field.as_str()),
+                "{} needs a scope in ApiScope::for_mutation or a place in KEYLESS", field,
+            );
+        }
+
+        for field in KEYLESS {
+            assert!(fields.iter().any(|f| f == field), "{} is not a mutation", field);
+        }
+    }
+}
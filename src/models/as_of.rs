@@ -0,0 +1,60 @@
+use chrono::NaiveDateTime;
+use uuid::Uuid;
+
+use super::{OrgTier, Role, Team, TeamOwnership, OrgOwnership};
+
+/// True if a record that starts at `start` and ends at `end` (open if None)
+/// was in effect at `as_of`. The end is exclusive, so a role that ended on a date
+/// is no longer held on that date.
+pub fn in_effect(start: NaiveDateTime, end: Option<NaiveDateTime>, as_of: NaiveDateTime) -> bool {
+    start <= as_of && end.is_none_or(|e| e > as_of)
+}
+
+/// An entity id and the date it is looked up on, used to batch `asOf` lookups
+pub type AsOfKey = (Uuid, NaiveDateTime);
+
+/// Pairs each key with the records of its id that were in effect on its date,
+/// keeping the order of records
+pub fn in_effect_for<T: AsOf + Clone>(keys: &[AsOfKey], records: &[T], id: fn(&T) -> Uuid) -> Vec<(AsOfKey, T)> {
+    keys.iter()
+        .flat_map(|key| records.iter()
+            .filter(move |r| id(r) == key.0 && r.in_effect_on(key.1))
+            .map(move |r| (*key, r.clone())))
+        .collect()
+}
+
+/// Records with a lifetime that can be checked against a point in time.
+/// Used by the `asOf` arguments to reconstruct the organization on a past date.
+pub trait AsOf {
+    fn in_effect_on(&self, as_of: NaiveDateTime) -> bool;
+}
+
+impl AsOf for Role {
+    fn in_effect_on(&self, as_of: NaiveDateTime) -> bool {
+        in_effect(self.start_datestamp, self.end_date, as_of)
+    }
+}
+
+impl AsOf for TeamOwnership {
+    fn in_effect_on(&self, as_of: NaiveDateTime) -> bool {
+        in_effect(self.start_datestamp, self.end_date, as_of)
+    }
+}
+
+impl AsOf for OrgOwnership {
+    fn in_effect_on(&self, as_of: NaiveDateTime) -> bool {
+        in_effect(self.created_at, self.retired_at, as_of)
+    }
+}
+
+impl AsOf for Team {
+    fn in_effect_on(&self, as_of: NaiveDateTime) -> bool {
+        in_effect(self.created_at, self.retired_at, as_of)
+    }
+}
+
+impl AsOf for OrgTier {
+    fn in_effect_on(&self, as_of: NaiveDateTime) -> bool {
+        in_effect(self.created_at, self.retired_at, as_of)
+    }
+}
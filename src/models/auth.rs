@@ -1,6 +1,8 @@
 use std::str::FromStr;
 
-use actix_web::{HttpRequest, Result};
+use actix_web::Result;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
 use argon2::password_hash::{PasswordHashString, SaltString};
 use chrono::{Duration, Local};
 use jsonwebtoken::{decode, DecodingKey, TokenData, Validation};
@@ -11,10 +13,11 @@ use argon2::{
     Argon2,
     password_hash::{PasswordHasher, PasswordVerifier},
 };
-use jsonwebtoken::errors::*;
 
 use crate::common_utils::UserRole;
-use crate::config_variables::TOKEN_DURATION;
+use crate::models::Granularity;
+use crate::config_variables::{TOKEN_DURATION, PASSWORD_MIN_LENGTH,
+    PASSWORD_MAX_LENGTH, PASSWORD_MIN_CHARACTER_CLASSES};
 
 lazy_static! {
     static ref JWT_SECRET_KEY: String = 
@@ -22,8 +25,9 @@ lazy_static! {
 }
 
 lazy_static! {
-    static ref PASSWORD_SECRET_KEY: String = 
-        std::env::var("PASSWORD_SECRET_KEY").expect("Can't read PASSWORD_SECRET_KEY");
+    /// Salt shared by hashes made before per-hash salts. Only used to spot them for rehashing.
+    static ref PASSWORD_SECRET_KEY: Option<String> = 
+        std::env::var("PASSWORD_SECRET_KEY").ok();
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -31,15 +35,31 @@ pub struct Claims {
     pub sub: String,
     pub exp: i64,
     pub role: String,
+    /// Unique id of the token, used to revoke it
+    pub jti: String,
+    pub iat: i64,
+    /// Issue time in microseconds since the epoch, so revoking a user's tokens catches
+    /// those issued earlier in the same second. Missing from tokens issued before it was added.
+    #[serde(default)]
+    pub iat_us: Option<i64>,
+    pub granularity: Granularity,
+    /// Id of the sign in the token belongs to, shared by every token refreshed from it
+    pub sid: String,
 }
 
-pub fn create_token(user_id: String, role: UserRole) -> String {
-    let exp_time = Local::now() + Duration::seconds(TOKEN_DURATION);
+pub fn create_token(user_id: String, role: UserRole, granularity: Granularity, session_id: Uuid) -> String {
+    let now = Local::now();
+    let exp_time = now + Duration::seconds(TOKEN_DURATION);
 
     let claims = Claims {
         sub: user_id,
         exp: exp_time.timestamp(),
         role: role.to_string(),
+        jti: uuid::Uuid::new_v4().to_string(),
+        iat: now.timestamp(),
+        iat_us: Some(now.timestamp_micros()),
+        granularity,
+        sid: session_id.to_string(),
     };
 
     encode(
@@ -50,41 +70,6 @@ pub fn create_token(user_id: String, role: UserRole) -> String {
     .expect("Can't create token")
 }
 
-pub fn get_claim(http_request: HttpRequest) -> Result<(UserRole, uuid::Uuid, i64), jsonwebtoken::errors::Error> {
-
-    println!("{:?}", &http_request.headers().get("Authorization"));
-
-    let token_data = http_request
-        .headers()
-        .get("Authorization")
-        .and_then(|header_value| {
-            header_value.to_str().ok().map(|s| {
-                let jwt_start_index = "Bearer ".len();
-                let jwt = s[jwt_start_index..s.len()].to_string();
-                let token_data = decode_token(&jwt);
-                println!("TOKEN: {:?}", &token_data);
-                token_data
-            })
-        });
-
-        let token = match token_data {
-            Some(td) => {
-                let token = match td {
-                    Ok(t) => t,
-                    Err(e) => return Err(e),
-                };
-                token
-            },
-            None => return Err(jsonwebtoken::errors::Error::from(ErrorKind::InvalidToken)),
-        };
-
-        let role = UserRole::from_str(&token.claims.role).expect("Can't parse role");
-        let uuid = uuid::Uuid::from_str(&token.claims.sub).expect("Can't parse CBSA_ID");
-        let exp_time = &token.claims.exp;
-
-        Ok((role, uuid.to_owned(), *exp_time))
-}
-
 pub fn decode_token(token: &str) -> Result<TokenData<Claims>, jsonwebtoken::errors::Error> {
     Ok(decode::<Claims>(
         &token,
@@ -93,12 +78,31 @@ pub fn decode_token(token: &str) -> Result<TokenData<Claims>, jsonwebtoken::erro
     )?)
 }
 
+/// Replaces an id with one that is stable within a session but can't be linked to the
+/// id, or to the same id in another session, without the JWT secret
+pub fn pseudonym(session_id: Uuid, id: Uuid) -> Uuid {
+    pseudonym_with(JWT_SECRET_KEY.as_bytes(), session_id, id)
+}
+
+fn pseudonym_with(secret: &[u8], session_id: Uuid, id: Uuid) -> Uuid {
+    let digest = Sha256::new()
+        .chain_update(secret)
+        .chain_update(session_id.as_bytes())
+        .chain_update(id.as_bytes())
+        .finalize();
+
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+
+    uuid::Builder::from_random_bytes(bytes).into_uuid()
+}
+
 pub fn hash_password(password: &str) -> Result<PasswordHashString, argon2::password_hash::Error> {
     let argon2 = Argon2::default();
 
     let pwd = password.as_bytes();
 
-    let salt = SaltString::from_b64(&PASSWORD_SECRET_KEY).expect("Unable to generate salt").to_owned();
+    let salt = SaltString::generate(&mut rand::rngs::OsRng);
 
     let result = argon2.hash_password(
         pwd,
@@ -122,4 +126,120 @@ pub fn verify_password(hash_string: String, password: &str) -> Result<bool, argo
     } else {
         Ok(false)
     }
-}
\ No newline at end of file
+}
+
+/// True if a hash should be replaced on the next successful sign in:
+/// it uses the shared legacy salt or different Argon2 parameters than the current default
+pub fn needs_rehash(hash_string: &str) -> bool {
+    let hash = match PasswordHashString::from_str(hash_string) {
+        Ok(h) => h,
+        Err(_) => return true,
+    };
+
+    let hash = hash.password_hash();
+
+    let legacy_salt = match (hash.salt, PASSWORD_SECRET_KEY.as_deref()) {
+        (Some(salt), Some(key)) => salt.as_str() == key,
+        _ => false,
+    };
+
+    let default = argon2::Params::default();
+
+    let default_params = argon2::Params::try_from(&hash)
+        .map(|p| (p.m_cost(), p.t_cost(), p.p_cost()) == (default.m_cost(), default.t_cost(), default.p_cost()))
+        .unwrap_or(false);
+
+    legacy_salt || !default_params || hash.algorithm != argon2::Algorithm::default().ident()
+}
+
+/// Checks a new password against the password policy in config_variables.
+/// Returns every rule the password breaks in one error.
+pub fn check_password_policy(password: &str, email: &str) -> async_graphql::Result<()> {
+    let mut problems = Vec::new();
+
+    let length = password.chars().count();
+
+    if length < PASSWORD_MIN_LENGTH {
+        problems.push(format!("be at least {} characters", PASSWORD_MIN_LENGTH));
+    }
+
+    if length > PASSWORD_MAX_LENGTH {
+        problems.push(format!("be at most {} characters", PASSWORD_MAX_LENGTH));
+    }
+
+    let classes = [
+        password.chars().any(|c| c.is_lowercase()),
+        password.chars().any(|c| c.is_uppercase()),
+        password.chars().any(|c| c.is_numeric()),
+        password.chars().any(|c| !c.is_alphanumeric()),
+    ].iter().filter(|c| **c).count();
+
+    if classes < PASSWORD_MIN_CHARACTER_CLASSES {
+        problems.push(format!(
+            "use at least {} of lowercase letters, uppercase letters, numbers and symbols",
+            PASSWORD_MIN_CHARACTER_CLASSES,
+        ));
+    }
+
+    let local_part = email.split('@').next().unwrap_or_default().to_lowercase();
+
+    if !local_part.is_empty() && password.to_lowercase().contains(&local_part) {
+        problems.push("not contain your email address".to_string());
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(async_graphql::Error::new(format!("Password must {}", problems.join(", "))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &[u8] = b"test secret";
+
+    #[test]
+    fn stable_within_a_session() {
+        let (session, id) = (Uuid::new_v4(), Uuid::new_v4());
+
+        assert_eq!(pseudonym_with(SECRET, session, id), pseudonym_with(SECRET, session, id));
+    }
+
+    #[test]
+    fn differs_from_the_id() {
+        let (session, id) = (Uuid::new_v4(), Uuid::new_v4());
+
+        assert_ne!(pseudonym_with(SECRET, session, id), id);
+    }
+
+    #[test]
+    fn differs_between_ids() {
+        let session = Uuid::new_v4();
+
+        assert_ne!(pseudonym_with(SECRET, session, Uuid::new_v4()), pseudonym_with(SECRET, session, Uuid::new_v4()));
+    }
+
+    #[test]
+    fn differs_between_sessions() {
+        let id = Uuid::new_v4();
+
+        assert_ne!(pseudonym_with(SECRET, Uuid::new_v4(), id), pseudonym_with(SECRET, Uuid::new_v4(), id));
+    }
+
+    #[test]
+    fn differs_between_secrets() {
+        let (session, id) = (Uuid::new_v4(), Uuid::new_v4());
+
+        assert_ne!(pseudonym_with(SECRET, session, id), pseudonym_with(b"another secret", session, id));
+    }
+
+    #[test]
+    fn is_a_version_4_uuid() {
+        let pseudonym = pseudonym_with(SECRET, Uuid::new_v4(), Uuid::new_v4());
+
+        assert_eq!(pseudonym.get_version(), Some(uuid::Version::Random));
+        assert_eq!(pseudonym.get_variant(), uuid::Variant::RFC4122);
+    }
+}
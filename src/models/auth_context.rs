@@ -0,0 +1,219 @@
+use std::fmt;
+use std::future::{ready, Ready};
+use std::str::FromStr;
+
+use actix_web::dev::Payload;
+use actix_web::http::{header, StatusCode};
+use actix_web::{FromRequest, HttpRequest, HttpResponse, ResponseError};
+use async_graphql::{Context, Error, ErrorExtensions};
+use chrono::DateTime;
+use jsonwebtoken::errors::ErrorKind;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::common_utils::UserRole;
+
+use super::{decode_token, ApiKey, ApiKeyScopes, Granularity, RevokedToken, User};
+
+/// Why a request could not be authorized.
+/// code() is returned in extensions.code of GraphQL errors and is stable for clients to branch on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AuthError {
+    /// No Authorization header
+    Missing,
+    /// The header isn't "Bearer <token>" or the token can't be decoded or verified
+    Invalid,
+    /// The token was valid but has expired
+    Expired,
+    /// The token was revoked by signOut, a password change or an Admin
+    Revoked,
+    /// Signed in, but without the UserRole required
+    Forbidden(UserRole),
+    /// Signed in, but the record belongs to another user
+    NotSelf,
+    /// Signed in with an API key that isn't scoped for the named field
+    OutOfScope(String),
+    /// Reading identifiable personal data without a valid X-Access-Rationale header
+    RationaleRequired,
+    /// Signed in, but granted less than the Granularity required
+    InsufficientGranularity(Granularity),
+}
+
+impl AuthError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            AuthError::Missing | AuthError::Invalid | AuthError::Revoked => "UNAUTHENTICATED",
+            AuthError::Expired => "TOKEN_EXPIRED",
+            AuthError::Forbidden(_) | AuthError::NotSelf | AuthError::OutOfScope(_) => "FORBIDDEN",
+            AuthError::RationaleRequired => "RATIONALE_REQUIRED",
+            AuthError::InsufficientGranularity(_) => "INSUFFICIENT_GRANULARITY",
+        }
+    }
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AuthError::Missing => f.write_str("Not signed in"),
+            AuthError::Invalid => f.write_str("Invalid bearer token"),
+            AuthError::Expired => f.write_str("Bearer token has expired"),
+            AuthError::Revoked => f.write_str("Bearer token has been revoked"),
+            AuthError::Forbidden(role) => write!(f, "Access denied: {} UserRole required", role),
+            AuthError::NotSelf => f.write_str("Access denied: only available for your own records"),
+            AuthError::OutOfScope(field) => write!(f, "Access denied: API key is not scoped for {}", field),
+            AuthError::RationaleRequired => f.write_str("Access denied: identifiable personal data needs an X-Access-Rationale header \
+                of AUTOMATED, ADMINISTRATIVE, RESEARCH or PUBLIC_HEALTH"),
+            AuthError::InsufficientGranularity(g) => write!(f, "Access denied: person records need {:?} access. \
+                Use aggregate queries such as peopleCount, capabilityCountsByName or capabilityCountsByDomain", g),
+        }
+    }
+}
+
+impl ErrorExtensions for AuthError {
+    fn extend(&self) -> Error {
+        Error::new(self.to_string()).extend_with(|_, e| e.set("code", self.code()))
+    }
+}
+
+impl ResponseError for AuthError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AuthError::Forbidden(_) | AuthError::NotSelf | AuthError::OutOfScope(_)
+                | AuthError::RationaleRequired | AuthError::InsufficientGranularity(_) => StatusCode::FORBIDDEN,
+            _ => StatusCode::UNAUTHORIZED,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code())
+            .json(json!({ "code": self.code(), "message": self.to_string() }))
+    }
+}
+
+/// The signed in user making a request, taken from a verified bearer token.
+/// Use as an actix extractor, or read it from the GraphQL context with AuthContext::from_ctx.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AuthContext {
+    pub user_id: Uuid,
+    pub role: UserRole,
+    /// Expiry of the bearer token or API key, seconds since the epoch
+    pub exp: i64,
+    /// Unique id of the bearer token, used to revoke it, or the id of the API key
+    pub jti: Uuid,
+    /// Set if the request was made with an API key rather than a bearer token
+    pub api_key_id: Option<Uuid>,
+    /// The most detailed view of personal data the user is granted
+    pub granularity: Granularity,
+    /// Id of the sign in, or of the API key, that pseudonyms are stable within
+    pub session_id: Uuid,
+}
+
+impl AuthContext {
+
+    /// Reads and verifies the Authorization header of a request
+    pub fn from_request(req: &HttpRequest) -> Result<Self, AuthError> {
+        let value = req.headers()
+            .get(header::AUTHORIZATION)
+            .ok_or(AuthError::Missing)?;
+
+        let jwt = value.to_str()
+            .ok()
+            .and_then(|s| s.split_once(' '))
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("Bearer"))
+            .map(|(_, jwt)| jwt.trim())
+            .filter(|jwt| !jwt.is_empty())
+            .ok_or(AuthError::Invalid)?;
+
+        let token = decode_token(jwt).map_err(|e| match e.kind() {
+            ErrorKind::ExpiredSignature => AuthError::Expired,
+            _ => AuthError::Invalid,
+        })?;
+
+        let role = UserRole::from_str(&token.claims.role).map_err(|_| AuthError::Invalid)?;
+        let user_id = Uuid::from_str(&token.claims.sub).map_err(|_| AuthError::Invalid)?;
+
+        // Tokens issued before jti was added can't be revoked individually, so they are refused
+        let jti = Uuid::from_str(&token.claims.jti).map_err(|_| AuthError::Invalid)?;
+        let session_id = Uuid::from_str(&token.claims.sid).map_err(|_| AuthError::Invalid)?;
+
+        // Fail closed if the denylist can't be checked
+        // Tokens without iat_us are taken as issued at the start of their second
+        let issued_at = token.claims.iat_us
+            .and_then(DateTime::from_timestamp_micros)
+            .or_else(|| DateTime::from_timestamp(token.claims.iat, 0))
+            .ok_or(AuthError::Invalid)?
+            .naive_utc();
+
+        match RevokedToken::is_revoked(jti, user_id, issued_at) {
+            Ok(false) => {},
+            Ok(true) => return Err(AuthError::Revoked),
+            Err(_) => return Err(AuthError::Invalid),
+        };
+
+        Ok(AuthContext {
+            user_id,
+            role,
+            exp: token.claims.exp,
+            jti,
+            api_key_id: None,
+            granularity: token.claims.granularity,
+            session_id,
+        })
+    }
+
+    /// Reads and checks the X-API-Key header of a request. None if there isn't one.
+    /// The key acts as its user with the user's current UserRole.
+    pub fn from_api_key(req: &HttpRequest) -> Option<Result<(Self, ApiKeyScopes), AuthError>> {
+        let value = req.headers().get("X-API-Key")?;
+
+        let key = match value.to_str().map(|s| s.trim()) {
+            Ok(k) if !k.is_empty() => k,
+            _ => return Some(Err(AuthError::Invalid)),
+        };
+
+        let api_key = match ApiKey::authenticate(key) {
+            Ok(Some(k)) => k,
+            _ => return Some(Err(AuthError::Invalid)),
+        };
+
+        let (role, granularity) = match User::get_by_id(&api_key.user_id) {
+            Ok(u) => match UserRole::from_str(&u.role) {
+                Ok(r) => (r, u.granularity),
+                Err(_) => return Some(Err(AuthError::Invalid)),
+            },
+            Err(_) => return Some(Err(AuthError::Invalid)),
+        };
+
+        let auth = AuthContext {
+            user_id: api_key.user_id,
+            role,
+            exp: api_key.expires_at.and_utc().timestamp(),
+            jti: api_key.id,
+            api_key_id: Some(api_key.id),
+            granularity,
+            session_id: api_key.id,
+        };
+
+        Some(Ok((auth, ApiKeyScopes(api_key.scopes))))
+    }
+
+    /// The signed in user for a GraphQL request, or the reason there isn't one
+    pub fn from_ctx(ctx: &Context<'_>) -> async_graphql::Result<Self> {
+        match ctx.data_opt::<AuthContext>() {
+            Some(auth) => Ok(*auth),
+            None => Err(ctx.data_opt::<AuthError>()
+                .cloned()
+                .unwrap_or(AuthError::Missing)
+                .extend()),
+        }
+    }
+}
+
+impl FromRequest for AuthContext {
+    type Error = AuthError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(AuthContext::from_request(req))
+    }
+}
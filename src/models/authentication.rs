@@ -1,137 +1,232 @@
-use crate::errors::CustomError;
+use std::sync::Arc;
+
 use chrono::{Duration, prelude::*};
 use serde::{Serialize, Deserialize};
+use diesel::{self, Insertable, Queryable, ExpressionMethods, OptionalExtension};
+use diesel::{RunQueryDsl, QueryDsl};
 use uuid::Uuid;
+use async_graphql::*;
 
-use crate::{generate_unique_code, schema::{
-    email_verification_code, password_reset_token}
-};
-use crate::database;
+use crate::config_variables::{EMAIL_VERIFICATION_DURATION, PASSWORD_RESET_DURATION};
+use crate::database::connection;
+use crate::mailer::{send_email, Email, Mailer};
+use crate::schema::*;
 
-use diesel::prelude::*;
-use diesel::RunQueryDsl;
-use diesel::{QueryDsl};
+use super::{User, generate_token, hash_token};
 
-#[derive(Serialize, Deserialize, Queryable, Insertable, Debug, Identifiable, AsChangeset, Clone)]
-#[table_name = "email_verification_code"]
+#[derive(Serialize, Deserialize, Queryable, Debug, Clone)]
+#[diesel(table_name = email_verifications)]
+/// A code emailed to a user to confirm they control their email address
 pub struct EmailVerification {
     pub id: Uuid,
+    pub user_id: Uuid,
     pub email_address: String,
-    pub activation_code: String,
+    pub code_hash: String,
     pub expires_on: NaiveDateTime,
+    pub created_at: NaiveDateTime,
 }
 
 #[derive(Debug, Serialize, Deserialize, AsChangeset, Insertable)]
-#[table_name = "email_verification_code"]
+#[diesel(table_name = email_verifications)]
 pub struct InsertableVerification {
+    pub user_id: Uuid,
     pub email_address: String,
-    pub activation_code: String,
+    pub code_hash: String,
     pub expires_on: NaiveDateTime,
 }
 
 impl InsertableVerification {
-    pub fn new(email_address: &String) -> Self {
-        let expires_on = Utc::now().naive_utc() + Duration::minutes(30);
-        let activation_code = generate_unique_code(5, false);
-
-        InsertableVerification {
-            email_address: email_address.to_owned(),
-            activation_code,
+    /// Returns the verification to store and the code to send
+    pub fn new(user: &User) -> (Self, String) {
+        let expires_on = Utc::now().naive_utc() + Duration::seconds(EMAIL_VERIFICATION_DURATION);
+        let (code, code_hash) = generate_token();
+
+        let verification = InsertableVerification {
+            user_id: user.id,
+            email_address: user.email.to_owned(),
+            code_hash,
             expires_on,
-        }
+        };
+
+        (verification, code)
     }
 }
 
 impl EmailVerification {
-    pub fn create(e: &InsertableVerification) -> Result<Self, CustomError> {
-        let conn = database::connection()?;
-        let ev = diesel::insert_into(email_verification_code::table)
+    /// Creates the user's verification, replacing any they already have
+    pub fn create(e: &InsertableVerification) -> Result<Self> {
+        let mut conn = connection()?;
+
+        let ev = diesel::insert_into(email_verifications::table)
             .values(e)
-            .on_conflict(email_verification_code::email_address)
+            .on_conflict(email_verifications::user_id)
             .do_update()
-            .set((
-                email_verification_code::activation_code.eq(&e.activation_code),
-                email_verification_code::expires_on.eq(&e.expires_on),
-            ))
-            .get_result(&conn)?;
+            .set(e)
+            .get_result(&mut conn)?;
+
         Ok(ev)
     }
 
-    pub fn find_by_email(email: &String) -> Result<Self, CustomError> {
-        let conn = database::connection()?;
-        let v = email_verification_code::table
-            .filter(email_verification_code::email_address.eq(&email))
-            .first(&conn)?;
+    pub fn get_by_code(code: &str) -> Result<Option<Self>> {
+        let mut conn = connection()?;
+
+        let v = email_verifications::table
+            .filter(email_verifications::code_hash.eq(hash_token(code)))
+            .first(&mut conn)
+            .optional()?;
+
         Ok(v)
     }
 
-    pub fn delete(id: Uuid) -> Result<usize, CustomError> {
-        let conn = database::connection()?;
-        let res = diesel::delete(email_verification_code::table.filter(
-            email_verification_code::id.eq(id)
-        )).execute(&conn)?;
+    pub fn delete(id: Uuid) -> Result<usize> {
+        let mut conn = connection()?;
+
+        let res = diesel::delete(email_verifications::table)
+            .filter(email_verifications::id.eq(id))
+            .execute(&mut conn)?;
+
         Ok(res)
     }
+
+    /// Creates a verification for the user's current email and sends them the code
+    pub async fn send(mailer: Arc<dyn Mailer>, user: &User) -> Result<()> {
+        let (verification, code) = InsertableVerification::new(user);
+
+        EmailVerification::create(&verification)?;
+
+        send_email(mailer, Email {
+            to: user.email.to_owned(),
+            subject: "Verify your email address".to_string(),
+            body: format!(
+                "Use this code with verifyEmail to confirm your email address:\n\n{}\n\nThe code expires in {} hours.",
+                code,
+                EMAIL_VERIFICATION_DURATION / 3600,
+            ),
+        }).await
+    }
+
+    /// Checks a code and returns the user to mark as verified. The code can only be used once.
+    pub fn redeem(code: &str) -> Result<User> {
+        let verification = EmailVerification::get_by_code(code)?
+            .ok_or_else(|| Error::new("Invalid verification code"))?;
+
+        EmailVerification::delete(verification.id)?;
+
+        if verification.expires_on <= Utc::now().naive_utc() {
+            return Err(Error::new("Verification code has expired. Please request a new one"));
+        }
+
+        let user = User::get_by_id(&verification.user_id)?;
+
+        if user.email != verification.email_address {
+            return Err(Error::new("Email address has changed since this code was sent"));
+        }
+
+        Ok(user)
+    }
 }
 
-#[derive(Serialize, Deserialize, Queryable, Insertable, Debug, Identifiable, AsChangeset, Clone)]
-#[table_name = "password_reset_token"]
+#[derive(Serialize, Deserialize, Queryable, Debug, Clone)]
+#[diesel(table_name = password_reset_tokens)]
+/// A single use token emailed to a user who has forgotten their password
 pub struct PasswordResetToken {
     pub id: Uuid,
-    pub email_address: String,
-    pub reset_token: String,
+    pub user_id: Uuid,
+    pub token_hash: String,
     pub expires_on: NaiveDateTime,
+    pub created_at: NaiveDateTime,
 }
 
 #[derive(Debug, Serialize, Deserialize, AsChangeset, Insertable)]
-#[table_name = "password_reset_token"]
+#[diesel(table_name = password_reset_tokens)]
 pub struct InsertablePasswordResetToken {
-    pub email_address: String,
-    pub reset_token: String,
+    pub user_id: Uuid,
+    pub token_hash: String,
     pub expires_on: NaiveDateTime,
 }
 
 impl InsertablePasswordResetToken {
-    pub fn new(email_address: &String) -> Self {
-        let expires_on = Utc::now().naive_utc() + Duration::minutes(30);
-        let reset_token = generate_unique_code(36, false);
-
-        InsertablePasswordResetToken {
-            email_address: email_address.to_owned(),
-            reset_token,
+    /// Returns the reset token to store and the token to send
+    pub fn new(user_id: Uuid) -> (Self, String) {
+        let expires_on = Utc::now().naive_utc() + Duration::seconds(PASSWORD_RESET_DURATION);
+        let (token, token_hash) = generate_token();
+
+        let reset = InsertablePasswordResetToken {
+            user_id,
+            token_hash,
             expires_on,
-        }
+        };
+
+        (reset, token)
     }
 }
 
 impl PasswordResetToken {
-    pub fn create(e: &InsertablePasswordResetToken) -> Result<Self, CustomError> {
-        let conn = database::connection()?;
-        let ev = diesel::insert_into(password_reset_token::table)
+    /// Creates the user's reset token, replacing any they already have
+    pub fn create(e: &InsertablePasswordResetToken) -> Result<Self> {
+        let mut conn = connection()?;
+
+        let ev = diesel::insert_into(password_reset_tokens::table)
             .values(e)
-            .on_conflict(password_reset_token::email_address)
+            .on_conflict(password_reset_tokens::user_id)
             .do_update()
-            .set((
-                password_reset_token::reset_token.eq(&e.reset_token),
-                password_reset_token::expires_on.eq(&e.expires_on),
-            ))
-            .get_result(&conn)?;
+            .set(e)
+            .get_result(&mut conn)?;
+
         Ok(ev)
     }
 
-    pub fn find_by_token(token: &String) -> Result<Self, CustomError> {
-        let conn = database::connection()?;
-        let v = password_reset_token::table
-            .filter(password_reset_token::reset_token.eq(&token))
-            .first(&conn)?;
+    pub fn get_by_token(token: &str) -> Result<Option<Self>> {
+        let mut conn = connection()?;
+
+        let v = password_reset_tokens::table
+            .filter(password_reset_tokens::token_hash.eq(hash_token(token)))
+            .first(&mut conn)
+            .optional()?;
+
         Ok(v)
     }
 
-    pub fn delete(id: Uuid) -> Result<usize, CustomError> {
-        let conn = database::connection()?;
-        let res = diesel::delete(password_reset_token::table.filter(
-            password_reset_token::id.eq(id)
-        )).execute(&conn)?;
+    pub fn delete(id: Uuid) -> Result<usize> {
+        let mut conn = connection()?;
+
+        let res = diesel::delete(password_reset_tokens::table)
+            .filter(password_reset_tokens::id.eq(id))
+            .execute(&mut conn)?;
+
         Ok(res)
     }
-}
\ No newline at end of file
+
+    /// Creates a reset token for the user and sends it to their email
+    pub async fn send(mailer: Arc<dyn Mailer>, user: &User) -> Result<()> {
+        let (reset, token) = InsertablePasswordResetToken::new(user.id);
+
+        PasswordResetToken::create(&reset)?;
+
+        send_email(mailer, Email {
+            to: user.email.to_owned(),
+            subject: "Reset your password".to_string(),
+            body: format!(
+                "Use this token with resetPassword to choose a new password:\n\n{}\n\nThe token expires in {} minutes. If you didn't ask to reset your password you can ignore this email.",
+                token,
+                PASSWORD_RESET_DURATION / 60,
+            ),
+        }).await
+    }
+
+    /// Checks a token and returns it with the user whose password may be reset.
+    /// The token stays valid until deleted, so a password rejected by the policy can be retried.
+    pub fn check(token: &str) -> Result<(Self, User)> {
+        let reset = PasswordResetToken::get_by_token(token)?
+            .ok_or_else(|| Error::new("Invalid password reset token"))?;
+
+        if reset.expires_on <= Utc::now().naive_utc() {
+            PasswordResetToken::delete(reset.id)?;
+            return Err(Error::new("Password reset token has expired. Please request a new one"));
+        }
+
+        let user = User::get_by_id(&reset.user_id)?;
+
+        Ok((reset, user))
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::fmt::Debug;
 
 use chrono::{prelude::*};
@@ -15,7 +16,9 @@ use crate::{database::connection};
 
 use crate::{schema::*, database};
 
-use crate::models::{Person, Skill, Organization, SkillDomain, Validation, ValidatedLevel};
+use crate::models::{Person, Skill, Organization, SkillDomain, Validation, ValidatedLevel, DeploymentExclusion,
+    ScoredValidation, ScoringPolicy, WeightedDecayPolicy, Cell, protect_cells};
+use crate::graphql::{Keyed, Keyset, Loaders, PageRequest, keyset_page, person_id_field};
 
 #[derive(Debug, Clone, Deserialize, Serialize, Queryable, Identifiable, Insertable, AsChangeset, SimpleObject, Associations)]
 #[diesel(belongs_to(Person))]
@@ -32,7 +35,7 @@ pub struct Capability {
 
     pub domain: SkillDomain,
 
-    #[graphql(visible = false)]
+    #[graphql(visible = false, directive = person_id_field::apply())]
     pub person_id: Uuid, // Person
     pub skill_id: Uuid, // Skill
     pub organization_id: Uuid, // Organization
@@ -44,8 +47,8 @@ pub struct Capability {
     pub updated_at: NaiveDateTime,
     pub retired_at: Option<NaiveDateTime>,
 
-    #[graphql(skip)]
-    pub validation_values: Vec<Option<i64>>,
+    /// Confidence (0 to 1) in validated_level, see WeightedDecayPolicy
+    pub validation_confidence: Option<f32>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, DbEnum, Serialize, Deserialize, Enum, PartialOrd, Ord, Display)]
@@ -97,16 +100,16 @@ impl CapabilityLevel {
 // Graphql
 #[ComplexObject]
 impl Capability {
-    pub async fn person(&self) -> Result<Person> {
-        Person::get_by_id(&self.person_id)
+    pub async fn person(&self, ctx: &Context<'_>) -> Result<Person> {
+        ctx.data::<Loaders>()?.person(self.person_id).await
     }
 
-    pub async fn skill_name(&self) -> Result<String> {
-        Skill::get_name_by_id(&self.skill_id)
+    pub async fn skill_name(&self, ctx: &Context<'_>) -> Result<String> {
+        Ok(ctx.data::<Loaders>()?.skill(self.skill_id).await?.name_en)
     }
 
-    pub async fn skill(&self) -> Result<Skill> {
-        Skill::get_by_id(&self.skill_id)
+    pub async fn skill(&self, ctx: &Context<'_>) -> Result<Skill> {
+        ctx.data::<Loaders>()?.skill(self.skill_id).await
     }
 
     /// Detailed view of validations for this capability
@@ -115,6 +118,12 @@ impl Capability {
     }
 }
 
+impl Keyed for Capability {
+    fn keyset(&self) -> Keyset {
+        Keyset { created_at: self.created_at, id: self.id }
+    }
+}
+
 // Non Graphql
 impl Capability {
     pub fn create(capability: &NewCapability) -> Result<Capability> {
@@ -164,6 +173,22 @@ impl Capability {
         Ok(res)
     }
 
+    /// Returns a page of capabilities ordered by (created_at, id) for cursor pagination
+    pub fn get_page(page: &PageRequest) -> Result<Vec<Self>> {
+        keyset_page!(capabilities, page)
+    }
+
+    /// Returns the number of capabilities in the system
+    pub fn count() -> Result<i64> {
+        let mut conn = connection()?;
+
+        let res = capabilities::table
+            .count()
+            .get_result(&mut conn)?;
+
+        Ok(res)
+    }
+
     pub fn get_count(count: i64) -> Result<Vec<Self>> {
         let mut conn = database::connection()?;
         let res = capabilities::table.limit(count).load::<Capability>(&mut conn)?;
@@ -187,6 +212,18 @@ impl Capability {
         Ok(res)
     }
 
+    /// Returns active capabilities in any of the skills
+    pub fn get_by_skill_ids(ids: &[Uuid]) -> Result<Vec<Self>> {
+        let mut conn = connection()?;
+
+        let res = capabilities::table
+            .filter(capabilities::skill_id.eq_any(ids))
+            .filter(capabilities::retired_at.is_null())
+            .load::<Capability>(&mut conn)?;
+
+        Ok(res)
+    }
+
     pub fn get_by_skill_id_and_level(id: Uuid, level: CapabilityLevel) -> Result<Vec<Self>> {
         let mut conn = connection()?;
 
@@ -219,6 +256,35 @@ impl Capability {
         Ok(res)
     }
 
+    pub fn get_by_organization_id(id: Uuid) -> Result<Vec<Self>> {
+        let mut conn = connection()?;
+
+        let res = capabilities::table
+            .filter(capabilities::organization_id.eq(id))
+            .load::<Capability>(&mut conn)?;
+
+        Ok(res)
+    }
+
+    /// Drops capabilities held by people the exclusion leaves out
+    pub fn exclude_deployed(
+        mut capabilities: Vec<Self>,
+        exclusion: Option<&DeploymentExclusion>,
+    ) -> Result<Vec<Self>> {
+
+        if let Some(exclusion) = exclusion {
+            let mut person_ids: Vec<Uuid> = capabilities.iter().map(|c| c.person_id).collect();
+            person_ids.sort();
+            person_ids.dedup();
+
+            let excluded = exclusion.excluded_person_ids(&person_ids)?;
+
+            capabilities.retain(|c| !excluded.contains(&c.person_id));
+        }
+
+        Ok(capabilities)
+    }
+
     pub fn get_by_domain_and_level(domain: &SkillDomain, level: CapabilityLevel) -> Result<Vec<Self>> {
         let mut conn = connection()?;
 
@@ -240,6 +306,57 @@ impl Capability {
         Ok(res)
     }
 
+    pub fn get_by_person_ids(ids: &[Uuid]) -> Result<Vec<Self>> {
+        let mut conn = connection()?;
+
+        let res = capabilities::table
+            .filter(capabilities::person_id.eq_any(ids))
+            .load::<Capability>(&mut conn)?;
+
+        Ok(res)
+    }
+
+    /// Returns the person's active capability for a skill, if they have one
+    pub fn get_by_person_and_skill_id(person_id: &Uuid, skill_id: &Uuid) -> Result<Option<Self>> {
+        let mut conn = connection()?;
+
+        let res = capabilities::table
+            .filter(capabilities::person_id.eq(person_id))
+            .filter(capabilities::skill_id.eq(skill_id))
+            .filter(capabilities::retired_at.is_null())
+            .first::<Capability>(&mut conn)
+            .optional()?;
+
+        Ok(res)
+    }
+
+    /// Returns the active capabilities held by a set of people in one skill
+    pub fn get_by_person_ids_and_skill_id(person_ids: &[Uuid], skill_id: &Uuid) -> Result<Vec<Self>> {
+        let mut conn = connection()?;
+
+        let res = capabilities::table
+            .filter(capabilities::person_id.eq_any(person_ids))
+            .filter(capabilities::skill_id.eq(skill_id))
+            .filter(capabilities::retired_at.is_null())
+            .load::<Capability>(&mut conn)?;
+
+        Ok(res)
+    }
+
+    /// Returns the number of people holding a validated level in a skill
+    pub fn count_validated_by_skill_id(skill_id: &Uuid) -> Result<i64> {
+        let mut conn = connection()?;
+
+        let res = capabilities::table
+            .filter(capabilities::skill_id.eq(skill_id))
+            .filter(capabilities::retired_at.is_null())
+            .filter(capabilities::validated_level.is_not_null())
+            .select(count(capabilities::person_id))
+            .first(&mut conn)?;
+
+        Ok(res)
+    }
+
     pub fn get_level_counts_by_name(name: String) -> Result<Vec<CapabilityCount>> {
         let mut conn = connection()?;
 
@@ -247,6 +364,7 @@ impl Capability {
 
         let res: Vec<(String, SkillDomain, Option<CapabilityLevel>, i64)> = capabilities::table
             .filter(capabilities::skill_id.eq(skill_id))
+            .filter(capabilities::validated_level.is_not_null())
             .group_by((capabilities::domain, capabilities::validated_level, capabilities::name_en))
             .select((capabilities::name_en, capabilities::domain, capabilities::validated_level, count(capabilities::id)))
             .order_by((capabilities::name_en, capabilities::validated_level))
@@ -260,7 +378,7 @@ impl Capability {
             counts.push(count);
         }
 
-        Ok(counts)
+        Ok(CapabilityCount::protected(counts))
     }
 
     pub fn get_level_counts_by_domain(domain: SkillDomain) -> Result<Vec<CapabilityCount>> {
@@ -268,6 +386,7 @@ impl Capability {
 
         let res: Vec<(String, SkillDomain, Option<CapabilityLevel>, i64)> = capabilities::table
             .filter(capabilities::domain.eq(domain))
+            .filter(capabilities::validated_level.is_not_null())
             .group_by((capabilities::domain, capabilities::validated_level, capabilities::name_en))
             .select((capabilities::name_en, capabilities::domain, capabilities::validated_level, count(capabilities::id)))
             .order_by((capabilities::name_en, capabilities::validated_level))
@@ -281,45 +400,45 @@ impl Capability {
             counts.push(count);
         }
 
-        Ok(counts)
+        Ok(CapabilityCount::protected(counts))
     }
 
-    /// Updates a Capability based on a new validation
-    pub fn update_from_validation(&mut self, validated_level: &CapabilityLevel) -> Result<Self> {
-
-        self.validation_values.push(Some(ValidatedLevel::get_value_from_capability_level(validated_level)));
-
-        let values: Option<Vec<i64>> = self.validation_values.clone().into_iter().collect();
+    /// Rescores the capability with the default policy after its validations change
+    pub fn rescore(&mut self) -> Result<ValidatedLevel> {
+        self.score_validations(&WeightedDecayPolicy::default())
+    }
 
-        let values = values.unwrap();
+    /// Scores the capability's validations with policy, then saves the resulting
+    /// validated_level and validation_confidence. Below the policy's quorum the
+    /// capability keeps the level it had, if any.
+    pub fn score_validations(&mut self, policy: &dyn ScoringPolicy) -> Result<ValidatedLevel> {
 
-        let validation_average: i64 = values.iter().sum::<i64>() / values.len() as i64;
+        let validations = Validation::get_by_capability_id(&self.id)?;
 
-        let validated_level = ValidatedLevel::get_capability_level_from_value(&validation_average);
+        let validator_ids: Vec<Uuid> = validations.iter().map(|v| v.validator_id).collect();
 
-        self.validated_level = Some(validated_level);
+        let validator_levels: HashMap<Uuid, CapabilityLevel> = Capability::get_by_person_ids_and_skill_id(&validator_ids, &self.skill_id)?
+            .into_iter()
+            .filter_map(|c| c.validated_level.map(|l| (c.person_id, l)))
+            .collect();
 
-        self.update()
-    }
+        let now = chrono::Utc::now().naive_utc();
 
-    /// Updates a Capability based on a vector of validations
-    pub fn update_from_batch_validations(&mut self, validated_levels: &Vec<CapabilityLevel>) -> Result<Self> {
+        let scored: Vec<ScoredValidation> = validations.iter()
+            .map(|v| ScoredValidation::new(v, validator_levels.get(&v.validator_id).copied(), now))
+            .collect();
 
-        for v in validated_levels {
-            self.validation_values.push(Some(ValidatedLevel::get_value_from_capability_level(v)));
-        };
-        
-        let values: Option<Vec<i64>> = self.validation_values.clone().into_iter().collect();
-        
-        let values = values.unwrap();
+        let mut level = policy.score(&scored);
 
-        let validation_average: i64 = values.iter().sum::<i64>() / values.len() as i64;
+        level.capability_level = level.capability_level.or(self.validated_level);
 
-        let validated_level = ValidatedLevel::get_capability_level_from_value(&validation_average);
+        self.validated_level = level.capability_level;
+        self.validation_confidence = Some(level.confidence);
+        self.updated_at = now;
 
-        self.validated_level = Some(validated_level);
+        self.update()?;
 
-        self.update()
+        Ok(level)
     }
     
     /// Updates a Capability based on changed data
@@ -346,7 +465,6 @@ pub struct NewCapability {
     pub skill_id: Uuid, // Skill
     pub organization_id: Uuid,
     pub self_identified_level: CapabilityLevel,
-    pub validation_values: Vec<i64>,
 }
 
 impl NewCapability {
@@ -360,8 +478,6 @@ impl NewCapability {
 
         let skill = Skill::get_by_id(&skill_id).expect("Unable to get skill");
 
-        let self_identified_value: i64 = ValidatedLevel::get_value_from_capability_level(&self_identified_level);
-        
         NewCapability {
             name_en: skill.name_en,
             name_fr: skill.name_fr,
@@ -370,7 +486,6 @@ impl NewCapability {
             skill_id: skill.id,
             organization_id: organization_id,
             self_identified_level,
-            validation_values: vec![self_identified_value],
         }
     }
 }
@@ -380,7 +495,9 @@ pub struct CapabilityCount {
     pub name: String,
     pub domain: SkillDomain,
     pub level: String,
-    pub counts: i64,
+    /// Null when suppressed to protect small groups
+    pub counts: Option<i64>,
+    pub suppressed: bool,
 }
 
 impl From<(String, SkillDomain, Option<CapabilityLevel>, i64)> for CapabilityCount {
@@ -388,8 +505,10 @@ impl From<(String, SkillDomain, Option<CapabilityLevel>, i64)> for CapabilityCou
         CapabilityCount {
             name,
             domain,
-            level: level.expect("Unable to translate Validated Level").to_string(),
-            counts,
+            // Count queries filter out capabilities without a validated level
+            level: level.expect("Counts need a validated level").to_string(),
+            counts: Some(counts),
+            suppressed: false,
         }
     }
 }
@@ -400,7 +519,23 @@ impl CapabilityCount {
             name,
             domain,
             level,
-            counts,
+            counts: Some(counts),
+            suppressed: false,
         }
     }
+
+    /// Suppresses small counts, and counts they could be worked out from, across the
+    /// results of one query by capability name and level. See protect_cells.
+    pub fn protected(mut counts: Vec<Self>) -> Vec<Self> {
+        let published = protect_cells(&counts.iter()
+            .map(|c| Cell { row: &c.name, column: &c.level, count: c.counts.unwrap_or(0) })
+            .collect::<Vec<Cell>>());
+
+        for (count, p) in counts.iter_mut().zip(published) {
+            count.suppressed = p.is_none();
+            count.counts = p;
+        }
+
+        counts
+    }
 }
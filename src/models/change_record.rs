@@ -0,0 +1,389 @@
+use chrono::prelude::*;
+use diesel_derive_enum::DbEnum;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use diesel::{self, Insertable, Queryable, BoolExpressionMethods, ExpressionMethods, PgAnyJsonExpressionMethods};
+use diesel::{RunQueryDsl, QueryDsl};
+use uuid::Uuid;
+use async_graphql::*;
+
+use crate::schema::*;
+use crate::database::connection;
+use crate::common_utils::REDACTED;
+use crate::graphql::{identifying_field, person_id_field};
+
+use super::{ApiKey, AuthContext, Capability, ContactData, Deployment, EmployeeData, HrStatePeriod, OrgOwnership, OrgTier, Person, Role, Team, TeamOwnership, User};
+
+/// Fields that change on every write and are left out of diffs
+const IGNORED_FIELDS: [&str; 1] = ["updated_at"];
+
+/// An entity whose changes are recorded in change_records
+pub trait Audited: Serialize {
+    /// Name stored in change_records.entity_type
+    const ENTITY: &'static str;
+    /// Fields whose values are never written to history. A change is still recorded.
+    const REDACTED_FIELDS: &'static [&'static str] = &[];
+
+    fn entity_id(&self) -> Uuid;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, DbEnum, Serialize, Deserialize, Enum)]
+#[ExistingTypePath = "crate::schema::sql_types::ChangeAction"]
+pub enum ChangeAction {
+    Create,
+    Update,
+    Delete,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Queryable, Insertable, SimpleObject)]
+#[graphql(complex)]
+#[diesel(table_name = change_records)]
+/// An append-only record of one mutation to an entity
+pub struct ChangeRecord {
+    pub id: Uuid,
+    /// The kind of entity changed, e.g. "person" or "role"
+    pub entity_type: String,
+    #[graphql(directive = person_id_field::apply())]
+    pub entity_id: Uuid,
+    pub action: ChangeAction,
+    #[graphql(skip)]
+    pub changes: Value,
+    #[graphql(directive = person_id_field::apply())]
+    /// The user whose token made the change
+    pub actor_id: Option<Uuid>,
+    pub changed_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, SimpleObject)]
+/// Before and after values of a single field
+pub struct FieldChange {
+    pub field: String,
+    pub before: Json<Value>,
+    pub after: Json<Value>,
+}
+
+#[ComplexObject]
+impl ChangeRecord {
+
+    #[graphql(directive = identifying_field::apply())]
+    /// The fields changed, in field name order
+    pub async fn changes(&self) -> Result<Vec<FieldChange>> {
+        let mut changes: Vec<FieldChange> = match &self.changes {
+            Value::Object(map) => map.iter()
+                .map(|(field, change)| FieldChange {
+                    field: field.to_owned(),
+                    before: Json(change["before"].clone()),
+                    after: Json(change["after"].clone()),
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        changes.sort_by(|a, b| a.field.cmp(&b.field));
+
+        Ok(changes)
+    }
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = change_records)]
+struct NewChangeRecord {
+    entity_type: String,
+    entity_id: Uuid,
+    action: ChangeAction,
+    changes: Value,
+    actor_id: Option<Uuid>,
+}
+
+/// Returns the Uuid of the signed in user from the request's JWT claim
+pub fn acting_user(ctx: &Context<'_>) -> Option<Uuid> {
+    ctx.data_opt::<AuthContext>().map(|a| a.user_id)
+}
+
+fn to_fields<T: Audited>(entity: Option<&T>) -> Result<Map<String, Value>> {
+    match entity {
+        Some(e) => match serde_json::to_value(e)? {
+            Value::Object(map) => Ok(map),
+            _ => Err(Error::new(format!("{} does not serialize to an object", T::ENTITY))),
+        },
+        None => Ok(Map::new()),
+    }
+}
+
+/// Builds {"field": {"before": .., "after": ..}} for every field that differs
+fn diff<T: Audited>(before: Option<&T>, after: Option<&T>) -> Result<Value> {
+
+    let before = to_fields(before)?;
+    let after = to_fields(after)?;
+
+    let mut changes = Map::new();
+
+    for field in before.keys().chain(after.keys().filter(|k| !before.contains_key(*k))) {
+        if IGNORED_FIELDS.contains(&field.as_str()) {
+            continue;
+        }
+
+        let old = before.get(field).cloned().unwrap_or(Value::Null);
+        let new = after.get(field).cloned().unwrap_or(Value::Null);
+
+        if old == new {
+            continue;
+        }
+
+        let change = if T::REDACTED_FIELDS.contains(&field.as_str()) {
+            json!({ "before": REDACTED, "after": REDACTED })
+        } else {
+            json!({ "before": old, "after": new })
+        };
+
+        changes.insert(field.to_owned(), change);
+    }
+
+    Ok(Value::Object(changes))
+}
+
+// Non Graphql
+impl ChangeRecord {
+
+    fn insert(record: &NewChangeRecord) -> Result<ChangeRecord> {
+        let mut conn = connection()?;
+
+        let res = diesel::insert_into(change_records::table)
+            .values(record)
+            .get_result(&mut conn)?;
+
+        Ok(res)
+    }
+
+    fn log<T: Audited>(
+        entity_id: Uuid,
+        action: ChangeAction,
+        before: Option<&T>,
+        after: Option<&T>,
+        actor_id: Option<Uuid>,
+    ) -> Result<Option<ChangeRecord>> {
+
+        let changes = diff(before, after)?;
+
+        if action == ChangeAction::Update && changes.as_object().is_some_and(|c| c.is_empty()) {
+            return Ok(None);
+        }
+
+        let record = ChangeRecord::insert(&NewChangeRecord {
+            entity_type: T::ENTITY.to_string(),
+            entity_id,
+            action,
+            changes,
+            actor_id,
+        })?;
+
+        Ok(Some(record))
+    }
+
+    /// Records every field of a newly created entity
+    pub fn log_create<T: Audited>(entity: &T, actor_id: Option<Uuid>) -> Result<Option<ChangeRecord>> {
+        ChangeRecord::log(entity.entity_id(), ChangeAction::Create, None, Some(entity), actor_id)
+    }
+
+    /// Records the fields that differ between before and after. Nothing is written if none do.
+    pub fn log_update<T: Audited>(before: &T, after: &T, actor_id: Option<Uuid>) -> Result<Option<ChangeRecord>> {
+        ChangeRecord::log(after.entity_id(), ChangeAction::Update, Some(before), Some(after), actor_id)
+    }
+
+    /// Records every field of a deleted entity as its before value
+    pub fn log_delete<T: Audited>(entity: &T, actor_id: Option<Uuid>) -> Result<Option<ChangeRecord>> {
+        ChangeRecord::log(entity.entity_id(), ChangeAction::Delete, Some(entity), None, actor_id)
+    }
+
+    /// Returns changes made after since to any of a set of entities, most recent first
+    pub fn get_by_entity_ids_since(ids: &[Uuid], since: NaiveDateTime) -> Result<Vec<Self>> {
+        let mut conn = connection()?;
+
+        let res = change_records::table
+            .filter(change_records::entity_id.eq_any(ids))
+            .filter(change_records::changed_at.gt(since))
+            .order_by(change_records::changed_at.desc())
+            .load::<ChangeRecord>(&mut conn)?;
+
+        Ok(res)
+    }
+
+    /// Returns the ids of entities of a type whose field was ever set to or from one of values
+    pub fn get_entity_ids_by_field_values(entity_type: &str, field: &str, values: &[Uuid]) -> Result<Vec<Uuid>> {
+        let mut conn = connection()?;
+
+        let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+
+        let res = change_records::table
+            .filter(change_records::entity_type.eq(entity_type))
+            .filter(change_records::changes.retrieve_as_object(field).retrieve_as_text("after").eq_any(&values)
+                .or(change_records::changes.retrieve_as_object(field).retrieve_as_text("before").eq_any(&values)))
+            .select(change_records::entity_id)
+            .distinct()
+            .load::<Uuid>(&mut conn)?;
+
+        Ok(res)
+    }
+
+    /// Rebuilds an entity as it was at as_of by undoing the changes in records made after it.
+    /// records must all belong to current, in any order. Returns None if the entity was
+    /// created after as_of. Changes made before history was recorded cannot be undone.
+    pub fn rewind<T: Audited + DeserializeOwned + Clone>(
+        current: &T,
+        records: &[&ChangeRecord],
+        as_of: NaiveDateTime,
+    ) -> Result<Option<T>> {
+
+        let mut later: Vec<&&ChangeRecord> = records.iter()
+            .filter(|r| r.changed_at > as_of)
+            .collect();
+
+        if later.is_empty() {
+            return Ok(Some(current.clone()));
+        }
+
+        later.sort_by_key(|r| std::cmp::Reverse(r.changed_at));
+
+        let mut fields = to_fields(Some(current))?;
+
+        for record in later {
+            if record.action == ChangeAction::Create {
+                return Ok(None);
+            }
+
+            if let Value::Object(changes) = &record.changes {
+                for (field, change) in changes {
+                    if !T::REDACTED_FIELDS.contains(&field.as_str()) {
+                        fields.insert(field.to_owned(), change["before"].clone());
+                    }
+                }
+            }
+        }
+
+        Ok(Some(serde_json::from_value(Value::Object(fields))?))
+    }
+
+    /// Returns the history of an entity, most recent change first
+    pub fn get_by_entity_id(id: Uuid, limit: i64) -> Result<Vec<Self>> {
+        let mut conn = connection()?;
+
+        let res = change_records::table
+            .filter(change_records::entity_id.eq(id))
+            .order_by(change_records::changed_at.desc())
+            .limit(limit)
+            .load::<ChangeRecord>(&mut conn)?;
+
+        Ok(res)
+    }
+}
+
+impl Audited for Person {
+    const ENTITY: &'static str = "person";
+    // Readable through the history query, which doesn't check for an access rationale
+    const REDACTED_FIELDS: &'static [&'static str] = &["family_name", "given_name", "peoplesoft_id", "orcid_id"];
+
+    fn entity_id(&self) -> Uuid {
+        self.id
+    }
+}
+
+impl Audited for Role {
+    const ENTITY: &'static str = "role";
+
+    fn entity_id(&self) -> Uuid {
+        self.id
+    }
+}
+
+impl Audited for Capability {
+    const ENTITY: &'static str = "capability";
+
+    fn entity_id(&self) -> Uuid {
+        self.id
+    }
+}
+
+impl Audited for Team {
+    const ENTITY: &'static str = "team";
+
+    fn entity_id(&self) -> Uuid {
+        self.id
+    }
+}
+
+impl Audited for OrgTier {
+    const ENTITY: &'static str = "org_tier";
+
+    fn entity_id(&self) -> Uuid {
+        self.id
+    }
+}
+
+impl Audited for TeamOwnership {
+    const ENTITY: &'static str = "team_ownership";
+
+    fn entity_id(&self) -> Uuid {
+        self.id
+    }
+}
+
+impl Audited for OrgOwnership {
+    const ENTITY: &'static str = "org_ownership";
+
+    fn entity_id(&self) -> Uuid {
+        self.id
+    }
+}
+
+impl Audited for Deployment {
+    const ENTITY: &'static str = "deployment";
+
+    fn entity_id(&self) -> Uuid {
+        self.id
+    }
+}
+
+impl Audited for EmployeeData {
+    const ENTITY: &'static str = "employee_data";
+
+    fn entity_id(&self) -> Uuid {
+        self.id
+    }
+}
+
+impl Audited for HrStatePeriod {
+    const ENTITY: &'static str = "hr_state_period";
+
+    fn entity_id(&self) -> Uuid {
+        self.id
+    }
+}
+
+impl Audited for ContactData {
+    const ENTITY: &'static str = "contact_data";
+    // The contact details themselves, the same fields the granularity filter redacts
+    const REDACTED_FIELDS: &'static [&'static str] = &["email", "phone", "work_address", "city", "province",
+        "postal_code", "country"];
+
+    fn entity_id(&self) -> Uuid {
+        self.id
+    }
+}
+
+impl Audited for User {
+    const ENTITY: &'static str = "user";
+    const REDACTED_FIELDS: &'static [&'static str] = &["hash", "access_key"];
+
+    fn entity_id(&self) -> Uuid {
+        self.id
+    }
+}
+
+impl Audited for ApiKey {
+    const ENTITY: &'static str = "api_key";
+    const REDACTED_FIELDS: &'static [&'static str] = &["key_hash"];
+
+    fn entity_id(&self) -> Uuid {
+        self.id
+    }
+}
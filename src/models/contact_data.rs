@@ -0,0 +1,201 @@
+use chrono::prelude::*;
+use diesel_derive_enum::DbEnum;
+use serde::{Deserialize, Serialize};
+use diesel::{self, Connection, Insertable, Queryable, ExpressionMethods, BoolExpressionMethods};
+use diesel::{RunQueryDsl, QueryDsl};
+use uuid::Uuid;
+use async_graphql::*;
+
+use crate::schema::*;
+use crate::database::connection;
+use crate::graphql::{identifying_field, person_id_field};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, DbEnum, Serialize, Deserialize, Enum)]
+#[ExistingTypePath = "crate::schema::sql_types::ContactType"]
+/// What a person uses a contact for
+pub enum ContactType {
+    Work,
+    Mobile,
+    Alternate,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Queryable, Insertable, AsChangeset, SimpleObject)]
+#[diesel(table_name = contact_datas)]
+#[diesel(belongs_to(Person))]
+#[diesel(treat_none_as_null = true)]
+/// A way to reach a person over a period. A person can have several contacts of each
+/// ContactType, one of which is current and primary.
+/// Referenced by Person
+pub struct ContactData {
+    pub id: Uuid,
+    #[graphql(directive = person_id_field::apply())]
+    pub person_id: Uuid,
+    pub contact_type: ContactType,
+
+    #[graphql(directive = identifying_field::apply())]
+    pub email: Option<String>,
+    #[graphql(directive = identifying_field::apply())]
+    pub phone: Option<String>,
+    #[graphql(directive = identifying_field::apply())]
+    pub work_address: Option<String>,
+    #[graphql(directive = identifying_field::apply())]
+    pub city: Option<String>,
+    #[graphql(directive = identifying_field::apply())]
+    pub province: Option<String>,
+    #[graphql(directive = identifying_field::apply())]
+    pub postal_code: Option<String>,
+    #[graphql(directive = identifying_field::apply())]
+    pub country: Option<String>,
+
+    pub is_primary: bool,
+
+    pub start_datestamp: NaiveDateTime,
+    pub end_date: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+// Non Graphql
+impl ContactData {
+    /// Saves a contact. A new current primary contact replaces the person's existing one.
+    pub fn create(contact: &NewContactData) -> Result<ContactData> {
+        let mut conn = connection()?;
+
+        let res = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            if contact.is_primary && contact.end_date.is_none() {
+                diesel::update(contact_datas::table)
+                    .filter(contact_datas::person_id.eq(contact.person_id))
+                    .filter(contact_datas::is_primary.eq(true))
+                    .filter(contact_datas::end_date.is_null())
+                    .set((
+                        contact_datas::is_primary.eq(false),
+                        contact_datas::updated_at.eq(chrono::Utc::now().naive_utc()),
+                    ))
+                    .execute(conn)?;
+            }
+
+            diesel::insert_into(contact_datas::table)
+                .values(contact)
+                .get_result(conn)
+        })?;
+
+        Ok(res)
+    }
+
+    pub fn batch_create(contacts: Vec<NewContactData>) -> Result<usize> {
+        let mut conn = connection()?;
+
+        let res = diesel::insert_into(contact_datas::table)
+            .values(contacts)
+            .execute(&mut conn)?;
+
+        Ok(res)
+    }
+
+    pub fn get_by_id(id: &Uuid) -> Result<Self> {
+        let mut conn = connection()?;
+
+        let res = contact_datas::table
+            .filter(contact_datas::id.eq(id))
+            .first(&mut conn)?;
+
+        Ok(res)
+    }
+
+    /// Returns all contacts for a set of people, primary first then most recent,
+    /// used to batch person -> contact lookups
+    pub fn get_by_person_ids(ids: &[Uuid]) -> Result<Vec<Self>> {
+        let mut conn = connection()?;
+
+        let res = contact_datas::table
+            .filter(contact_datas::person_id.eq_any(ids))
+            .order_by((contact_datas::is_primary.desc(), contact_datas::start_datestamp.desc()))
+            .load::<ContactData>(&mut conn)?;
+
+        Ok(res)
+    }
+
+    /// Returns the current primary contact of each of a set of people who have one
+    pub fn get_primary_by_person_ids(ids: &[Uuid]) -> Result<Vec<Self>> {
+        let mut conn = connection()?;
+
+        let res = contact_datas::table
+            .filter(contact_datas::person_id.eq_any(ids))
+            .filter(contact_datas::is_primary.eq(true))
+            .filter(contact_datas::end_date.is_null())
+            .load::<ContactData>(&mut conn)?;
+
+        Ok(res)
+    }
+
+    /// Saves changes to a contact. Making it the current primary contact replaces the
+    /// person's existing one.
+    pub fn update(&mut self) -> Result<Self> {
+        let mut conn = connection()?;
+
+        self.updated_at = chrono::Utc::now().naive_utc();
+
+        let res = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            if self.is_primary && self.end_date.is_none() {
+                diesel::update(contact_datas::table)
+                    .filter(contact_datas::person_id.eq(self.person_id))
+                    .filter(contact_datas::id.ne(self.id))
+                    .filter(contact_datas::is_primary.eq(true).and(contact_datas::end_date.is_null()))
+                    .set((
+                        contact_datas::is_primary.eq(false),
+                        contact_datas::updated_at.eq(self.updated_at),
+                    ))
+                    .execute(conn)?;
+            }
+
+            diesel::update(contact_datas::table)
+                .filter(contact_datas::id.eq(&self.id))
+                .set(self.clone())
+                .get_result(conn)
+        })?;
+
+        Ok(res)
+    }
+
+    pub fn in_effect_on(&self, date: NaiveDateTime) -> bool {
+        self.start_datestamp <= date && self.end_date.is_none_or(|d| d > date)
+    }
+
+    /// Checks a contact before it is saved
+    pub fn check(
+        email: Option<&str>,
+        phone: Option<&str>,
+        work_address: Option<&str>,
+        start_datestamp: NaiveDateTime,
+        end_date: Option<NaiveDateTime>,
+    ) -> Result<()> {
+
+        if [email, phone, work_address].iter().all(|f| f.is_none_or(|s| s.trim().is_empty())) {
+            return Err(Error::new("A contact needs an email, phone or work_address"));
+        }
+
+        if end_date.is_some_and(|d| d <= start_datestamp) {
+            return Err(Error::new("end_date must be after start_datestamp"));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Insertable, InputObject)]
+#[diesel(table_name = contact_datas)]
+pub struct NewContactData {
+    pub person_id: Uuid,
+    pub contact_type: ContactType,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub work_address: Option<String>,
+    pub city: Option<String>,
+    pub province: Option<String>,
+    pub postal_code: Option<String>,
+    pub country: Option<String>,
+    #[graphql(default)]
+    pub is_primary: bool,
+    pub start_datestamp: NaiveDateTime,
+    pub end_date: Option<NaiveDateTime>,
+}
@@ -0,0 +1,313 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{prelude::*, Duration, Months};
+use serde::{Deserialize, Serialize};
+use diesel::{self, Insertable, Queryable, ExpressionMethods};
+use diesel::{RunQueryDsl, QueryDsl};
+use uuid::Uuid;
+use async_graphql::*;
+
+use crate::config_variables::{DATE_FORMAT, DEPLOYMENT_WINDOW_MONTHS};
+
+use crate::schema::*;
+use crate::database::connection;
+use crate::graphql::Loaders;
+
+use super::{Person, Role};
+
+#[derive(Debug, Clone, Deserialize, Serialize, Queryable, Insertable, AsChangeset)]
+#[diesel(table_name = deployments)]
+#[diesel(belongs_to(Person))]
+#[diesel(belongs_to(Role))]
+/// A person pulled onto an emergency response or surge event
+/// Referenced by Person
+pub struct Deployment {
+    pub id: Uuid,
+    pub person_id: Uuid,
+    /// The role the person was pulled from, if any
+    pub role_id: Option<Uuid>,
+    pub event_name: String,
+    /// Share of the person's time (FTE) spent on the deployment
+    pub effort: f64,
+
+    pub start_datestamp: NaiveDateTime,
+    pub end_date: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[Object]
+impl Deployment {
+
+    pub async fn id(&self) -> Uuid {
+        self.id
+    }
+
+    pub async fn person(&self, ctx: &Context<'_>) -> Result<Person> {
+        ctx.data::<Loaders>()?.person(self.person_id).await
+    }
+
+    /// The role the person was pulled from
+    pub async fn originating_role(&self, ctx: &Context<'_>) -> Result<Option<Role>> {
+        match self.role_id {
+            Some(r) => Ok(Some(ctx.data::<Loaders>()?.role(r).await?)),
+            None => Ok(None)
+        }
+    }
+
+    pub async fn event_name(&self) -> Result<String> {
+        Ok(self.event_name.to_owned())
+    }
+
+    pub async fn effort(&self) -> Result<f64> {
+        Ok(self.effort)
+    }
+
+    pub async fn start_date(&self) -> Result<String> {
+        Ok(self.start_datestamp.format(DATE_FORMAT).to_string())
+    }
+
+    pub async fn end_date(&self) -> Result<String> {
+        match self.end_date {
+            Some(d) => Ok(d.format(DATE_FORMAT).to_string()),
+            None => Ok("Still Deployed".to_string())
+        }
+    }
+
+    /// Length of the deployment in days, counted to today if it is still underway
+    pub async fn days(&self) -> Result<i64> {
+        Ok(self.days_between(self.start_datestamp, chrono::Utc::now().naive_utc()))
+    }
+
+    pub async fn created_at(&self) -> Result<String> {
+        Ok(self.created_at.format(DATE_FORMAT).to_string())
+    }
+
+    pub async fn updated_at(&self) -> Result<String> {
+        Ok(self.updated_at.format(DATE_FORMAT).to_string())
+    }
+}
+
+// Non Graphql
+impl Deployment {
+    pub fn create(deployment: &NewDeployment) -> Result<Deployment> {
+        let mut conn = connection()?;
+
+        let res = diesel::insert_into(deployments::table)
+            .values(deployment)
+            .get_result(&mut conn)?;
+
+        Ok(res)
+    }
+
+    pub fn get_by_id(id: &Uuid) -> Result<Self> {
+        let mut conn = connection()?;
+
+        let res = deployments::table
+            .filter(deployments::id.eq(id))
+            .first(&mut conn)?;
+
+        Ok(res)
+    }
+
+    pub fn get_by_person_id(id: Uuid) -> Result<Vec<Self>> {
+        let mut conn = connection()?;
+
+        let res = deployments::table
+            .filter(deployments::person_id.eq(id))
+            .order_by(deployments::start_datestamp.desc())
+            .load::<Deployment>(&mut conn)?;
+
+        Ok(res)
+    }
+
+    /// Returns all deployments for a set of people, used to batch person -> deployments lookups
+    pub fn get_by_person_ids(ids: &[Uuid]) -> Result<Vec<Self>> {
+        let mut conn = connection()?;
+
+        let res = deployments::table
+            .filter(deployments::person_id.eq_any(ids))
+            .order_by(deployments::start_datestamp.desc())
+            .load::<Deployment>(&mut conn)?;
+
+        Ok(res)
+    }
+
+    pub fn update(&mut self) -> Result<Self> {
+        let mut conn = connection()?;
+
+        self.updated_at = chrono::Utc::now().naive_utc();
+
+        let res = diesel::update(deployments::table)
+            .filter(deployments::id.eq(&self.id))
+            .set(self.clone())
+            .get_result(&mut conn)?;
+
+        Ok(res)
+    }
+
+    /// Deletes a deployment entered in error and returns it
+    pub fn delete(id: Uuid) -> Result<Self> {
+        let mut conn = connection()?;
+
+        let res = diesel::delete(deployments::table)
+            .filter(deployments::id.eq(id))
+            .get_result(&mut conn)?;
+
+        Ok(res)
+    }
+
+    /// Whole days of the deployment falling between from and to
+    pub fn days_between(&self, from: NaiveDateTime, to: NaiveDateTime) -> i64 {
+        let start = self.start_datestamp.max(from);
+        let end = self.end_date.unwrap_or(to).min(to);
+
+        (end - start).num_days().max(0)
+    }
+
+    pub fn is_underway(&self, now: NaiveDateTime) -> bool {
+        self.start_datestamp <= now && self.end_date.is_none_or(|d| d > now)
+    }
+
+    /// Checks the dates and effort of a deployment before it is saved
+    pub fn check(
+        start_datestamp: NaiveDateTime,
+        end_date: Option<NaiveDateTime>,
+        effort: f64,
+    ) -> Result<()> {
+
+        if end_date.is_some_and(|d| d < start_datestamp) {
+            return Err(Error::new("end_date must be after start_datestamp"));
+        }
+
+        if !(effort > 0.0 && effort <= 1.0) {
+            return Err(Error::new("effort must be greater than 0 and at most 1"));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Insertable, InputObject)]
+#[diesel(table_name = deployments)]
+pub struct NewDeployment {
+    pub person_id: Uuid,
+    pub role_id: Option<Uuid>,
+    pub event_name: String,
+    pub effort: f64,
+    pub start_datestamp: NaiveDateTime,
+    pub end_date: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, SimpleObject)]
+/// Deployment load for a person, used to spot burnout risk
+pub struct DeploymentMetrics {
+    /// Length of the look-back window in months
+    pub window_months: i32,
+    /// Deployments that overlap the window
+    pub deployments_in_window: i32,
+    /// Days deployed within the window
+    pub days_in_window: i64,
+    /// Days deployed across all deployments
+    pub cumulative_days: i64,
+    pub currently_deployed: bool,
+    /// When the most recent finished deployment ended
+    pub last_ended_at: Option<NaiveDateTime>,
+}
+
+impl DeploymentMetrics {
+
+    pub fn window_start(months: i32, now: NaiveDateTime) -> NaiveDateTime {
+        now.checked_sub_months(Months::new(months.max(0) as u32)).unwrap_or(now)
+    }
+
+    pub fn from_deployments(deployments: &[Deployment], months: i32, now: NaiveDateTime) -> Self {
+
+        let window_start = Self::window_start(months, now);
+
+        let in_window: Vec<&Deployment> = deployments.iter()
+            .filter(|d| d.start_datestamp <= now && d.end_date.is_none_or(|e| e >= window_start))
+            .collect();
+
+        DeploymentMetrics {
+            window_months: months,
+            deployments_in_window: in_window.len() as i32,
+            days_in_window: in_window.iter().map(|d| d.days_between(window_start, now)).sum(),
+            cumulative_days: deployments.iter().map(|d| d.days_between(d.start_datestamp, now)).sum(),
+            currently_deployed: deployments.iter().any(|d| d.is_underway(now)),
+            last_ended_at: deployments.iter()
+                .filter_map(|d| d.end_date)
+                .filter(|e| *e <= now)
+                .max(),
+        }
+    }
+
+    /// Metrics for each of person_ids. People with no deployments get zeroed metrics.
+    pub fn for_person_ids(person_ids: &[Uuid], months: i32) -> Result<HashMap<Uuid, Self>> {
+
+        let now = chrono::Utc::now().naive_utc();
+
+        let mut by_person: HashMap<Uuid, Vec<Deployment>> = person_ids.iter()
+            .map(|id| (*id, Vec::new()))
+            .collect();
+
+        for deployment in Deployment::get_by_person_ids(person_ids)? {
+            by_person.entry(deployment.person_id).or_default().push(deployment);
+        }
+
+        Ok(by_person.into_iter()
+            .map(|(id, deployments)| (id, Self::from_deployments(&deployments, months, now)))
+            .collect())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, InputObject)]
+/// Leaves out people who are deployed or have been deployed heavily.
+/// A person is excluded if any limit is exceeded.
+pub struct DeploymentExclusion {
+    /// Look-back window for the limits below
+    #[graphql(default_with = "DEPLOYMENT_WINDOW_MONTHS")]
+    pub window_months: i32,
+    /// Exclude people with more deployments than this in the window
+    pub max_deployments: Option<i32>,
+    /// Exclude people with more deployed days than this in the window
+    pub max_days: Option<i64>,
+    /// Exclude people whose last deployment ended fewer than this many days ago
+    pub cooldown_days: Option<i64>,
+    #[graphql(default = true)]
+    pub exclude_current: bool,
+}
+
+impl DeploymentExclusion {
+
+    /// Excludes people who are deployed or finished a deployment in the last `days` days
+    pub fn cooldown(days: i64) -> Self {
+        DeploymentExclusion {
+            window_months: DEPLOYMENT_WINDOW_MONTHS,
+            max_deployments: None,
+            max_days: None,
+            cooldown_days: Some(days),
+            exclude_current: true,
+        }
+    }
+
+    pub fn excludes(&self, metrics: &DeploymentMetrics, now: NaiveDateTime) -> bool {
+        (self.exclude_current && metrics.currently_deployed)
+            || self.max_deployments.is_some_and(|max| metrics.deployments_in_window > max)
+            || self.max_days.is_some_and(|max| metrics.days_in_window > max)
+            || self.cooldown_days.is_some_and(|days| metrics.last_ended_at
+                .is_some_and(|e| e > now - Duration::days(days)))
+    }
+
+    /// Returns the subset of person_ids to leave out
+    pub fn excluded_person_ids(&self, person_ids: &[Uuid]) -> Result<HashSet<Uuid>> {
+
+        let now = chrono::Utc::now().naive_utc();
+
+        Ok(DeploymentMetrics::for_person_ids(person_ids, self.window_months)?
+            .into_iter()
+            .filter(|(_, m)| self.excludes(m, now))
+            .map(|(id, _)| id)
+            .collect())
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+
+use lazy_static::lazy_static;
+
+use crate::config_variables::{DEFAULT_MIN_CELL_SIZE, DEFAULT_CELL_ROUNDING_BASE};
+
+lazy_static! {
+    /// Smallest count aggregate queries publish. Set with MIN_CELL_SIZE.
+    static ref MIN_CELL_SIZE: i64 = env_or("MIN_CELL_SIZE", DEFAULT_MIN_CELL_SIZE);
+    /// Published counts are rounded to a multiple of this. Set with CELL_ROUNDING_BASE.
+    static ref CELL_ROUNDING_BASE: i64 = env_or("CELL_ROUNDING_BASE", DEFAULT_CELL_ROUNDING_BASE);
+}
+
+fn env_or(name: &str, default: i64) -> i64 {
+    std::env::var(name)
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .filter(|v| *v >= 1)
+        .unwrap_or(default)
+}
+
+/// One count in a table of aggregate results, e.g. people holding a capability at a level.
+/// Cells in the same row, the same column, or the whole table add up to totals a reader
+/// may know or get from another query.
+#[derive(Debug, Clone, Copy)]
+pub struct Cell<'a> {
+    pub row: &'a str,
+    pub column: &'a str,
+    pub count: i64,
+}
+
+/// Returns the count to publish for each cell, or None if it is suppressed.
+///
+/// Cells below MIN_CELL_SIZE are suppressed. Then, so they can't be worked out by
+/// subtracting the other cells from a row, column or table total, more cells are suppressed,
+/// smallest first, until every total with a suppressed cell hides at least two cells adding up
+/// to MIN_CELL_SIZE or more. Published counts are rounded to CELL_ROUNDING_BASE.
+pub fn protect_cells(cells: &[Cell]) -> Vec<Option<i64>> {
+    protect_cells_with(cells, *MIN_CELL_SIZE, *CELL_ROUNDING_BASE)
+}
+
+fn protect_cells_with<'a>(cells: &[Cell<'a>], min_cell_size: i64, rounding_base: i64) -> Vec<Option<i64>> {
+
+    let mut suppressed: Vec<bool> = cells.iter()
+        .map(|c| c.count < min_cell_size)
+        .collect();
+
+    let margins: [fn(&Cell<'a>) -> &'a str; 3] = [|c| c.row, |c| c.column, |_| ""];
+
+    loop {
+        let mut changed = false;
+
+        for margin in margins.iter() {
+            let mut groups: HashMap<&str, Vec<usize>> = HashMap::new();
+
+            for (i, cell) in cells.iter().enumerate() {
+                groups.entry(margin(cell)).or_default().push(i);
+            }
+
+            for group in groups.values() {
+                let hidden: Vec<usize> = group.iter().copied().filter(|i| suppressed[*i]).collect();
+
+                if hidden.is_empty() || hidden.len() == group.len() {
+                    continue;
+                }
+
+                let hidden_total: i64 = hidden.iter().map(|i| cells[*i].count).sum();
+
+                if hidden.len() > 1 && hidden_total >= min_cell_size {
+                    continue;
+                }
+
+                if let Some(next) = group.iter()
+                    .copied()
+                    .filter(|i| !suppressed[*i])
+                    .min_by_key(|i| cells[*i].count) {
+                    suppressed[next] = true;
+                    changed = true;
+                }
+            }
+        }
+
+        if !changed {
+            break;
+        }
+    }
+
+    cells.iter()
+        .zip(suppressed)
+        .map(|(cell, s)| (!s).then(|| round_to(cell.count, rounding_base)))
+        .collect()
+}
+
+/// Rounds to the nearest multiple of base, halves rounding up
+fn round_to(count: i64, base: i64) -> i64 {
+    if base <= 1 {
+        return count;
+    }
+
+    (count + base / 2) / base * base
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cells<'a>(counts: &[(&'a str, &'a str, i64)]) -> Vec<Cell<'a>> {
+        counts.iter()
+            .map(|(row, column, count)| Cell { row, column, count: *count })
+            .collect()
+    }
+
+    #[test]
+    fn publishes_cells_at_or_above_min_cell_size() {
+        let published = protect_cells_with(&cells(&[("a", "x", 5), ("a", "y", 8), ("b", "x", 12)]), 5, 1);
+
+        assert_eq!(published, vec![Some(5), Some(8), Some(12)]);
+    }
+
+    #[test]
+    fn suppresses_small_cells() {
+        let published = protect_cells_with(&cells(&[("a", "x", 4), ("b", "y", 1)]), 5, 1);
+
+        assert_eq!(published, vec![None, None]);
+    }
+
+    #[test]
+    fn suppresses_a_complement_in_the_same_row() {
+        let published = protect_cells_with(&cells(&[("a", "x", 2), ("a", "y", 7), ("a", "z", 9)]), 5, 1);
+
+        assert_eq!(published, vec![None, None, Some(9)]);
+    }
+
+    #[test]
+    fn suppresses_a_complement_in_the_same_column() {
+        let published = protect_cells_with(&cells(&[("a", "x", 2), ("b", "x", 7), ("c", "x", 9)]), 5, 1);
+
+        assert_eq!(published, vec![None, None, Some(9)]);
+    }
+
+    #[test]
+    fn suppresses_a_complement_against_the_table_total() {
+        let published = protect_cells_with(&cells(&[("a", "x", 2), ("b", "y", 8), ("c", "z", 9)]), 5, 1);
+
+        assert_eq!(published, vec![None, None, Some(9)]);
+    }
+
+    #[test]
+    fn suppresses_complements_until_hidden_cells_reach_min_cell_size() {
+        let published = protect_cells_with(&cells(&[("a", "x", 1), ("a", "y", 2), ("a", "z", 6), ("a", "w", 7)]), 5, 1);
+
+        assert_eq!(published, vec![None, None, None, Some(7)]);
+    }
+
+    #[test]
+    fn rounds_published_counts() {
+        let published = protect_cells_with(&cells(&[("a", "x", 7), ("b", "x", 12), ("c", "x", 13)]), 5, 5);
+
+        assert_eq!(published, vec![Some(5), Some(10), Some(15)]);
+    }
+
+    #[test]
+    fn rounds_halves_up() {
+        assert_eq!(round_to(15, 10), 20);
+        assert_eq!(round_to(14, 10), 10);
+        assert_eq!(round_to(14, 1), 14);
+    }
+}
@@ -0,0 +1,417 @@
+use chrono::prelude::*;
+use diesel_derive_enum::DbEnum;
+use serde::{Deserialize, Serialize};
+use diesel::{self, Connection, Insertable, Queryable, ExpressionMethods, BoolExpressionMethods, OptionalExtension};
+use diesel::{RunQueryDsl, QueryDsl, QueryResult};
+use diesel::pg::PgConnection;
+use uuid::Uuid;
+use async_graphql::*;
+
+use crate::schema::*;
+use crate::database::connection;
+use crate::graphql::{Loaders, person_id_field};
+
+use super::{HrGroup, Person};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, DbEnum, Serialize, Deserialize, Enum)]
+#[ExistingTypePath = "crate::schema::sql_types::ClassificationType"]
+/// Whether a person holds a classification in their own right or is acting in it
+pub enum ClassificationType {
+    Substantive,
+    Acting,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, DbEnum, Serialize, Deserialize, Enum)]
+#[ExistingTypePath = "crate::schema::sql_types::HrState"]
+/// A person's employment state
+pub enum HrState {
+    Active,
+    Leave,
+    Secondment,
+    Departed,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Queryable, Insertable, AsChangeset, SimpleObject)]
+#[graphql(complex)]
+#[diesel(table_name = employee_datas)]
+#[diesel(belongs_to(Person))]
+/// A period in which a person held a classification, substantively or acting.
+/// Acting assignments are recorded here rather than as roles.
+/// Referenced by Person
+pub struct EmployeeData {
+    pub id: Uuid,
+    #[graphql(directive = person_id_field::apply())]
+    pub person_id: Uuid,
+    pub title_en: String,
+    pub title_fr: String,
+    pub hr_group: HrGroup,
+    pub hr_level: i32,
+    pub classification: ClassificationType,
+
+    pub start_datestamp: NaiveDateTime,
+    pub end_date: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[ComplexObject]
+impl EmployeeData {
+
+    pub async fn person(&self, ctx: &Context<'_>) -> Result<Person> {
+        ctx.data::<Loaders>()?.person(self.person_id).await
+    }
+}
+
+// Non Graphql
+impl EmployeeData {
+    pub fn create(employee_data: &NewEmployeeData) -> Result<EmployeeData> {
+        let mut conn = connection()?;
+
+        let res = diesel::insert_into(employee_datas::table)
+            .values(employee_data)
+            .get_result(&mut conn)?;
+
+        Ok(res)
+    }
+
+    pub fn get_by_id(id: &Uuid) -> Result<Self> {
+        let mut conn = connection()?;
+
+        let res = employee_datas::table
+            .filter(employee_datas::id.eq(id))
+            .first(&mut conn)?;
+
+        Ok(res)
+    }
+
+    pub fn get_by_person_id(id: Uuid) -> Result<Vec<Self>> {
+        let mut conn = connection()?;
+
+        let res = employee_datas::table
+            .filter(employee_datas::person_id.eq(id))
+            .order_by(employee_datas::start_datestamp.desc())
+            .load::<EmployeeData>(&mut conn)?;
+
+        Ok(res)
+    }
+
+    /// Returns all classifications for a set of people, used to batch person -> employee data lookups
+    pub fn get_by_person_ids(ids: &[Uuid]) -> Result<Vec<Self>> {
+        let mut conn = connection()?;
+
+        let res = employee_datas::table
+            .filter(employee_datas::person_id.eq_any(ids))
+            .order_by(employee_datas::start_datestamp.desc())
+            .load::<EmployeeData>(&mut conn)?;
+
+        Ok(res)
+    }
+
+    /// Returns the acting assignments in effect on a date
+    pub fn get_acting_on(date: NaiveDateTime) -> Result<Vec<Self>> {
+        let mut conn = connection()?;
+
+        let res = employee_datas::table
+            .filter(employee_datas::classification.eq(ClassificationType::Acting))
+            .filter(employee_datas::start_datestamp.le(date))
+            .filter(employee_datas::end_date.is_null().or(employee_datas::end_date.gt(date)))
+            .order_by(employee_datas::start_datestamp.desc())
+            .load::<EmployeeData>(&mut conn)?;
+
+        Ok(res)
+    }
+
+    pub fn update(&mut self) -> Result<Self> {
+        let mut conn = connection()?;
+
+        self.updated_at = chrono::Utc::now().naive_utc();
+
+        let res = diesel::update(employee_datas::table)
+            .filter(employee_datas::id.eq(&self.id))
+            .set(self.clone())
+            .get_result(&mut conn)?;
+
+        Ok(res)
+    }
+
+    pub fn in_effect_on(&self, date: NaiveDateTime) -> bool {
+        self.start_datestamp <= date && self.end_date.is_none_or(|d| d > date)
+    }
+
+    /// Checks the dates of a classification before it is saved. Periods of the same
+    /// ClassificationType for a person can't overlap. `id` is the period being updated, if any.
+    pub fn check(
+        id: Option<Uuid>,
+        person_id: Uuid,
+        classification: ClassificationType,
+        start_datestamp: NaiveDateTime,
+        end_date: Option<NaiveDateTime>,
+    ) -> Result<()> {
+
+        EmployeeData::check_against(&EmployeeData::get_by_person_id(person_id)?, id, classification, start_datestamp, end_date)
+    }
+
+    /// Checks the dates of a classification against the person's existing classifications
+    fn check_against(
+        existing: &[Self],
+        id: Option<Uuid>,
+        classification: ClassificationType,
+        start_datestamp: NaiveDateTime,
+        end_date: Option<NaiveDateTime>,
+    ) -> Result<()> {
+
+        if end_date.is_some_and(|d| d <= start_datestamp) {
+            return Err(Error::new("end_date must be after start_datestamp"));
+        }
+
+        let overlapping = existing.iter()
+            .filter(|e| Some(e.id) != id && e.classification == classification)
+            .any(|e| e.start_datestamp < end_date.unwrap_or(NaiveDateTime::MAX)
+                && e.end_date.is_none_or(|d| d > start_datestamp));
+
+        if overlapping {
+            return Err(Error::new(format!("Person already holds a {:?} classification in this period", classification)));
+        }
+
+        Ok(())
+    }
+
+    /// Ends open classifications on a date within a transaction, used when a person departs.
+    /// The classifications must start before end_date. Returns each before and after it ended.
+    fn end_open(conn: &mut PgConnection, open: &[Self], end_date: NaiveDateTime) -> QueryResult<Vec<(Self, Self)>> {
+        let mut ended = Vec::new();
+
+        for before in open {
+            let after = diesel::update(employee_datas::table)
+                .filter(employee_datas::id.eq(before.id))
+                .set((
+                    employee_datas::end_date.eq(end_date),
+                    employee_datas::updated_at.eq(chrono::Utc::now().naive_utc()),
+                ))
+                .get_result::<EmployeeData>(conn)?;
+
+            ended.push((before.clone(), after));
+        }
+
+        Ok(ended)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Insertable, InputObject)]
+#[diesel(table_name = employee_datas)]
+pub struct NewEmployeeData {
+    pub person_id: Uuid,
+    pub title_en: String,
+    pub title_fr: String,
+    pub hr_group: HrGroup,
+    pub hr_level: i32,
+    pub classification: ClassificationType,
+    pub start_datestamp: NaiveDateTime,
+    pub end_date: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Queryable, Insertable, AsChangeset, SimpleObject)]
+#[diesel(table_name = hr_state_periods)]
+#[diesel(belongs_to(Person))]
+/// A period a person spent in an HrState. The current state has no end_date.
+/// Referenced by Person
+pub struct HrStatePeriod {
+    pub id: Uuid,
+    #[graphql(directive = person_id_field::apply())]
+    pub person_id: Uuid,
+    pub hr_state: HrState,
+
+    pub start_datestamp: NaiveDateTime,
+    pub end_date: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Insertable, InputObject)]
+#[diesel(table_name = hr_state_periods)]
+pub struct NewHrStatePeriod {
+    pub person_id: Uuid,
+    pub hr_state: HrState,
+    pub start_datestamp: NaiveDateTime,
+}
+
+/// The changes made by HrStatePeriod::record
+pub struct RecordedHrState {
+    /// The person's previous state, now ended, if they had one
+    pub closed: Option<HrStatePeriod>,
+    pub created: HrStatePeriod,
+    /// Classifications ended by a departure, before and after
+    pub ended: Vec<(EmployeeData, EmployeeData)>,
+}
+
+// Non Graphql
+impl HrStatePeriod {
+
+    pub fn get_current_by_person_id(id: Uuid) -> Result<Option<Self>> {
+        let mut conn = connection()?;
+
+        let res = hr_state_periods::table
+            .filter(hr_state_periods::person_id.eq(id))
+            .filter(hr_state_periods::end_date.is_null())
+            .first(&mut conn)
+            .optional()?;
+
+        Ok(res)
+    }
+
+    pub fn get_by_person_id(id: Uuid) -> Result<Vec<Self>> {
+        let mut conn = connection()?;
+
+        let res = hr_state_periods::table
+            .filter(hr_state_periods::person_id.eq(id))
+            .order_by(hr_state_periods::start_datestamp.desc())
+            .load::<HrStatePeriod>(&mut conn)?;
+
+        Ok(res)
+    }
+
+    /// Returns all state periods for a set of people, used to batch person -> hr state lookups
+    pub fn get_by_person_ids(ids: &[Uuid]) -> Result<Vec<Self>> {
+        let mut conn = connection()?;
+
+        let res = hr_state_periods::table
+            .filter(hr_state_periods::person_id.eq_any(ids))
+            .order_by(hr_state_periods::start_datestamp.desc())
+            .load::<HrStatePeriod>(&mut conn)?;
+
+        Ok(res)
+    }
+
+    /// Ends the person's current state at start_datestamp and opens the new one.
+    /// HrState::Departed also ends the person's open classifications, all in one transaction.
+    pub fn record(period: &NewHrStatePeriod) -> Result<RecordedHrState> {
+
+        let current = HrStatePeriod::get_current_by_person_id(period.person_id)?;
+
+        if let Some(c) = &current {
+            if c.hr_state == period.hr_state {
+                return Err(Error::new(format!("Person is already in the {:?} state", period.hr_state)));
+            }
+
+            if period.start_datestamp <= c.start_datestamp {
+                return Err(Error::new("start_datestamp must be after the start of the current state"));
+            }
+        }
+
+        let open_classifications: Vec<EmployeeData> = if period.hr_state == HrState::Departed {
+            let classifications = EmployeeData::get_by_person_id(period.person_id)?;
+
+            if classifications.iter().any(|e| e.start_datestamp >= period.start_datestamp) {
+                return Err(Error::new("Person holds a classification starting on or after the departure date"));
+            }
+
+            classifications.into_iter().filter(|e| e.end_date.is_none()).collect()
+        } else {
+            Vec::new()
+        };
+
+        let mut conn = connection()?;
+
+        let res = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            let closed = match &current {
+                Some(c) => Some(diesel::update(hr_state_periods::table)
+                    .filter(hr_state_periods::id.eq(c.id))
+                    .filter(hr_state_periods::end_date.is_null())
+                    .set((
+                        hr_state_periods::end_date.eq(period.start_datestamp),
+                        hr_state_periods::updated_at.eq(chrono::Utc::now().naive_utc()),
+                    ))
+                    .get_result::<HrStatePeriod>(conn)?),
+                None => None,
+            };
+
+            let created = diesel::insert_into(hr_state_periods::table)
+                .values(period)
+                .get_result::<HrStatePeriod>(conn)?;
+
+            let ended = EmployeeData::end_open(conn, &open_classifications, period.start_datestamp)?;
+
+            Ok(RecordedHrState { closed, created, ended })
+        })?;
+
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::HrGroup;
+
+    fn date(day: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 1, day).unwrap().and_hms_opt(0, 0, 0).unwrap()
+    }
+
+    fn period(classification: ClassificationType, start: u32, end: Option<u32>) -> EmployeeData {
+        EmployeeData {
+            id: Uuid::new_v4(),
+            person_id: Uuid::nil(),
+            title_en: "Analyst".to_string(),
+            title_fr: "Analyste".to_string(),
+            hr_group: HrGroup::EC,
+            hr_level: 4,
+            classification,
+            start_datestamp: date(start),
+            end_date: end.map(date),
+            created_at: date(1),
+            updated_at: date(1),
+        }
+    }
+
+    fn check(existing: &[EmployeeData], id: Option<Uuid>, start: u32, end: Option<u32>) -> Result<()> {
+        EmployeeData::check_against(existing, id, ClassificationType::Substantive, date(start), end.map(date))
+    }
+
+    #[test]
+    fn end_must_follow_start() {
+        assert!(check(&[], None, 10, Some(10)).is_err());
+        assert!(check(&[], None, 10, Some(9)).is_err());
+        assert!(check(&[], None, 10, Some(11)).is_ok());
+    }
+
+    #[test]
+    fn refuses_overlapping_periods() {
+        let existing = [period(ClassificationType::Substantive, 5, Some(15))];
+
+        assert!(check(&existing, None, 10, Some(20)).is_err());
+        assert!(check(&existing, None, 1, Some(6)).is_err());
+        assert!(check(&existing, None, 7, Some(8)).is_err());
+        assert!(check(&existing, None, 1, None).is_err());
+    }
+
+    #[test]
+    fn refuses_periods_after_an_open_one_starts() {
+        let existing = [period(ClassificationType::Substantive, 5, None)];
+
+        assert!(check(&existing, None, 20, Some(25)).is_err());
+        assert!(check(&existing, None, 1, Some(5)).is_ok());
+    }
+
+    #[test]
+    fn allows_adjacent_periods() {
+        let existing = [period(ClassificationType::Substantive, 5, Some(15))];
+
+        // End dates are exclusive, so a period may start the day the last one ended
+        assert!(check(&existing, None, 15, None).is_ok());
+        assert!(check(&existing, None, 1, Some(5)).is_ok());
+    }
+
+    #[test]
+    fn allows_overlap_with_another_classification() {
+        let existing = [period(ClassificationType::Acting, 5, Some(15))];
+
+        assert!(check(&existing, None, 10, Some(20)).is_ok());
+    }
+
+    #[test]
+    fn ignores_the_period_being_updated() {
+        let existing = [period(ClassificationType::Substantive, 5, Some(15))];
+
+        assert!(check(&existing, Some(existing[0].id), 6, Some(20)).is_ok());
+    }
+}
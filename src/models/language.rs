@@ -14,6 +14,7 @@ use async_graphql::*;
 
 use crate::database::connection;
 use crate::schema::*;
+use crate::graphql::person_id_field;
 
 
 #[derive(Debug, Clone, Deserialize, Serialize, Queryable, Identifiable, AsChangeset, SimpleObject, PartialEq)]
@@ -21,6 +22,7 @@ use crate::schema::*;
 /// A representation of a persons' language proficiency
 pub struct LanguageData {
     pub id: Uuid,
+    #[graphql(directive = person_id_field::apply())]
     pub person_id: Uuid,
     pub language_name: LanguageName,
     pub reading: Option<LanguageLevel>,
@@ -104,6 +106,15 @@ impl LanguageData {
         Ok(res)
     }
 
+    pub fn get_by_person_ids(ids: &[Uuid]) -> Result<Vec<Self>> {
+        let mut conn = connection()?;
+
+        let res = language_datas::table.filter(language_datas::person_id.eq_any(ids))
+            .load::<LanguageData>(&mut conn)?;
+
+        Ok(res)
+    }
+
     pub fn get_all() -> Result<Vec<Self>> {
         let mut conn = connection()?;
 
@@ -0,0 +1,354 @@
+use std::collections::HashMap;
+
+use async_graphql::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{Capability, CapabilityLevel, Person, Requirement, Role};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Enum)]
+/// Which capability level to compare against requirements
+pub enum LevelSource {
+    /// Only validated levels count. Unvalidated capabilities are treated as not held
+    #[default]
+    Validated,
+    /// The person's self identified level
+    SelfIdentified,
+}
+
+impl LevelSource {
+    pub fn level(&self, capability: &Capability) -> Option<CapabilityLevel> {
+        match self {
+            LevelSource::Validated => capability.validated_level,
+            LevelSource::SelfIdentified => Some(capability.self_identified_level),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+/// Comparison of one requirement against the level a person holds
+pub struct SkillGap {
+    pub skill_id: Uuid,
+    pub skill_name: String,
+    pub required_level: CapabilityLevel,
+    pub held_level: Option<CapabilityLevel>,
+}
+
+#[derive(Debug, Clone, SimpleObject)]
+#[graphql(concrete(name = "RoleMatch", params(Role)))]
+#[graphql(concrete(name = "PersonMatch", params(Person)))]
+/// A ranked match between a person and a role
+pub struct MatchResult<T: OutputType> {
+    /// The matched role or person
+    pub candidate: T,
+    /// 0 to 1. Each requirement met or exceeded counts 1, an unmet requirement
+    /// gets partial credit for the level held
+    pub score: f64,
+    /// Requirements held at exactly the required level
+    pub met: Vec<SkillGap>,
+    /// Requirements held below the required level or not at all
+    pub unmet: Vec<SkillGap>,
+    /// Requirements held above the required level
+    pub exceeded: Vec<SkillGap>,
+}
+
+/// Scores of a single candidate before it is loaded
+struct Scored {
+    score: f64,
+    met: Vec<SkillGap>,
+    unmet: Vec<SkillGap>,
+    exceeded: Vec<SkillGap>,
+}
+
+/// Levels are ordinal, Desired = 0 to Specialist = 4
+fn rank(level: CapabilityLevel) -> f64 {
+    match level {
+        CapabilityLevel::Desired => 0.0,
+        CapabilityLevel::Novice => 1.0,
+        CapabilityLevel::Experienced => 2.0,
+        CapabilityLevel::Expert => 3.0,
+        CapabilityLevel::Specialist => 4.0,
+    }
+}
+
+/// Credit for holding `held` against `required`: 1 when met or exceeded,
+/// partial credit for a lower level and 0 when the skill is not held
+pub fn level_credit(held: Option<CapabilityLevel>, required: CapabilityLevel) -> f64 {
+    match held {
+        Some(l) if l >= required => 1.0,
+        Some(l) => (rank(l) + 1.0) / (rank(required) + 1.0),
+        None => 0.0,
+    }
+}
+
+/// Compares a set of requirements against held levels keyed by skill_id.
+/// Retired requirements are skipped.
+fn score_requirements(requirements: &[&Requirement], held: &HashMap<Uuid, CapabilityLevel>) -> Scored {
+
+    let mut scored = Scored { score: 0.0, met: Vec::new(), unmet: Vec::new(), exceeded: Vec::new() };
+
+    let requirements: Vec<&Requirement> = requirements.iter()
+        .copied()
+        .filter(|r| r.retired_at.is_none())
+        .collect();
+
+    if requirements.is_empty() {
+        return scored;
+    }
+
+    let mut credit = 0.0;
+
+    for req in &requirements {
+        let held_level = held.get(&req.skill_id).copied();
+
+        let gap = SkillGap {
+            skill_id: req.skill_id,
+            skill_name: req.name_en.clone(),
+            required_level: req.required_level,
+            held_level,
+        };
+
+        credit += level_credit(held_level, req.required_level);
+
+        match held_level {
+            Some(l) if l == req.required_level => scored.met.push(gap),
+            Some(l) if l > req.required_level => scored.exceeded.push(gap),
+            _ => scored.unmet.push(gap),
+        }
+    }
+
+    scored.score = credit / requirements.len() as f64;
+
+    scored
+}
+
+/// Highest score first, then fewest unmet, then most exceeded
+fn rank_scores<K: Copy>(scores: &mut [(K, Scored)]) {
+    scores.sort_by(|(_, a), (_, b)| {
+        b.score.total_cmp(&a.score)
+            .then(a.unmet.len().cmp(&b.unmet.len()))
+            .then(b.exceeded.len().cmp(&a.exceeded.len()))
+    });
+}
+
+/// Ranks active, vacant roles for a person by how well the person's capabilities
+/// meet each role's requirements
+pub fn match_roles_for_person(
+    person_id: Uuid,
+    source: LevelSource,
+    min_score: f64,
+    limit: usize,
+) -> Result<Vec<MatchResult<Role>>> {
+
+    let held: HashMap<Uuid, CapabilityLevel> = Capability::get_by_person_id(person_id)?
+        .iter()
+        .filter(|c| c.retired_at.is_none())
+        .filter_map(|c| source.level(c).map(|l| (c.skill_id, l)))
+        .collect();
+
+    let skill_ids: Vec<Uuid> = held.keys().copied().collect();
+
+    // Candidate roles need at least one requirement on a skill the person holds
+    let mut role_ids: Vec<Uuid> = Requirement::get_by_skill_ids(&skill_ids)?
+        .iter()
+        .map(|r| r.role_id)
+        .collect();
+
+    role_ids.sort();
+    role_ids.dedup();
+
+    let mut roles: HashMap<Uuid, Role> = Role::get_active_vacant_by_ids(&role_ids)?
+        .into_iter()
+        .map(|r| (r.id, r))
+        .collect();
+
+    let open_ids: Vec<Uuid> = roles.keys().copied().collect();
+
+    let mut requirements_by_role: HashMap<Uuid, Vec<Requirement>> = HashMap::new();
+    for req in Requirement::get_active_by_role_ids(&open_ids)? {
+        requirements_by_role.entry(req.role_id).or_default().push(req);
+    }
+
+    let mut scores: Vec<(Uuid, Scored)> = requirements_by_role.iter()
+        .map(|(role_id, reqs)| (*role_id, score_requirements(&reqs.iter().collect::<Vec<_>>(), &held)))
+        .filter(|(_, s)| s.score > 0.0 && s.score >= min_score)
+        .collect();
+
+    rank_scores(&mut scores);
+
+    Ok(scores.into_iter()
+        .take(limit)
+        .filter_map(|(id, s)| roles.remove(&id).map(|role| MatchResult {
+            candidate: role,
+            score: s.score,
+            met: s.met,
+            unmet: s.unmet,
+            exceeded: s.exceeded,
+        }))
+        .collect())
+}
+
+/// Ranks people for a role by how well their capabilities meet the role's requirements
+pub fn match_people_for_role(
+    role_id: Uuid,
+    source: LevelSource,
+    min_score: f64,
+    limit: usize,
+) -> Result<Vec<MatchResult<Person>>> {
+
+    let requirements = Requirement::get_active_by_role_ids(&[role_id])?;
+
+    let skill_ids: Vec<Uuid> = requirements.iter().map(|r| r.skill_id).collect();
+
+    // Candidates hold at least one of the required skills
+    let mut held_by_person: HashMap<Uuid, HashMap<Uuid, CapabilityLevel>> = HashMap::new();
+    for cap in Capability::get_by_skill_ids(&skill_ids)? {
+        if let Some(level) = source.level(&cap) {
+            held_by_person.entry(cap.person_id).or_default().insert(cap.skill_id, level);
+        }
+    }
+
+    let reqs: Vec<&Requirement> = requirements.iter().collect();
+
+    let mut scores: Vec<(Uuid, Scored)> = held_by_person.iter()
+        .map(|(person_id, held)| (*person_id, score_requirements(&reqs, held)))
+        .filter(|(_, s)| s.score > 0.0 && s.score >= min_score)
+        .collect();
+
+    rank_scores(&mut scores);
+    scores.truncate(limit);
+
+    let ids: Vec<Uuid> = scores.iter().map(|(id, _)| *id).collect();
+
+    let mut people: HashMap<Uuid, Person> = Person::get_by_ids(&ids)?
+        .into_iter()
+        .map(|p| (p.id, p))
+        .collect();
+
+    Ok(scores.into_iter()
+        .filter_map(|(id, s)| people.remove(&id).map(|person| MatchResult {
+            candidate: person,
+            score: s.score,
+            met: s.met,
+            unmet: s.unmet,
+            exceeded: s.exceeded,
+        }))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+
+    use super::*;
+    use crate::models::SkillDomain;
+
+    fn requirement(skill_id: Uuid, required_level: CapabilityLevel) -> Requirement {
+        Requirement {
+            id: Uuid::new_v4(),
+            name_en: "Skill".to_string(),
+            name_fr: "Compétence".to_string(),
+            domain: SkillDomain::Combat,
+            role_id: Uuid::new_v4(),
+            skill_id,
+            required_level,
+            created_at: NaiveDateTime::default(),
+            updated_at: NaiveDateTime::default(),
+            retired_at: None,
+        }
+    }
+
+    fn scored(score: f64, unmet: usize, exceeded: usize) -> Scored {
+        let gap = SkillGap {
+            skill_id: Uuid::nil(),
+            skill_name: String::new(),
+            required_level: CapabilityLevel::Novice,
+            held_level: None,
+        };
+
+        Scored { score, met: Vec::new(), unmet: vec![gap.clone(); unmet], exceeded: vec![gap; exceeded] }
+    }
+
+    #[test]
+    fn full_credit_when_met_or_exceeded() {
+        assert_eq!(level_credit(Some(CapabilityLevel::Expert), CapabilityLevel::Expert), 1.0);
+        assert_eq!(level_credit(Some(CapabilityLevel::Specialist), CapabilityLevel::Novice), 1.0);
+    }
+
+    #[test]
+    fn partial_credit_for_a_lower_level() {
+        // Novice is rank 1 and Expert rank 3, so (1 + 1) / (3 + 1)
+        assert_eq!(level_credit(Some(CapabilityLevel::Novice), CapabilityLevel::Expert), 0.5);
+        assert_eq!(level_credit(Some(CapabilityLevel::Desired), CapabilityLevel::Specialist), 0.2);
+    }
+
+    #[test]
+    fn no_credit_when_not_held() {
+        assert_eq!(level_credit(None, CapabilityLevel::Desired), 0.0);
+    }
+
+    #[test]
+    fn no_requirements_scores_zero() {
+        let scored = score_requirements(&[], &HashMap::new());
+
+        assert_eq!(scored.score, 0.0);
+        assert!(scored.met.is_empty() && scored.unmet.is_empty() && scored.exceeded.is_empty());
+    }
+
+    #[test]
+    fn sorts_requirements_into_met_unmet_and_exceeded() {
+        let (a, b, c, d) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+
+        let requirements = [
+            requirement(a, CapabilityLevel::Experienced),
+            requirement(b, CapabilityLevel::Novice),
+            requirement(c, CapabilityLevel::Expert),
+            requirement(d, CapabilityLevel::Novice),
+        ];
+
+        let held = HashMap::from([
+            (a, CapabilityLevel::Experienced),
+            (b, CapabilityLevel::Expert),
+            (c, CapabilityLevel::Novice),
+        ]);
+
+        let scored = score_requirements(&requirements.iter().collect::<Vec<_>>(), &held);
+
+        assert_eq!(scored.met.iter().map(|g| g.skill_id).collect::<Vec<_>>(), vec![a]);
+        assert_eq!(scored.exceeded.iter().map(|g| g.skill_id).collect::<Vec<_>>(), vec![b]);
+        assert_eq!(scored.unmet.iter().map(|g| g.skill_id).collect::<Vec<_>>(), vec![c, d]);
+
+        // 1 + 1 + 0.5 + 0 over four requirements
+        assert_eq!(scored.score, 0.625);
+    }
+
+    #[test]
+    fn retired_requirements_do_not_lower_the_score() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+
+        let mut retired = requirement(b, CapabilityLevel::Specialist);
+        retired.retired_at = Some(NaiveDateTime::default());
+
+        let requirements = [requirement(a, CapabilityLevel::Novice), retired];
+        let held = HashMap::from([(a, CapabilityLevel::Novice)]);
+
+        let scored = score_requirements(&requirements.iter().collect::<Vec<_>>(), &held);
+
+        assert_eq!(scored.score, 1.0);
+        assert!(scored.unmet.is_empty());
+    }
+
+    #[test]
+    fn ranks_by_score_then_fewest_unmet_then_most_exceeded() {
+        let mut scores = vec![
+            (1, scored(0.5, 1, 0)),
+            (2, scored(0.9, 2, 0)),
+            (3, scored(0.5, 0, 0)),
+            (4, scored(0.5, 0, 2)),
+        ];
+
+        rank_scores(&mut scores);
+
+        assert_eq!(scores.iter().map(|(k, _)| *k).collect::<Vec<_>>(), vec![2, 4, 3, 1]);
+    }
+}
@@ -0,0 +1,210 @@
+use std::collections::{HashMap, HashSet};
+
+use async_graphql::*;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::config_variables::{MOBILIZATION_COOLDOWN_DAYS, MOBILIZATION_FIT_WEIGHT, MOBILIZATION_WORK_SCALE};
+
+use super::{Capability, CapabilityLevel, ContactData, DeploymentExclusion, LanguageData, LanguageLevel, LanguageName, LevelSource,
+    Person, Role, Skill, SkillGap, Work, level_credit};
+
+#[derive(Debug, Clone, Deserialize, InputObject)]
+/// Filters for a surge mobilization search
+pub struct MobilizationSearch {
+    /// Skills needed for the event. Candidates hold at least one at min_level or above
+    pub skills: Vec<Uuid>,
+    pub min_level: CapabilityLevel,
+    /// Candidates must speak every listed language
+    #[graphql(default)]
+    pub languages: Vec<LanguageName>,
+    pub province: Option<String>,
+    /// Upper bound on the total effort (FTE) of a candidate's active roles
+    pub max_current_effort: Option<f64>,
+    #[graphql(default)]
+    pub level_source: LevelSource,
+    /// Deployment limits for candidates. When empty, people currently deployed or whose last
+    /// deployment ended in the last MOBILIZATION_COOLDOWN_DAYS are excluded
+    pub deployment_exclusion: Option<DeploymentExclusion>,
+}
+
+#[derive(Debug, Clone, SimpleObject)]
+/// A person ranked for mobilization by skill fit and availability
+pub struct MobilizationCandidate {
+    pub person: Person,
+    /// Weighted blend of fit and availability, 0 to 1
+    pub score: f64,
+    /// Average credit across the requested skills, 0 to 1
+    pub fit: f64,
+    /// 0 to 1, lower as role effort and open work go up
+    pub availability: f64,
+    /// Total effort (FTE) of the person's active roles
+    pub current_effort: f64,
+    /// Total effort of open work on the person's active roles
+    pub open_work_effort: i32,
+    /// The candidate's level for each requested skill
+    pub skills: Vec<SkillGap>,
+}
+
+/// Scores for a candidate before the person is loaded
+struct Ranked {
+    person_id: Uuid,
+    score: f64,
+    fit: f64,
+    availability: f64,
+    current_effort: f64,
+    open_work_effort: i32,
+    skills: Vec<SkillGap>,
+}
+
+/// A person can be mobilized if they speak the language at more than basic proficiency
+fn speaks(data: &LanguageData) -> bool {
+    matches!(data.speaking, Some(LanguageLevel::B | LanguageLevel::C | LanguageLevel::E))
+}
+
+impl MobilizationCandidate {
+
+    /// Finds and ranks people for a surge.
+    /// Recently deployed people are excluded (see MobilizationSearch::deployment_exclusion)
+    /// so the same people are not tapped repeatedly.
+    pub fn search(search: &MobilizationSearch, limit: usize) -> Result<Vec<MobilizationCandidate>> {
+
+        if search.skills.is_empty() {
+            return Err(Error::new("At least one skill is required"));
+        }
+
+        // Skill levels held by each person in the requested skills
+        let mut held_by_person: HashMap<Uuid, HashMap<Uuid, CapabilityLevel>> = HashMap::new();
+        for cap in Capability::get_by_skill_ids(&search.skills)? {
+            if let Some(level) = search.level_source.level(&cap) {
+                held_by_person.entry(cap.person_id).or_default().insert(cap.skill_id, level);
+            }
+        }
+
+        held_by_person.retain(|_, held| held.values().any(|l| *l >= search.min_level));
+
+        let mut person_ids: Vec<Uuid> = held_by_person.keys().copied().collect();
+
+        if let Some(province) = &search.province {
+            // Located by their current primary contact
+            let in_province: HashSet<Uuid> = ContactData::get_primary_by_person_ids(&person_ids)?
+                .into_iter()
+                .filter(|c| c.province.as_deref().is_some_and(|p| p.eq_ignore_ascii_case(province)))
+                .map(|c| c.person_id)
+                .collect();
+
+            person_ids.retain(|id| in_province.contains(id));
+        }
+
+        if !search.languages.is_empty() {
+            let mut spoken: HashMap<Uuid, Vec<LanguageName>> = HashMap::new();
+            for data in LanguageData::get_by_person_ids(&person_ids)? {
+                if speaks(&data) {
+                    spoken.entry(data.person_id).or_default().push(data.language_name);
+                }
+            }
+
+            person_ids.retain(|id| spoken.get(id)
+                .is_some_and(|langs| search.languages.iter().all(|l| langs.contains(l))));
+        }
+
+        let exclusion = search.deployment_exclusion.clone()
+            .unwrap_or_else(|| DeploymentExclusion::cooldown(MOBILIZATION_COOLDOWN_DAYS));
+
+        let recently_mobilized = exclusion.excluded_person_ids(&person_ids)?;
+
+        person_ids.retain(|id| !recently_mobilized.contains(id));
+
+        // Current load from active roles and their open work
+        let active_roles: Vec<Role> = Role::get_by_person_ids(&person_ids)?
+            .into_iter()
+            .filter(|r| r.active)
+            .collect();
+
+        let role_ids: Vec<Uuid> = active_roles.iter().map(|r| r.id).collect();
+
+        let mut work_by_role: HashMap<Uuid, i32> = HashMap::new();
+        for work in Work::get_open_by_role_ids(&role_ids)? {
+            *work_by_role.entry(work.role_id).or_insert(0) += work.effort;
+        }
+
+        let mut load: HashMap<Uuid, (f64, i32)> = HashMap::new();
+        for role in &active_roles {
+            if let Some(person_id) = role.person_id {
+                let entry = load.entry(person_id).or_insert((0.0, 0));
+                entry.0 += role.effort;
+                entry.1 += work_by_role.get(&role.id).copied().unwrap_or(0);
+            }
+        }
+
+        let skill_names: HashMap<Uuid, String> = Skill::get_by_ids(&search.skills)?
+            .into_iter()
+            .map(|s| (s.id, s.name_en))
+            .collect();
+
+        let mut ranked: Vec<Ranked> = Vec::new();
+
+        for id in person_ids {
+            let (current_effort, open_work_effort) = load.get(&id).copied().unwrap_or((0.0, 0));
+
+            if search.max_current_effort.is_some_and(|max| current_effort > max) {
+                continue;
+            }
+
+            let held = &held_by_person[&id];
+
+            let skills: Vec<SkillGap> = search.skills.iter()
+                .map(|skill_id| SkillGap {
+                    skill_id: *skill_id,
+                    skill_name: skill_names.get(skill_id).cloned().unwrap_or_default(),
+                    required_level: search.min_level,
+                    held_level: held.get(skill_id).copied(),
+                })
+                .collect();
+
+            let fit = skills.iter()
+                .map(|s| level_credit(s.held_level, s.required_level))
+                .sum::<f64>() / skills.len() as f64;
+
+            // Half from unallocated FTE, half from how little open work is on their plate
+            let availability = 0.5 * (1.0 - current_effort.min(1.0))
+                + 0.5 / (1.0 + open_work_effort as f64 / MOBILIZATION_WORK_SCALE);
+
+            let score = MOBILIZATION_FIT_WEIGHT * fit + (1.0 - MOBILIZATION_FIT_WEIGHT) * availability;
+
+            ranked.push(Ranked {
+                person_id: id,
+                score,
+                fit,
+                availability,
+                current_effort,
+                open_work_effort,
+                skills,
+            });
+        }
+
+        ranked.sort_by(|a, b| b.score.total_cmp(&a.score));
+        ranked.truncate(limit);
+
+        let ids: Vec<Uuid> = ranked.iter().map(|r| r.person_id).collect();
+
+        let mut people: HashMap<Uuid, Person> = Person::get_by_ids(&ids)?
+            .into_iter()
+            .map(|p| (p.id, p))
+            .collect();
+
+        Ok(ranked.into_iter()
+            .filter_map(|r| {
+                people.remove(&r.person_id).map(|person| MobilizationCandidate {
+                    person,
+                    score: r.score,
+                    fit: r.fit,
+                    availability: r.availability,
+                    current_effort: r.current_effort,
+                    open_work_effort: r.open_work_effort,
+                    skills: r.skills,
+                })
+            })
+            .collect())
+    }
+}
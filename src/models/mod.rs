@@ -8,6 +8,7 @@ mod role;
 mod skill;
 mod capability;
 mod validation;
+mod validation_policy;
 mod affiliation;
 mod task;
 mod work;
@@ -15,11 +16,26 @@ mod language;
 mod publication;
 mod publication_contributor;
 mod requirement;
+mod org_chart;
+mod matching;
+mod mobilization;
+mod deployment;
+mod employee_data;
+mod contact_data;
+mod as_of;
+mod change_record;
+mod org_diff;
+mod disclosure;
+mod session;
+mod api_key;
 
 mod access_log;
 mod user;
 mod messages;
 mod auth;
+mod auth_context;
+mod oidc;
+mod authentication;
 
 pub use person::*;
 pub use organization::*;
@@ -31,6 +47,7 @@ pub use role::*;
 pub use skill::*;
 pub use capability::*;
 pub use validation::*;
+pub use validation_policy::*;
 pub use affiliation::*;
 pub use task::*;
 pub use work::*;
@@ -38,8 +55,23 @@ pub use language::*;
 pub use publication::*;
 pub use publication_contributor::*;
 pub use requirement::*;
+pub use org_chart::*;
+pub use matching::*;
+pub use mobilization::*;
+pub use deployment::*;
+pub use employee_data::*;
+pub use contact_data::*;
+pub use as_of::*;
+pub use change_record::*;
+pub use org_diff::*;
+pub use disclosure::*;
+pub use session::*;
+pub use api_key::*;
 
 pub use self::access_log::*;
 pub use self::user::*;
 //pub use messages::*;
-pub use auth::*;
\ No newline at end of file
+pub use auth::*;
+pub use auth_context::*;
+pub use oidc::*;
+pub use authentication::*;
\ No newline at end of file
@@ -0,0 +1,237 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use alcoholic_jwt::{token_kid, validate, Validation, JWKS};
+use async_graphql::*;
+use lazy_static::lazy_static;
+use serde_json::Value;
+
+use crate::common_utils::UserRole;
+use crate::config_variables::{OIDC_JWKS_CACHE_SECONDS, OIDC_JWKS_MIN_REFRESH_SECONDS};
+
+/// Where the provider's signing keys come from
+#[derive(Debug, Clone)]
+enum JwksSource {
+    Url(String),
+    /// A local JWKS document, for testing without the provider
+    File(PathBuf),
+}
+
+/// OpenID Connect settings, read from the environment.
+/// OIDC sign in is disabled unless OIDC_ISSUER is set.
+#[derive(Debug, Clone)]
+pub struct OidcConfig {
+    /// Must match the iss claim
+    pub issuer: String,
+    /// Must match the aud claim, usually our client id
+    pub audience: String,
+    jwks_source: JwksSource,
+    /// Claim holding the user's role or roles, e.g. "roles" or "groups"
+    pub role_claim: String,
+    /// Replace the user's role with the one in the role claim at every sign in.
+    /// Set OIDC_SYNC_ROLE=false to keep roles assigned in the app after the user is created.
+    pub sync_role: bool,
+}
+
+impl OidcConfig {
+    fn from_env() -> std::result::Result<Option<Self>, String> {
+        let issuer = match std::env::var("OIDC_ISSUER") {
+            Ok(issuer) => issuer,
+            Err(_) => return Ok(None),
+        };
+
+        let audience = std::env::var("OIDC_AUDIENCE")
+            .map_err(|_| "OIDC_ISSUER is set but OIDC_AUDIENCE isn't".to_string())?;
+
+        let jwks_source = match (std::env::var("OIDC_JWKS_FILE"), std::env::var("OIDC_JWKS_URL")) {
+            (Ok(path), _) => JwksSource::File(PathBuf::from(path)),
+            (_, Ok(url)) => JwksSource::Url(url),
+            _ => return Err("OIDC_ISSUER is set but neither OIDC_JWKS_URL nor OIDC_JWKS_FILE is".to_string()),
+        };
+
+        let sync_role = match std::env::var("OIDC_SYNC_ROLE").as_deref() {
+            Err(_) | Ok("true") => true,
+            Ok("false") => false,
+            Ok(other) => return Err(format!("OIDC_SYNC_ROLE must be true or false, not {}", other)),
+        };
+
+        Ok(Some(OidcConfig {
+            issuer,
+            audience,
+            jwks_source,
+            role_claim: std::env::var("OIDC_ROLE_CLAIM").unwrap_or_else(|_| "roles".to_string()),
+            sync_role,
+        }))
+    }
+}
+
+/// Reads the OIDC settings from the environment. Called from main so that a partial
+/// configuration stops the server at startup rather than failing at the first sign in.
+pub fn check_oidc_config() -> std::result::Result<(), String> {
+    OIDC_CONFIG.as_ref().map(|_| ()).map_err(|e| e.to_owned())
+}
+
+struct CachedJwks {
+    jwks: JWKS,
+    fetched_at: Instant,
+}
+
+lazy_static! {
+    static ref OIDC_CONFIG: std::result::Result<Option<OidcConfig>, String> = OidcConfig::from_env();
+    static ref JWKS_CACHE: RwLock<Option<CachedJwks>> = RwLock::new(None);
+}
+
+/// Returns the cached key set unless it is older than max_age
+fn cached_jwks(max_age: Duration) -> Option<JWKS> {
+    let cache = JWKS_CACHE.read().ok()?;
+
+    cache.as_ref()
+        .filter(|c| c.fetched_at.elapsed() < max_age)
+        .map(|c| c.jwks.clone())
+}
+
+async fn fetch_jwks(source: &JwksSource) -> Result<JWKS> {
+    let jwks = match source {
+        JwksSource::Url(url) => reqwest::get(url)
+            .await?
+            .error_for_status()?
+            .json::<JWKS>()
+            .await?,
+        JwksSource::File(path) => {
+            let path = path.to_owned();
+            let text = actix_web::web::block(move || std::fs::read_to_string(path))
+                .await
+                .map_err(|e| Error::new(e.to_string()))??;
+
+            serde_json::from_str(&text)?
+        },
+    };
+
+    if let Ok(mut cache) = JWKS_CACHE.write() {
+        *cache = Some(CachedJwks { jwks: jwks.clone(), fetched_at: Instant::now() });
+    }
+
+    Ok(jwks)
+}
+
+/// An identity asserted by the OIDC provider in a verified RS256 ID or access token
+#[derive(Debug, Clone)]
+pub struct ExternalIdentity {
+    pub issuer: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub name: Option<String>,
+    /// Highest UserRole named in the role claim, or User if none match
+    pub role: UserRole,
+    /// Whether role replaces an existing user's role, see OidcConfig::sync_role
+    pub sync_role: bool,
+}
+
+impl ExternalIdentity {
+
+    /// Verifies the token's signature against the provider's JWKS and its
+    /// issuer, audience, expiry and subject claims
+    pub async fn from_token(token: &str) -> Result<Self> {
+        let config = OIDC_CONFIG.as_ref()
+            .ok()
+            .and_then(|c| c.as_ref())
+            .ok_or_else(|| Error::new("OIDC sign in is not configured"))?;
+
+        let kid = token_kid(token)
+            .map_err(|e| Error::new(format!("Invalid token: {:?}", e)))?
+            .ok_or_else(|| Error::new("Token has no kid header"))?;
+
+        let cached = cached_jwks(Duration::from_secs(OIDC_JWKS_CACHE_SECONDS));
+
+        // Refetch if the key isn't cached, as the provider may have rotated keys,
+        // but no more often than OIDC_JWKS_MIN_REFRESH_SECONDS
+        let jwks = match cached {
+            Some(jwks) if jwks.find(&kid).is_some() => jwks,
+            Some(jwks) if cached_jwks(Duration::from_secs(OIDC_JWKS_MIN_REFRESH_SECONDS)).is_some() => jwks,
+            _ => fetch_jwks(&config.jwks_source).await?,
+        };
+
+        let jwk = jwks.find(&kid)
+            .ok_or_else(|| Error::new("Token was not signed by a known key"))?;
+
+        let validations = vec![
+            Validation::Issuer(config.issuer.to_owned()),
+            Validation::Audience(config.audience.to_owned()),
+            Validation::NotExpired,
+            Validation::SubjectPresent,
+        ];
+
+        let valid = validate(token, jwk, validations)
+            .map_err(|e| Error::new(format!("Invalid token: {:?}", e)))?;
+
+        let claims = valid.claims;
+
+        Ok(ExternalIdentity {
+            issuer: config.issuer.to_owned(),
+            subject: claims["sub"].as_str().unwrap_or_default().to_string(),
+            email: claims["email"].as_str().map(|s| s.to_string()),
+            email_verified: claims["email_verified"].as_bool().unwrap_or(false),
+            name: claims["name"].as_str().map(|s| s.to_string()),
+            role: role_from_claim(&claims[config.role_claim.as_str()]),
+            sync_role: config.sync_role,
+        })
+    }
+
+    /// Stored in users.external_id to link the identity to a User
+    pub fn external_id(&self) -> String {
+        format!("{}|{}", self.issuer, self.subject)
+    }
+}
+
+/// Maps a string or array of strings such as "analyst" or ["USER", "OPERATOR"] to the
+/// highest UserRole named. Values that aren't a UserRole are ignored.
+fn role_from_claim(claim: &Value) -> UserRole {
+    let values: Vec<&str> = match claim {
+        Value::String(s) => vec![s.as_str()],
+        Value::Array(a) => a.iter().filter_map(|v| v.as_str()).collect(),
+        _ => Vec::new(),
+    };
+
+    values.iter()
+        .filter_map(|v| UserRole::from_str(&v.trim().to_uppercase()).ok())
+        .max()
+        .unwrap_or(UserRole::User)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn reads_a_single_role() {
+        assert_eq!(role_from_claim(&json!("ANALYST")), UserRole::Analyst);
+    }
+
+    #[test]
+    fn ignores_case_and_whitespace() {
+        assert_eq!(role_from_claim(&json!(" operator ")), UserRole::Operator);
+    }
+
+    #[test]
+    fn takes_the_highest_role_in_an_array() {
+        assert_eq!(role_from_claim(&json!(["USER", "admin", "ANALYST"])), UserRole::Admin);
+    }
+
+    #[test]
+    fn ignores_values_that_are_not_roles() {
+        assert_eq!(role_from_claim(&json!(["staff", 3, "analyst"])), UserRole::Analyst);
+    }
+
+    #[test]
+    fn defaults_to_user() {
+        assert_eq!(role_from_claim(&Value::Null), UserRole::User);
+        assert_eq!(role_from_claim(&json!("superuser")), UserRole::User);
+        assert_eq!(role_from_claim(&json!([])), UserRole::User);
+        assert_eq!(role_from_claim(&json!({ "role": "ADMIN" })), UserRole::User);
+    }
+}
@@ -0,0 +1,280 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use async_graphql::*;
+
+use crate::config_variables::{ORG_CHART_IMAGE_URL, ORG_CHART_PROFILE_URL};
+use crate::common_utils::{HolderDataGuard, REDACTED};
+use crate::graphql::identifying_field;
+
+use super::{Organization, OrgTier, OrgOwnership, Person, Role, Team};
+
+#[derive(Debug, Clone, Serialize, SimpleObject)]
+#[serde(rename_all = "camelCase")]
+/// A row in the d3-org-chart flat data model.
+/// Field order matches the CSV header:
+/// name,imageUrl,area,profileUrl,office,tags,isLoggedUser,positionName,id,parentId,size
+pub struct OrgChartNode {
+    #[graphql(
+        directive = identifying_field::apply(),
+        guard = "HolderDataGuard::new(self.holder)",
+    )]
+    pub name: String,
+    #[graphql(directive = identifying_field::apply())]
+    pub image_url: String,
+    /// Organization the node belongs to
+    pub area: String,
+    #[graphql(directive = identifying_field::apply())]
+    pub profile_url: String,
+    /// OrgTier name for tier nodes, team name for role nodes
+    pub office: String,
+    pub tags: String,
+    pub is_logged_user: bool,
+    pub position_name: String,
+    /// OrgTier id for tier nodes, Role id for role nodes
+    pub id: Uuid,
+    pub parent_id: Option<Uuid>,
+    /// Number of occupied roles in the tier's team, 1 for role nodes
+    pub size: i32,
+    #[graphql(skip)]
+    #[serde(skip)]
+    /// Person and User ids of the person named by the node, None if vacant
+    pub holder: Option<(Uuid, Uuid)>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, InputObject)]
+/// Options used to shape the org chart
+pub struct OrgChartOptions {
+    /// Organization to chart from its top tiers. Ignored if root_tier_id is set
+    pub organization_id: Option<Uuid>,
+    /// Tier to use as the root of the chart
+    pub root_tier_id: Option<Uuid>,
+    /// Number of tiers below the root to include. Unlimited if not set
+    pub max_depth: Option<i32>,
+    /// Add a node for every active role in each tier's team
+    #[serde(default)]
+    #[graphql(default)]
+    pub expand_teams: bool,
+}
+
+impl OrgChartNode {
+
+    /// Walks OrgTier parent/child links from the root(s) breadth first, returning
+    /// one node per tier (represented by its current owner) and, if expand_teams is set,
+    /// one node per active role in the tier's team.
+    /// `logged_user` marks the nodes held by the signed in user.
+    pub fn build(options: &OrgChartOptions, logged_user: Option<Uuid>) -> Result<Vec<OrgChartNode>> {
+
+        let (organization_id, roots) = match (options.root_tier_id, options.organization_id) {
+            (Some(root_id), _) => {
+                let root = OrgTier::get_by_id(&root_id)?;
+
+                if root.retired_at.is_some() {
+                    return Err(Error::new(format!("OrgTier {} is retired", root_id)));
+                }
+
+                (root.organization_id, vec![root.id])
+            },
+            (None, Some(org_id)) => {
+                let top = OrgTier::get_active_top_by_org_id(&org_id)?;
+                (org_id, top.iter().map(|t| t.id).collect())
+            },
+            (None, None) => return Err(Error::new("Either organization_id or root_tier_id is required")),
+        };
+
+        let organization = Organization::get_by_id(&organization_id)?;
+
+        // Load the whole organization in a handful of queries and walk it in memory.
+        // Retired tiers and teams are left out, along with the tiers and roles under them.
+        let tiers: HashMap<Uuid, OrgTier> = OrgTier::get_active_by_org_id(&organization_id)?
+            .into_iter()
+            .map(|t| (t.id, t))
+            .collect();
+
+        let mut children: HashMap<Uuid, Vec<Uuid>> = HashMap::new();
+        for tier in tiers.values() {
+            if let Some(parent) = tier.parent_tier {
+                children.entry(parent).or_default().push(tier.id);
+            }
+        }
+
+        // Select the tiers to chart, recording each tier's depth below the root
+        let mut selected: Vec<Uuid> = Vec::new();
+        let mut visited: HashSet<Uuid> = roots.iter().copied().collect();
+        let mut frontier = roots;
+        let mut depth = 0;
+
+        while !frontier.is_empty() {
+            selected.extend(frontier.iter().copied());
+
+            if options.max_depth.is_some_and(|max| depth >= max) {
+                break;
+            }
+
+            let mut next: Vec<Uuid> = frontier.iter()
+                .flat_map(|id| children.get(id).cloned().unwrap_or_default())
+                .collect();
+
+            // Parent links can form a cycle, so only walk each tier once
+            next.retain(|id| visited.insert(*id));
+
+            next.sort_by_key(|id| tiers.get(id).map(|t| t.name_en.clone()));
+
+            frontier = next;
+            depth += 1;
+        }
+
+        let selected_set: HashSet<Uuid> = selected.iter().copied().collect();
+
+        let owner_by_tier: HashMap<Uuid, Uuid> = OrgOwnership::get_current_by_org_tier_ids(&selected)?
+            .into_iter()
+            .map(|o| (o.org_tier_id, o.owner_id))
+            .collect();
+
+        let teams = Team::get_active_by_org_tier_ids(&selected)?;
+        let team_ids: Vec<Uuid> = teams.iter().map(|t| t.id).collect();
+
+        let team_roles: Vec<Role> = Role::get_by_team_ids(&team_ids)?
+            .into_iter()
+            .filter(|r| r.active)
+            .collect();
+
+        let mut person_ids: Vec<Uuid> = owner_by_tier.values().copied().collect();
+        person_ids.extend(team_roles.iter().filter_map(|r| r.person_id));
+
+        let persons: HashMap<Uuid, Person> = Person::get_by_ids(&person_ids)?
+            .into_iter()
+            .map(|p| (p.id, p))
+            .collect();
+
+        let owner_roles: Vec<Role> = Role::get_by_person_ids(&owner_by_tier.values().copied().collect::<Vec<Uuid>>())?
+            .into_iter()
+            .filter(|r| r.active)
+            .collect();
+
+        let mut teams_by_tier: HashMap<Uuid, Vec<&Team>> = HashMap::new();
+        for team in &teams {
+            teams_by_tier.entry(team.org_tier_id).or_default().push(team);
+        }
+
+        let mut nodes = Vec::new();
+
+        for tier_id in &selected {
+            let tier = &tiers[tier_id];
+            let tier_teams = teams_by_tier.get(tier_id).cloned().unwrap_or_default();
+            let tier_team_ids: HashSet<Uuid> = tier_teams.iter().map(|t| t.id).collect();
+
+            let owner = owner_by_tier.get(tier_id).and_then(|id| persons.get(id));
+
+            // Prefer the owner's role on one of this tier's teams
+            let owner_title = owner.and_then(|o| {
+                let held: Vec<&Role> = owner_roles.iter()
+                    .filter(|r| r.person_id == Some(o.id))
+                    .collect();
+
+                held.iter()
+                    .find(|r| tier_team_ids.contains(&r.team_id))
+                    .or(held.first())
+                    .map(|r| r.title_en.clone())
+            });
+
+            let position_name = match owner_title {
+                Some(title) => format!("{} - {}", title, tier.name_en),
+                None => tier.name_en.clone(),
+            };
+
+            let size = team_roles.iter()
+                .filter(|r| tier_team_ids.contains(&r.team_id) && r.person_id.is_some())
+                .count() as i32;
+
+            // Parent is left empty for the root(s) so the chart has a single entry point
+            let parent_id = tier.parent_tier.filter(|p| selected_set.contains(p));
+
+            nodes.push(OrgChartNode {
+                name: owner.map(Person::full_name).unwrap_or_else(|| "Vacant".to_string()),
+                image_url: ORG_CHART_IMAGE_URL.to_string(),
+                area: organization.name_en.clone(),
+                profile_url: profile_url(owner),
+                office: tier.name_en.clone(),
+                tags: format!("{:?}", tier.primary_domain),
+                is_logged_user: is_logged(owner, logged_user),
+                position_name,
+                id: tier.id,
+                parent_id,
+                size,
+                holder: owner.map(|o| (o.id, o.user_id)),
+            });
+
+            if options.expand_teams {
+                for team in tier_teams {
+                    for role in team_roles.iter().filter(|r| r.team_id == team.id) {
+                        let holder = role.person_id.and_then(|id| persons.get(&id));
+
+                        // The owner is already represented by the tier node
+                        if holder.is_some() && role.person_id == owner.map(|o| o.id) {
+                            continue;
+                        }
+
+                        nodes.push(OrgChartNode {
+                            name: holder.map(Person::full_name).unwrap_or_else(|| "Vacant".to_string()),
+                            image_url: ORG_CHART_IMAGE_URL.to_string(),
+                            area: organization.name_en.clone(),
+                            profile_url: profile_url(holder),
+                            office: team.name_en.clone(),
+                            tags: format!("{:?}", team.primary_domain),
+                            is_logged_user: is_logged(holder, logged_user),
+                            position_name: role.title_en.clone(),
+                            id: role.id,
+                            parent_id: Some(tier.id),
+                            size: 1,
+                            holder: holder.map(|h| (h.id, h.user_id)),
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(nodes)
+    }
+
+    /// Blanks the names, images and profile links of nodes for users below
+    /// Granularity::Identifiable
+    pub fn redact(nodes: &mut [OrgChartNode]) {
+        for node in nodes {
+            if node.holder.is_some() {
+                node.name = REDACTED.to_string();
+                node.profile_url = String::new();
+            }
+            node.image_url = String::new();
+        }
+    }
+
+    /// Serializes nodes as CSV with the d3-org-chart header
+    pub fn to_csv(nodes: &[OrgChartNode]) -> Result<String> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+
+        for node in nodes {
+            writer.serialize(node)?;
+        }
+
+        let bytes = writer.into_inner()
+            .map_err(|e| Error::new(e.to_string()))?;
+
+        String::from_utf8(bytes).map_err(|e| Error::new(e.to_string()))
+    }
+}
+
+fn profile_url(person: Option<&Person>) -> String {
+    match person {
+        Some(p) => format!("{}/{}", ORG_CHART_PROFILE_URL, p.id),
+        None => String::new(),
+    }
+}
+
+fn is_logged(person: Option<&Person>, logged_user: Option<Uuid>) -> bool {
+    match (person, logged_user) {
+        (Some(p), Some(user_id)) => p.user_id == user_id,
+        _ => false,
+    }
+}
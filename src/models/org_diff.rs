@@ -0,0 +1,640 @@
+use std::collections::HashMap;
+
+use async_graphql::*;
+use chrono::{Duration, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::common_utils::{HolderDataGuard, PersonalDataGuard};
+use crate::graphql::{identifying_field, person_id_field};
+
+use super::{AsOf, Capability, Cell, ChangeAction, ChangeRecord, LevelSource, OrgOwnership, OrgTier, Person, Role,
+    SkillDomain, Team, TeamOwnership, ValidatedLevel, in_effect, protect_cells};
+
+#[derive(Debug, Clone, Deserialize, InputObject)]
+/// The organization and dates to compare. Changes after `from` up to and including `to` are reported.
+pub struct OrgDiffOptions {
+    pub organization_id: Uuid,
+    pub from: NaiveDateTime,
+    pub to: NaiveDateTime,
+    /// Which capability level to compare in capability_changes
+    #[graphql(default)]
+    #[serde(default)]
+    pub level_source: LevelSource,
+}
+
+#[derive(Debug, Clone, Serialize, SimpleObject)]
+pub struct TeamChange {
+    pub team_id: Uuid,
+    pub name: String,
+    pub at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, SimpleObject)]
+pub struct RoleChange {
+    pub role_id: Uuid,
+    pub title: String,
+    pub team_id: Uuid,
+    pub team_name: String,
+    #[graphql(directive = person_id_field::apply())]
+    pub person_id: Option<Uuid>,
+    #[graphql(
+        directive = identifying_field::apply(),
+        guard = "HolderDataGuard::new(self.holder)",
+    )]
+    pub person_name: Option<String>,
+    pub at: NaiveDateTime,
+    #[graphql(skip)]
+    #[serde(skip)]
+    /// Person and User ids of the holder named, None if vacant
+    pub holder: Option<(Uuid, Uuid)>,
+}
+
+#[derive(Debug, Clone, Serialize, SimpleObject)]
+/// A person whose roles were on a different team at `to` than at `from`
+pub struct PersonMove {
+    #[graphql(directive = person_id_field::apply())]
+    pub person_id: Uuid,
+    #[graphql(
+        directive = identifying_field::apply(),
+        guard = "PersonalDataGuard::new(self.person_id, self.user_id)",
+    )]
+    pub person_name: String,
+    pub from_team_id: Uuid,
+    pub from_team_name: String,
+    pub to_team_id: Uuid,
+    pub to_team_name: String,
+    #[graphql(skip)]
+    #[serde(skip)]
+    pub user_id: Uuid,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum)]
+pub enum OwnedUnit {
+    Team,
+    OrgTier,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum)]
+pub enum OwnershipChangeKind {
+    Assigned,
+    Ended,
+}
+
+#[derive(Debug, Clone, Serialize, SimpleObject)]
+pub struct OwnershipChange {
+    pub unit: OwnedUnit,
+    pub unit_id: Uuid,
+    pub unit_name: String,
+    #[graphql(directive = person_id_field::apply())]
+    pub owner_id: Uuid,
+    #[graphql(
+        directive = identifying_field::apply(),
+        guard = "PersonalDataGuard::new(self.owner_id, self.owner_user_id)",
+    )]
+    pub owner_name: String,
+    pub change: OwnershipChangeKind,
+    pub at: NaiveDateTime,
+    #[graphql(skip)]
+    #[serde(skip)]
+    pub owner_user_id: Uuid,
+}
+
+#[derive(Debug, Clone, Serialize, SimpleObject)]
+/// Capabilities held in a skill domain at each date.
+/// Levels before change history was recorded are taken as the current level.
+/// Counts are null when suppressed to protect small groups, along with the averages
+/// and net change worked out from them.
+pub struct DomainCapabilityChange {
+    pub domain: SkillDomain,
+    pub held_from: Option<i32>,
+    pub held_to: Option<i32>,
+    pub net_held: Option<i32>,
+    /// Average level, Desired = 0 to Specialist = 4
+    pub average_level_from: Option<f64>,
+    pub average_level_to: Option<f64>,
+    /// Capabilities held at both dates at a higher level at `to`
+    pub raised: Option<i32>,
+    /// Capabilities held at both dates at a lower level at `to`
+    pub lowered: Option<i32>,
+    pub suppressed: bool,
+}
+
+#[derive(Debug, Clone, SimpleObject)]
+/// Changes to an organization between two dates, for HR reviews
+pub struct OrgDiff {
+    pub organization_id: Uuid,
+    pub from: NaiveDateTime,
+    pub to: NaiveDateTime,
+    pub teams_created: Vec<TeamChange>,
+    pub teams_retired: Vec<TeamChange>,
+    /// Vacant roles created in the period
+    pub roles_opened: Vec<RoleChange>,
+    /// Roles a person started in the period
+    pub roles_filled: Vec<RoleChange>,
+    /// Roles a person left in the period
+    pub roles_vacated: Vec<RoleChange>,
+    pub people_moved: Vec<PersonMove>,
+    pub ownership_changes: Vec<OwnershipChange>,
+    pub capability_changes: Vec<DomainCapabilityChange>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+/// Flat row of the CSV export
+struct OrgDiffRow {
+    section: &'static str,
+    change: String,
+    entity_id: Option<Uuid>,
+    name: String,
+    detail: String,
+    occurred_at: Option<NaiveDateTime>,
+}
+
+/// Levels held in one domain at each date, before averaging
+struct DomainTally {
+    domain: SkillDomain,
+    at_from: Vec<f64>,
+    at_to: Vec<f64>,
+    raised: usize,
+    lowered: usize,
+}
+
+/// Written in the CSV in place of suppressed counts
+const SUPPRESSED: &str = "suppressed";
+
+fn shown(count: Option<i32>) -> String {
+    count.map(|c| c.to_string()).unwrap_or_else(|| SUPPRESSED.to_string())
+}
+
+/// Change history of a set of roles from a date on, used to see who held them when
+struct RoleHistory {
+    records: HashMap<Uuid, Vec<ChangeRecord>>,
+}
+
+impl RoleHistory {
+    fn load(roles: &[Role], since: NaiveDateTime) -> Result<Self> {
+        let ids: Vec<Uuid> = roles.iter().map(|r| r.id).collect();
+
+        let mut records: HashMap<Uuid, Vec<ChangeRecord>> = HashMap::new();
+        for record in ChangeRecord::get_by_entity_ids_since(&ids, since)? {
+            records.entry(record.entity_id).or_default().push(record);
+        }
+
+        Ok(RoleHistory { records })
+    }
+
+    fn of(&self, role: &Role) -> Vec<&ChangeRecord> {
+        self.records.get(&role.id)
+            .map(|r| r.iter().collect())
+            .unwrap_or_default()
+    }
+
+    /// The role as it was at as_of, or None if it was created after
+    fn rewind(&self, role: &Role, as_of: NaiveDateTime) -> Result<Option<Role>> {
+        ChangeRecord::rewind(role, &self.of(role), as_of)
+    }
+
+    fn rewind_all(&self, roles: &[Role], as_of: NaiveDateTime) -> Result<Vec<Role>> {
+        let mut res = Vec::new();
+
+        for role in roles {
+            if let Some(r) = self.rewind(role, as_of)? {
+                res.push(r);
+            }
+        }
+
+        Ok(res)
+    }
+
+    /// Each change of a role's holder after from, up to and including to,
+    /// as the role before and after the change and when it was made
+    fn reassignments(&self, roles: &[Role], from: NaiveDateTime, to: NaiveDateTime)
+        -> Result<Vec<(Role, Role, NaiveDateTime)>> {
+
+        let mut res = Vec::new();
+
+        for role in roles {
+            for record in self.of(role) {
+                let reassigned = record.action == ChangeAction::Update
+                    && within(record.changed_at, from, to)
+                    && record.changes.get("person_id").is_some();
+
+                if !reassigned {
+                    continue;
+                }
+
+                let before = self.rewind(role, record.changed_at - Duration::microseconds(1))?;
+                let after = self.rewind(role, record.changed_at)?;
+
+                if let (Some(before), Some(after)) = (before, after) {
+                    res.push((before, after, record.changed_at));
+                }
+            }
+        }
+
+        Ok(res)
+    }
+}
+
+/// True if t falls after from, up to and including to
+fn within(t: NaiveDateTime, from: NaiveDateTime, to: NaiveDateTime) -> bool {
+    from < t && t <= to
+}
+
+fn level_value(capability: &Capability, source: LevelSource) -> Option<f64> {
+    source.level(capability)
+        .map(|l| ValidatedLevel::get_value_from_capability_level(&l) as f64 / 100.0)
+}
+
+fn average(values: &[f64]) -> Option<f64> {
+    if values.is_empty() {
+        None
+    } else {
+        Some(values.iter().sum::<f64>() / values.len() as f64)
+    }
+}
+
+impl OrgDiff {
+
+    pub fn build(options: &OrgDiffOptions) -> Result<OrgDiff> {
+
+        let (from, to) = (options.from, options.to);
+
+        if from >= to {
+            return Err(Error::new("from must be before to"));
+        }
+
+        let teams = Team::get_by_org_id(&options.organization_id)?;
+        let team_ids: Vec<Uuid> = teams.iter().map(|t| t.id).collect();
+        let team_names: HashMap<Uuid, String> = teams.iter()
+            .map(|t| (t.id, t.name_en.to_owned()))
+            .collect();
+
+        let org_tiers = OrgTier::get_by_org_id(&options.organization_id)?;
+        let tier_ids: Vec<Uuid> = org_tiers.iter().map(|t| t.id).collect();
+
+        let roles = Role::get_by_team_ids(&team_ids)?;
+        let team_ownerships = TeamOwnership::get_by_team_ids(&team_ids)?;
+        let org_ownerships = OrgOwnership::get_by_org_tier_ids(&tier_ids)?;
+
+        let history = RoleHistory::load(&roles, from)?;
+
+        // Roles as they were at each date, and whenever their holder changed in between
+        let at_from = history.rewind_all(&roles, from)?;
+        let at_to = history.rewind_all(&roles, to)?;
+        let reassignments = history.reassignments(&roles, from, to)?;
+
+        let mut person_ids: Vec<Uuid> = at_from.iter().chain(&at_to)
+            .chain(reassignments.iter().flat_map(|(before, after, _)| [before, after]))
+            .filter_map(|r| r.person_id)
+            .chain(team_ownerships.iter().map(|o| o.person_id))
+            .chain(org_ownerships.iter().map(|o| o.owner_id))
+            .collect();
+        person_ids.sort();
+        person_ids.dedup();
+
+        let names: HashMap<Uuid, (String, Uuid)> = Person::get_by_ids(&person_ids)?
+            .iter()
+            .map(|p| (p.id, (p.full_name(), p.user_id)))
+            .collect();
+
+        let name_of = |id: &Uuid| names.get(id).map(|(n, _)| n.to_owned()).unwrap_or_default();
+        let user_of = |id: &Uuid| names.get(id).map(|(_, u)| *u).unwrap_or_default();
+        let team_name = |id: &Uuid| team_names.get(id).cloned().unwrap_or_default();
+
+        let team_change = |t: &Team, at: NaiveDateTime| TeamChange {
+            team_id: t.id,
+            name: t.name_en.to_owned(),
+            at,
+        };
+
+        let teams_created = teams.iter()
+            .filter(|t| within(t.created_at, from, to))
+            .map(|t| team_change(t, t.created_at))
+            .collect();
+
+        let teams_retired = teams.iter()
+            .filter_map(|t| t.retired_at.filter(|r| within(*r, from, to)).map(|r| team_change(t, r)))
+            .collect();
+
+        let role_change = |r: &Role, at: NaiveDateTime| RoleChange {
+            role_id: r.id,
+            title: r.title_en.to_owned(),
+            team_id: r.team_id,
+            team_name: team_name(&r.team_id),
+            person_id: r.person_id,
+            person_name: r.person_id.as_ref().map(name_of),
+            at,
+            holder: r.person_id.map(|id| (id, user_of(&id))),
+        };
+
+        let mut roles_opened = Vec::new();
+
+        for role in roles.iter().filter(|r| within(r.created_at, from, to)) {
+            if history.rewind(role, role.created_at)?.is_some_and(|r| r.person_id.is_none()) {
+                roles_opened.push(role_change(role, role.created_at));
+            }
+        }
+
+        // Roles started or ended by their holder at `to`, and holders changed in the period
+        let mut roles_filled: Vec<RoleChange> = at_to.iter()
+            .filter(|r| r.person_id.is_some() && within(r.start_datestamp, from, to))
+            .map(|r| role_change(r, r.start_datestamp))
+            .collect();
+
+        let mut roles_vacated: Vec<RoleChange> = at_to.iter()
+            .filter(|r| r.person_id.is_some())
+            .filter_map(|r| r.end_date.filter(|e| within(*e, from, to)).map(|e| role_change(r, e)))
+            .collect();
+
+        for (before, after, at) in &reassignments {
+            if before.person_id.is_some() {
+                roles_vacated.push(role_change(before, *at));
+            }
+
+            if after.person_id.is_some() {
+                roles_filled.push(role_change(after, *at));
+            }
+        }
+
+        roles_filled.sort_by_key(|r| r.at);
+        roles_vacated.sort_by_key(|r| r.at);
+
+        // Teams each person held roles on at each date
+        let mut held: HashMap<Uuid, (Vec<Uuid>, Vec<Uuid>)> = HashMap::new();
+        for role in at_from.iter().filter(|r| r.in_effect_on(from)) {
+            if let Some(person_id) = role.person_id {
+                held.entry(person_id).or_default().0.push(role.team_id);
+            }
+        }
+        for role in at_to.iter().filter(|r| r.in_effect_on(to)) {
+            if let Some(person_id) = role.person_id {
+                held.entry(person_id).or_default().1.push(role.team_id);
+            }
+        }
+
+        let mut people_moved: Vec<PersonMove> = held.iter()
+            .filter_map(|(person_id, (at_from, at_to))| {
+                let left = at_from.iter().find(|t| !at_to.contains(t))?;
+                let joined = at_to.iter().find(|t| !at_from.contains(t))?;
+
+                Some(PersonMove {
+                    person_id: *person_id,
+                    person_name: name_of(person_id),
+                    from_team_id: *left,
+                    from_team_name: team_name(left),
+                    to_team_id: *joined,
+                    to_team_name: team_name(joined),
+                    user_id: user_of(person_id),
+                })
+            })
+            .collect();
+
+        people_moved.sort_by(|a, b| a.person_name.cmp(&b.person_name));
+
+        let tier_names: HashMap<Uuid, String> = org_tiers.iter()
+            .map(|t| (t.id, t.name_en.to_owned()))
+            .collect();
+
+        let mut ownership_changes: Vec<OwnershipChange> = Vec::new();
+
+        let mut push_ownership = |unit, unit_id: Uuid, unit_name: String, owner_id: Uuid,
+                                  start: NaiveDateTime, end: Option<NaiveDateTime>| {
+            for (change, at) in [(OwnershipChangeKind::Assigned, Some(start)), (OwnershipChangeKind::Ended, end)] {
+                if let Some(at) = at.filter(|t| within(*t, from, to)) {
+                    ownership_changes.push(OwnershipChange {
+                        unit,
+                        unit_id,
+                        unit_name: unit_name.to_owned(),
+                        owner_id,
+                        owner_name: name_of(&owner_id),
+                        change,
+                        at,
+                        owner_user_id: user_of(&owner_id),
+                    });
+                }
+            }
+        };
+
+        for o in &team_ownerships {
+            push_ownership(OwnedUnit::Team, o.team_id, team_name(&o.team_id), o.person_id,
+                o.start_datestamp, o.end_date);
+        }
+
+        for o in &org_ownerships {
+            push_ownership(OwnedUnit::OrgTier, o.org_tier_id,
+                tier_names.get(&o.org_tier_id).cloned().unwrap_or_default(), o.owner_id,
+                o.created_at, o.retired_at);
+        }
+
+        ownership_changes.sort_by_key(|o| o.at);
+
+        let capability_changes = Self::compare_capabilities(options)?;
+
+        Ok(OrgDiff {
+            organization_id: options.organization_id,
+            from,
+            to,
+            teams_created,
+            teams_retired,
+            roles_opened,
+            roles_filled,
+            roles_vacated,
+            people_moved,
+            ownership_changes,
+            capability_changes,
+        })
+    }
+
+    /// Compares capability levels per domain at both dates, undoing later changes with
+    /// the change history
+    fn compare_capabilities(options: &OrgDiffOptions) -> Result<Vec<DomainCapabilityChange>> {
+
+        let (from, to) = (options.from, options.to);
+
+        let capabilities = Capability::get_by_organization_id(options.organization_id)?;
+        let ids: Vec<Uuid> = capabilities.iter().map(|c| c.id).collect();
+
+        let mut records: HashMap<Uuid, Vec<ChangeRecord>> = HashMap::new();
+        for record in ChangeRecord::get_by_entity_ids_since(&ids, from)? {
+            records.entry(record.entity_id).or_default().push(record);
+        }
+
+        let mut domains: Vec<DomainTally> = Vec::new();
+
+        for capability in &capabilities {
+            let history: Vec<&ChangeRecord> = records.get(&capability.id)
+                .map(|r| r.iter().collect())
+                .unwrap_or_default();
+
+            let level_at = |date| -> Result<Option<f64>> {
+                Ok(ChangeRecord::rewind(capability, &history, date)?
+                    .filter(|c| in_effect(c.created_at, c.retired_at, date))
+                    .and_then(|c| level_value(&c, options.level_source)))
+            };
+
+            let (before, after) = (level_at(from)?, level_at(to)?);
+
+            if before.is_none() && after.is_none() {
+                continue;
+            }
+
+            let i = match domains.iter().position(|d| d.domain == capability.domain) {
+                Some(i) => i,
+                None => {
+                    domains.push(DomainTally {
+                        domain: capability.domain,
+                        at_from: Vec::new(),
+                        at_to: Vec::new(),
+                        raised: 0,
+                        lowered: 0,
+                    });
+                    domains.len() - 1
+                }
+            };
+
+            let tally = &mut domains[i];
+
+            if let Some(l) = before {
+                tally.at_from.push(l);
+            }
+
+            if let Some(l) = after {
+                tally.at_to.push(l);
+            }
+
+            match (before, after) {
+                (Some(b), Some(a)) if a > b => tally.raised += 1,
+                (Some(b), Some(a)) if a < b => tally.lowered += 1,
+                _ => {},
+            }
+        }
+
+        let names: Vec<String> = domains.iter().map(|t| format!("{:?}", t.domain)).collect();
+
+        // Each measure is protected on its own, as a domain's measures don't add up to anything
+        let protect = |count: fn(&DomainTally) -> usize| -> Vec<Option<i32>> {
+            protect_cells(&domains.iter()
+                .zip(&names)
+                .map(|(t, name)| Cell { row: name, column: "", count: count(t) as i64 })
+                .collect::<Vec<Cell>>())
+                .into_iter()
+                .map(|c| c.map(|c| c as i32))
+                .collect()
+        };
+
+        let held_from = protect(|t| t.at_from.len());
+        let held_to = protect(|t| t.at_to.len());
+        let raised = protect(|t| t.raised);
+        let lowered = protect(|t| t.lowered);
+
+        Ok(domains.iter()
+            .enumerate()
+            .map(|(i, t)| DomainCapabilityChange {
+                domain: t.domain,
+                held_from: held_from[i],
+                held_to: held_to[i],
+                net_held: held_from[i].zip(held_to[i]).map(|(from, to)| to - from),
+                average_level_from: held_from[i].and(average(&t.at_from)),
+                average_level_to: held_to[i].and(average(&t.at_to)),
+                raised: raised[i],
+                lowered: lowered[i],
+                suppressed: [held_from[i], held_to[i], raised[i], lowered[i]].iter().any(|c| c.is_none()),
+            })
+            .collect())
+    }
+
+    fn rows(&self) -> Vec<OrgDiffRow> {
+
+        let mut rows: Vec<OrgDiffRow> = Vec::new();
+
+        for (change, teams) in [("created", &self.teams_created), ("retired", &self.teams_retired)] {
+            rows.extend(teams.iter().map(|t| OrgDiffRow {
+                section: "teams",
+                change: change.to_string(),
+                entity_id: Some(t.team_id),
+                name: t.name.to_owned(),
+                detail: String::new(),
+                occurred_at: Some(t.at),
+            }));
+        }
+
+        for (change, roles) in [
+            ("opened", &self.roles_opened),
+            ("filled", &self.roles_filled),
+            ("vacated", &self.roles_vacated),
+        ] {
+            rows.extend(roles.iter().map(|r| OrgDiffRow {
+                section: "roles",
+                change: change.to_string(),
+                entity_id: Some(r.role_id),
+                name: r.title.to_owned(),
+                detail: match &r.person_name {
+                    Some(p) => format!("{} ({})", r.team_name, p),
+                    None => r.team_name.to_owned(),
+                },
+                occurred_at: Some(r.at),
+            }));
+        }
+
+        rows.extend(self.people_moved.iter().map(|m| OrgDiffRow {
+            section: "people",
+            change: "moved".to_string(),
+            entity_id: Some(m.person_id),
+            name: m.person_name.to_owned(),
+            detail: format!("{} -> {}", m.from_team_name, m.to_team_name),
+            occurred_at: None,
+        }));
+
+        rows.extend(self.ownership_changes.iter().map(|o| OrgDiffRow {
+            section: "ownership",
+            change: format!("{:?}", o.change).to_lowercase(),
+            entity_id: Some(o.unit_id),
+            name: o.unit_name.to_owned(),
+            detail: format!("{:?} owner {}", o.unit, o.owner_name),
+            occurred_at: Some(o.at),
+        }));
+
+        rows.extend(self.capability_changes.iter().map(|c| OrgDiffRow {
+            section: "capabilities",
+            change: c.net_held.map(|n| format!("{:+}", n)).unwrap_or_else(|| SUPPRESSED.to_string()),
+            entity_id: None,
+            name: format!("{:?}", c.domain),
+            detail: format!("held {} -> {}, raised {}, lowered {}",
+                shown(c.held_from), shown(c.held_to), shown(c.raised), shown(c.lowered)),
+            occurred_at: None,
+        }));
+
+        rows
+    }
+
+    /// The people named in the diff, as the field naming them with their person and user ids
+    pub fn named(&self) -> Vec<(&'static str, Uuid, Uuid)> {
+        let roles = [&self.roles_opened, &self.roles_filled, &self.roles_vacated].into_iter()
+            .flatten()
+            .filter_map(|r| r.holder)
+            .map(|(person_id, user_id)| ("personName", person_id, user_id));
+
+        let moves = self.people_moved.iter()
+            .map(|m| ("personName", m.person_id, m.user_id));
+
+        let owners = self.ownership_changes.iter()
+            .map(|o| ("ownerName", o.owner_id, o.owner_user_id));
+
+        roles.chain(moves).chain(owners).collect()
+    }
+
+    /// Serializes the diff as one CSV row per change
+    pub fn to_csv(&self) -> Result<String> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+
+        for row in self.rows() {
+            writer.serialize(row)?;
+        }
+
+        let bytes = writer.into_inner()
+            .map_err(|e| Error::new(e.to_string()))?;
+
+        String::from_utf8(bytes).map_err(|e| Error::new(e.to_string()))
+    }
+}
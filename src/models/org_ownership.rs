@@ -2,21 +2,25 @@ use std::fmt::Debug;
 
 use chrono::{prelude::*};
 use serde::{Deserialize, Serialize};
-use diesel::{self, Insertable, Queryable, ExpressionMethods};
+use diesel::{self, Connection, Insertable, Queryable, ExpressionMethods};
 use diesel::{RunQueryDsl, QueryDsl};
 use uuid::Uuid;
 use async_graphql::*;
 
 use crate::database::connection;
 use crate::schema::*;
+use crate::graphql::person_id_field;
 
-#[derive(Debug, Clone, Deserialize, Serialize, Queryable, Insertable, AsChangeset)]
+use super::{AsOfKey, in_effect_for};
+
+#[derive(Debug, Clone, Deserialize, Serialize, Queryable, Insertable, AsChangeset, SimpleObject)]
 #[diesel(table_name = org_tier_ownerships)]
 #[diesel(belongs_to(Person))]
 /// Represents a relationship between a person (owner) and an organizational tier
 /// Will be used to inform approvals and organizational authority
 pub struct OrgOwnership {
     pub id: Uuid,
+    #[graphql(directive = person_id_field::apply())]
     pub owner_id: Uuid,
     pub org_tier_id: Uuid,
 
@@ -82,6 +86,68 @@ impl OrgOwnership {
         Ok(res)
     }
 
+    /// Returns the unretired ownerships for a set of org tiers
+    pub fn get_current_by_org_tier_ids(ids: &[Uuid]) -> Result<Vec<Self>> {
+        let mut conn = connection()?;
+
+        let res = org_tier_ownerships::table
+            .filter(org_tier_ownerships::org_tier_id.eq_any(ids))
+            .filter(org_tier_ownerships::retired_at.is_null())
+            .load::<Self>(&mut conn)?;
+
+        Ok(res)
+    }
+
+    /// Returns all ownerships, current and retired, for a set of org tiers
+    pub fn get_by_org_tier_ids(ids: &[Uuid]) -> Result<Vec<Self>> {
+        let mut conn = connection()?;
+
+        let res = org_tier_ownerships::table
+            .filter(org_tier_ownerships::org_tier_id.eq_any(ids))
+            .load::<Self>(&mut conn)?;
+
+        Ok(res)
+    }
+
+    /// Returns the ownerships in effect for each (org tier, as_of) key, latest first
+    pub fn get_by_org_tier_ids_as_of(keys: &[AsOfKey]) -> Result<Vec<(AsOfKey, Self)>> {
+        let mut conn = connection()?;
+
+        let ids: Vec<Uuid> = keys.iter().map(|(id, _)| *id).collect();
+
+        let res = org_tier_ownerships::table
+            .filter(org_tier_ownerships::org_tier_id.eq_any(ids))
+            .order_by(org_tier_ownerships::created_at.desc())
+            .load::<Self>(&mut conn)?;
+
+        Ok(in_effect_for(keys, &res, |o| o.org_tier_id))
+    }
+
+    /// Retires the tier's current ownership (if any) and creates a new one for owner_id.
+    /// The previous ownership keeps its history with retired_at set to now.
+    pub fn transfer(org_tier_id: Uuid, owner_id: Uuid) -> Result<Self> {
+        let mut conn = connection()?;
+
+        let now = chrono::Utc::now().naive_utc();
+
+        let res = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            diesel::update(org_tier_ownerships::table)
+                .filter(org_tier_ownerships::org_tier_id.eq(org_tier_id))
+                .filter(org_tier_ownerships::retired_at.is_null())
+                .set((
+                    org_tier_ownerships::retired_at.eq(now),
+                    org_tier_ownerships::updated_at.eq(now),
+                ))
+                .execute(conn)?;
+
+            diesel::insert_into(org_tier_ownerships::table)
+                .values(&NewOrgOwnership::new(owner_id, org_tier_id))
+                .get_result(conn)
+        })?;
+
+        Ok(res)
+    }
+
     pub fn get_org_tier_ids_by_owner_id(id: &Uuid) -> Result<Vec<Uuid>> {
         let mut conn = connection()?;
 
@@ -1,16 +1,21 @@
+use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
 
 use chrono::{prelude::*};
 use serde::{Deserialize, Serialize};
-use diesel::{self, Insertable, Queryable, ExpressionMethods, BoolExpressionMethods, PgTextExpressionMethods};
+use diesel::{self, Connection, Insertable, Queryable, ExpressionMethods, BoolExpressionMethods, PgTextExpressionMethods};
 use diesel::{RunQueryDsl, QueryDsl};
 use uuid::Uuid;
 use async_graphql::*;
 
 use crate::database::connection;
 use crate::schema::*;
+use crate::graphql::{Keyed, Keyset, Loaders, PageRequest, keyset_page};
 
-use super::{Organization, Person, OrgOwnership, SkillDomain, Team};
+use super::{Organization, Person, SkillDomain, Team, AsOf};
+
+/// tier_level of tiers without a parent
+pub const TOP_TIER_LEVEL: i32 = 1;
 
 #[derive(Debug, Clone, Deserialize, Serialize, Queryable, Insertable, AsChangeset, SimpleObject)]
 #[graphql(complex)]
@@ -39,29 +44,51 @@ pub struct OrgTier {
 #[ComplexObject]
 impl OrgTier {
 
-    pub async fn organization(&self) -> Result<Organization> {
-        Organization::get_by_id(&self.organization_id)
+    pub async fn organization(&self, ctx: &Context<'_>) -> Result<Organization> {
+        ctx.data::<Loaders>()?.organization(self.organization_id).await
     }
 
-    pub async fn parent_organization_tier(&self) -> Result<Option<OrgTier>> {
+    pub async fn parent_organization_tier(&self, ctx: &Context<'_>) -> Result<Option<OrgTier>> {
         match self.parent_tier {
-            Some(id) => Ok(Some(OrgTier::get_by_id(&id)?)),
+            Some(id) => Ok(Some(ctx.data::<Loaders>()?.org_tier(id).await?)),
             None => Ok(None),
         }
     }
 
-    pub async fn child_organization_tier(&self) -> Result<Vec<OrgTier>> {
-        OrgTier::get_child_org_tiers(&self.id)
+    /// Returns child tiers, limited to those that existed on as_of if it is given
+    pub async fn child_organization_tier(&self, as_of: Option<NaiveDateTime>) -> Result<Vec<OrgTier>> {
+        let children = OrgTier::get_child_org_tiers(&self.id)?;
+
+        match as_of {
+            Some(date) => Ok(children.into_iter().filter(|t| t.in_effect_on(date)).collect()),
+            None => Ok(children),
+        }
     }
 
-    pub async fn owner(&self) -> Result<Person> {
-        let org_tier_ownership = OrgOwnership::get_by_org_tier_id(&self.id).unwrap();
+    /// Returns the current owner, or the owner on as_of if it is given
+    pub async fn owner(&self, ctx: &Context<'_>, as_of: Option<NaiveDateTime>) -> Result<Person> {
+        let loaders = ctx.data::<Loaders>()?;
 
-        Person::get_by_id(&org_tier_ownership.owner_id)
+        let owner = match as_of {
+            Some(date) => loaders.org_tier_owner_as_of(self.id, date).await?,
+            None => loaders.org_tier_owner(self.id).await?,
+        };
+
+        owner.ok_or_else(|| Error::new(format!("OrgTier {} has no owner on that date", self.id)))
     }
 
-    pub async fn teams(&self) -> Result<Vec<Team>> {
-        Team::get_by_org_tier_id(&self.id)
+    /// Returns the tier's teams, or the teams that existed on as_of if it is given
+    pub async fn teams(&self, as_of: Option<NaiveDateTime>) -> Result<Vec<Team>> {
+        match as_of {
+            Some(date) => Team::get_by_org_tier_id_as_of(&self.id, date),
+            None => Team::get_by_org_tier_id(&self.id),
+        }
+    }
+}
+
+impl Keyed for OrgTier {
+    fn keyset(&self) -> Keyset {
+        Keyset { created_at: self.created_at, id: self.id }
     }
 }
 
@@ -107,6 +134,22 @@ impl OrgTier {
         Ok(res)
     }
 
+    /// Returns a page of org tiers ordered by (created_at, id) for cursor pagination
+    pub fn get_page(page: &PageRequest) -> Result<Vec<Self>> {
+        keyset_page!(org_tiers, page)
+    }
+
+    /// Returns the number of org tiers in the system
+    pub fn count() -> Result<i64> {
+        let mut conn = connection()?;
+
+        let res = org_tiers::table
+            .count()
+            .get_result(&mut conn)?;
+
+        Ok(res)
+    }
+
     pub fn get_count(count: i64) -> Result<Vec<OrgTier>> {
         let mut conn = connection()?;
         let res = org_tiers::table
@@ -158,6 +201,31 @@ impl OrgTier {
         Ok(res)
     }
 
+    /// Returns the org tiers of an organization that haven't been retired
+    pub fn get_active_by_org_id(id: &Uuid) -> Result<Vec<OrgTier>> {
+        let mut conn = connection()?;
+
+        let res = org_tiers::table
+            .filter(org_tiers::organization_id.eq(id))
+            .filter(org_tiers::retired_at.is_null())
+            .load::<OrgTier>(&mut conn)?;
+
+        Ok(res)
+    }
+
+    /// Returns the top org tiers of an organization that haven't been retired
+    pub fn get_active_top_by_org_id(id: &Uuid) -> Result<Vec<OrgTier>> {
+        let mut conn = connection()?;
+
+        let res = org_tiers::table
+            .filter(org_tiers::organization_id.eq(id))
+            .filter(org_tiers::parent_tier.is_null())
+            .filter(org_tiers::retired_at.is_null())
+            .load::<OrgTier>(&mut conn)?;
+
+        Ok(res)
+    }
+
     pub fn get_child_org_tiers(id: &Uuid) -> Result<Vec<OrgTier>> {
         let mut conn = connection()?;
 
@@ -177,6 +245,133 @@ impl OrgTier {
         Ok(org_tier_ownership)
     }
     
+    /// Returns the tier_level a new tier would have under parent_id in organization_id
+    pub fn level_under(organization_id: &Uuid, parent_id: Option<Uuid>) -> Result<i32> {
+        match parent_id {
+            Some(id) => {
+                let parent = OrgTier::get_by_id(&id)?;
+
+                if &parent.organization_id != organization_id {
+                    return Err(Error::new("Parent tier must belong to the same organization"));
+                }
+
+                if parent.retired_at.is_some() {
+                    return Err(Error::new("Cannot add a tier under a retired tier"));
+                }
+
+                Ok(parent.tier_level + 1)
+            },
+            None => Ok(TOP_TIER_LEVEL),
+        }
+    }
+
+    /// Re-parents a tier under parent_id, or makes it a top tier if parent_id is None.
+    /// Rejects moves under a retired tier or that would make the tier its own ancestor,
+    /// and shifts the tier_level of the tier and all of its descendants to match the new position.
+    /// The organization's tiers are locked while the move is checked and made, so concurrent
+    /// moves can't combine into a cycle.
+    pub fn move_to(id: Uuid, parent_id: Option<Uuid>) -> Result<OrgTier> {
+        let tier = OrgTier::get_by_id(&id)?;
+
+        let now = chrono::Utc::now().naive_utc();
+
+        let mut conn = connection()?;
+
+        let res = conn.transaction::<_, Error, _>(|conn| {
+            let tiers: HashMap<Uuid, OrgTier> = org_tiers::table
+                .filter(org_tiers::organization_id.eq(tier.organization_id))
+                .for_update()
+                .load::<OrgTier>(conn)?
+                .into_iter()
+                .map(|t| (t.id, t))
+                .collect();
+
+            let (new_level, descendants) = OrgTier::plan_move(&tiers, id, parent_id)?;
+
+            let delta = new_level - tiers[&id].tier_level;
+
+            diesel::update(org_tiers::table)
+                .filter(org_tiers::id.eq(id))
+                .set((
+                    org_tiers::parent_tier.eq(parent_id),
+                    org_tiers::tier_level.eq(new_level),
+                    org_tiers::updated_at.eq(now),
+                ))
+                .execute(conn)?;
+
+            if delta != 0 && !descendants.is_empty() {
+                diesel::update(org_tiers::table)
+                    .filter(org_tiers::id.eq_any(&descendants))
+                    .set((
+                        org_tiers::tier_level.eq(org_tiers::tier_level + delta),
+                        org_tiers::updated_at.eq(now),
+                    ))
+                    .execute(conn)?;
+            }
+
+            let moved = org_tiers::table
+                .filter(org_tiers::id.eq(id))
+                .first(conn)?;
+
+            Ok(moved)
+        })?;
+
+        Ok(res)
+    }
+
+    /// Checks a move against the organization's tiers and returns the tier's new level
+    /// and the ids of its descendants
+    fn plan_move(tiers: &HashMap<Uuid, OrgTier>, id: Uuid, parent_id: Option<Uuid>) -> Result<(i32, Vec<Uuid>)> {
+        if !tiers.contains_key(&id) {
+            return Err(Error::new(format!("OrgTier {} not found", id)));
+        }
+
+        let new_level = match parent_id {
+            Some(pid) => {
+                let parent = tiers.get(&pid)
+                    .ok_or_else(|| Error::new("Parent tier must belong to the same organization"))?;
+
+                if parent.retired_at.is_some() {
+                    return Err(Error::new("Cannot move a tier under a retired tier"));
+                }
+
+                // Walk up from the new parent. Reaching the tier means it would become its own ancestor
+                let mut seen = HashSet::new();
+                let mut cursor = Some(pid);
+
+                while let Some(current) = cursor {
+                    if current == id {
+                        return Err(Error::new("Cannot move a tier under itself or one of its descendants"));
+                    }
+                    if !seen.insert(current) {
+                        return Err(Error::new("Existing org tier hierarchy contains a cycle"));
+                    }
+                    cursor = tiers.get(&current).and_then(|t| t.parent_tier);
+                }
+
+                parent.tier_level + 1
+            },
+            None => TOP_TIER_LEVEL,
+        };
+
+        let mut children: HashMap<Uuid, Vec<Uuid>> = HashMap::new();
+        for t in tiers.values() {
+            if let Some(parent) = t.parent_tier {
+                children.entry(parent).or_default().push(t.id);
+            }
+        }
+
+        let mut descendants = Vec::new();
+        let mut stack = children.get(&id).cloned().unwrap_or_default();
+
+        while let Some(current) = stack.pop() {
+            descendants.push(current);
+            stack.extend(children.get(&current).cloned().unwrap_or_default());
+        }
+
+        Ok((new_level, descendants))
+    }
+
     pub fn update(&self) -> Result<OrgTier> {
         let mut conn = connection()?;
 
@@ -220,3 +415,90 @@ impl NewOrgTier {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tiers named by (id, parent) with levels following the parent links
+    fn tiers(links: &[(u128, Option<u128>)]) -> HashMap<Uuid, OrgTier> {
+        let mut res: HashMap<Uuid, OrgTier> = HashMap::new();
+
+        for (id, parent) in links {
+            let tier_level = parent
+                .and_then(|p| res.get(&Uuid::from_u128(p)))
+                .map(|p| p.tier_level + 1)
+                .unwrap_or(TOP_TIER_LEVEL);
+
+            res.insert(Uuid::from_u128(*id), OrgTier {
+                id: Uuid::from_u128(*id),
+                organization_id: Uuid::nil(),
+                tier_level,
+                name_en: id.to_string(),
+                name_fr: id.to_string(),
+                primary_domain: SkillDomain::Combat,
+                parent_tier: parent.map(Uuid::from_u128),
+                created_at: NaiveDateTime::default(),
+                updated_at: NaiveDateTime::default(),
+                retired_at: None,
+            });
+        }
+
+        res
+    }
+
+    fn id(n: u128) -> Uuid {
+        Uuid::from_u128(n)
+    }
+
+    #[test]
+    fn moves_a_tier_and_its_descendants() {
+        // 1 -> 2 -> 3 -> 4 and 1 -> 5
+        let tiers = tiers(&[(1, None), (2, Some(1)), (3, Some(2)), (4, Some(3)), (5, Some(1))]);
+
+        let (level, mut descendants) = OrgTier::plan_move(&tiers, id(3), Some(id(5))).unwrap();
+        descendants.sort();
+
+        assert_eq!(level, 3);
+        assert_eq!(descendants, vec![id(4)]);
+    }
+
+    #[test]
+    fn moves_a_tier_to_the_top() {
+        let tiers = tiers(&[(1, None), (2, Some(1))]);
+
+        assert_eq!(OrgTier::plan_move(&tiers, id(2), None).unwrap(), (TOP_TIER_LEVEL, Vec::new()));
+    }
+
+    #[test]
+    fn refuses_moves_under_itself_or_a_descendant() {
+        let tiers = tiers(&[(1, None), (2, Some(1)), (3, Some(2))]);
+
+        assert!(OrgTier::plan_move(&tiers, id(2), Some(id(2))).is_err());
+        assert!(OrgTier::plan_move(&tiers, id(1), Some(id(3))).is_err());
+    }
+
+    #[test]
+    fn refuses_a_retired_parent() {
+        let mut tiers = tiers(&[(1, None), (2, Some(1)), (3, None)]);
+        tiers.get_mut(&id(3)).unwrap().retired_at = Some(NaiveDateTime::default());
+
+        assert!(OrgTier::plan_move(&tiers, id(2), Some(id(3))).is_err());
+    }
+
+    #[test]
+    fn refuses_a_parent_in_another_organization() {
+        let tiers = tiers(&[(1, None), (2, Some(1))]);
+
+        assert!(OrgTier::plan_move(&tiers, id(2), Some(id(9))).is_err());
+    }
+
+    #[test]
+    fn refuses_to_walk_an_existing_cycle() {
+        // 2 and 3 are each other's parent
+        let mut tiers = tiers(&[(1, None), (2, None), (3, Some(2))]);
+        tiers.get_mut(&id(2)).unwrap().parent_tier = Some(id(3));
+
+        assert!(OrgTier::plan_move(&tiers, id(1), Some(id(2))).is_err());
+    }
+}
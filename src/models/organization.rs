@@ -15,6 +15,7 @@ use crate::database::connection;
 use crate::schema::*;
 
 use crate::models::{CapabilityCount, CapabilityLevel, Affiliation, SkillDomain, Publication};
+use crate::graphql::{Keyed, Keyset, PageRequest, keyset_page};
 
 use super::OrgTier;
 
@@ -60,6 +61,7 @@ impl Organization {
 
         let res: Vec<(String, SkillDomain, Option<CapabilityLevel>, i64)> = capabilities::table
             .filter(capabilities::organization_id.eq(self.id))
+            .filter(capabilities::validated_level.is_not_null())
             .group_by((capabilities::domain, capabilities::validated_level, capabilities::name_en))
             .select((capabilities::name_en, capabilities::domain, capabilities::validated_level, count(capabilities::id)))
             .order_by((capabilities::name_en, capabilities::validated_level))
@@ -73,7 +75,13 @@ impl Organization {
         counts.push(count);
     }
 
-    Ok(counts)
+    Ok(CapabilityCount::protected(counts))
+    }
+}
+
+impl Keyed for Organization {
+    fn keyset(&self) -> Keyset {
+        Keyset { created_at: self.created_at, id: self.id }
     }
 }
 
@@ -97,6 +105,16 @@ impl Organization {
         Ok(res)
     }
 
+    pub fn get_by_ids(ids: &[Uuid]) -> Result<Vec<Organization>> {
+        let mut conn = connection()?;
+
+        let res = organizations::table
+            .filter(organizations::id.eq_any(ids))
+            .load::<Organization>(&mut conn)?;
+
+        Ok(res)
+    }
+
     pub fn get_all() -> Result<Vec<Organization>> {
         let mut conn = connection()?;
 
@@ -106,6 +124,22 @@ impl Organization {
         Ok(res)
     }
 
+    /// Returns a page of organizations ordered by (created_at, id) for cursor pagination
+    pub fn get_page(page: &PageRequest) -> Result<Vec<Self>> {
+        keyset_page!(organizations, page)
+    }
+
+    /// Returns the number of organizations in the system
+    pub fn count() -> Result<i64> {
+        let mut conn = connection()?;
+
+        let res = organizations::table
+            .count()
+            .get_result(&mut conn)?;
+
+        Ok(res)
+    }
+
     pub fn get_count(count: i64) -> Result<Vec<Organization>> {
         let mut conn = connection()?;
 
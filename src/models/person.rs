@@ -1,9 +1,8 @@
-use std::collections::HashMap;
 use std::fmt::Debug;
 
 use chrono::{prelude::*};
 use serde::{Deserialize, Serialize};
-use diesel::{self, Insertable, Queryable, ExpressionMethods, PgTextExpressionMethods, BoolExpressionMethods};
+use diesel::{self, Insertable, Queryable, ExpressionMethods, PgTextExpressionMethods, BoolExpressionMethods, OptionalExtension};
 use diesel::{RunQueryDsl, QueryDsl};
 use uuid::Uuid;
 use async_graphql::*;
@@ -11,30 +10,35 @@ use async_graphql::*;
 use crate::models::{Organization};
 
 use crate::common_utils::{
-    is_analyst, RoleGuard, UserRole, is_admin};
+    is_analyst, RoleGuard, UserRole, is_admin, SelfOrRoleGuard, is_signed_in, PersonalDataGuard};
 
 use crate::database::connection;
 use crate::schema::*;
 
 use crate::models::{Role, TeamOwnership, Team, OrgTier, OrgOwnership, Capability, Affiliation, LanguageData, 
     Publication};
+use crate::graphql::{Keyed, Keyset, Loaders, PageRequest, identifying_field, keyset_page, person_id_field, person_record_type};
 
-use super::{Validation, Requirement};
+use super::{Validation, LevelSource, MatchResult, match_roles_for_person, Deployment, DeploymentMetrics,
+    EmployeeData, HrStatePeriod, ContactData};
+use crate::config_variables::DEPLOYMENT_WINDOW_MONTHS;
 
 #[derive(Debug, Clone, Deserialize, Serialize, Queryable, Identifiable, Insertable, AsChangeset, SimpleObject)]
-#[graphql(complex)]
+#[graphql(complex, directive = person_record_type::apply())]
 #[diesel(table_name = persons)]
 #[diesel(belongs_to(Organization))]
 /// Represents a person working in an organization
 /// Referenced by Team
 /// Referenced by ReportingRelationship
-/// Will break out address and contact info soon
+/// Contact details are held in ContactData
 pub struct Person {
+    #[graphql(directive = person_id_field::apply())]
     pub id: Uuid,
 
     #[graphql(
         guard = "RoleGuard::new(UserRole::Admin)",
         visible = "is_admin",
+        directive = person_id_field::apply(),
     )]
     pub user_id: Uuid,
     #[graphql(skip)]
@@ -42,21 +46,14 @@ pub struct Person {
     #[graphql(skip)]
     pub given_name: String,
 
-    // contact info - this will be another module - just here for expediency
-    pub email: String,
-    pub phone: String,
-    pub work_address: String,
-    pub city: String,
-    pub province: String,
-    pub postal_code: String,
-    pub country: String,
-
     pub organization_id: Uuid, // Organization 
     #[graphql(
-        guard = "RoleGuard::new(UserRole::Admin)",
+        guard = "RoleGuard::new(UserRole::Admin).and(PersonalDataGuard::new(self.id, self.user_id))",
         visible = "is_admin",
+        directive = identifying_field::apply(),
     )]
     pub peoplesoft_id: String,
+    #[graphql(directive = identifying_field::apply())]
     pub orcid_id: String,
 
     pub created_at: NaiveDateTime,
@@ -64,9 +61,19 @@ pub struct Person {
     pub retired_at: Option<NaiveDateTime>,
 }
 
+impl Keyed for Person {
+    fn keyset(&self) -> Keyset {
+        Keyset { created_at: self.created_at, id: self.id }
+    }
+}
 
 // Non Graphql
 impl Person {
+    /// Given then family name, as the person is named in org charts and reports
+    pub fn full_name(&self) -> String {
+        format!("{} {}", self.given_name, self.family_name)
+    }
+
     pub fn create(person: &NewPerson) -> Result<Person> {
         let mut conn = connection()?;
         let res = diesel::insert_into(persons::table)
@@ -115,6 +122,18 @@ impl Person {
         Ok(res)
     }
 
+    /// Returns the person record of a user, if they have one
+    pub fn get_by_user_id(user_id: &Uuid) -> Result<Option<Person>> {
+        let mut conn = connection()?;
+
+        let res = persons::table
+            .filter(persons::user_id.eq(user_id))
+            .first(&mut conn)
+            .optional()?;
+
+        Ok(res)
+    }
+
     pub fn get_by_ids(ids: &Vec<Uuid>) -> Result<Vec<Person>> {
         let mut conn = connection()?;
 
@@ -134,6 +153,11 @@ impl Person {
         Ok(res)
     }
 
+    /// Returns a page of persons ordered by (created_at, id) for cursor pagination
+    pub fn get_page(page: &PageRequest) -> Result<Vec<Self>> {
+        keyset_page!(persons, page)
+    }
+
     pub fn get_all_ids() -> Result<Vec<Uuid>> {
         let mut conn = connection()?;
 
@@ -192,39 +216,69 @@ impl Person {
 impl Person {
 
     #[graphql(
-        guard = "RoleGuard::new(UserRole::Analyst)",
+        guard = "RoleGuard::new(UserRole::Analyst).and(PersonalDataGuard::new(self.id, self.user_id))",
         visible = "is_analyst",
+        directive = identifying_field::apply(),
     )]
     pub async fn internal_peoplesoft_id(&self) -> Result<String> {
         Ok(self.peoplesoft_id.to_owned())
     }
 
-    /*
     #[graphql(
-        guard = "RoleGuard::new(UserRole::Analyst)",
-        visible = "is_analyst",
+        guard = "SelfOrRoleGuard::new(self.user_id, UserRole::Analyst).and(PersonalDataGuard::new(self.id, self.user_id))",
+        visible = "is_signed_in",
+        directive = identifying_field::apply(),
     )]
-     */
-    /// Returns the person's family or second name
+    /// Returns the person's family or second name. Only available to the person and analysts and above.
     pub async fn family_name(&self) -> Result<String> {
         Ok(self.family_name.to_owned())
     }
     
-    /*
     #[graphql(
-        guard = "RoleGuard::new(UserRole::Analyst)",
-        visible = "is_analyst",
+        guard = "SelfOrRoleGuard::new(self.user_id, UserRole::Analyst).and(PersonalDataGuard::new(self.id, self.user_id))",
+        visible = "is_signed_in",
+        directive = identifying_field::apply(),
     )]
-     */
-    /// Returns the persons given or first name
+    /// Returns the persons given or first name. Only available to the person and analysts and above.
     pub async fn given_name(&self) -> Result<String> {
         Ok(self.given_name.to_owned())
     }
 
+    #[graphql(
+        guard = "SelfOrRoleGuard::new(self.user_id, UserRole::Analyst).and(PersonalDataGuard::new(self.id, self.user_id))",
+        visible = "is_signed_in",
+        directive = identifying_field::apply(),
+    )]
+    /// Returns the person's contacts, primary first then most recent. Set current to only return
+    /// those in effect today. Only available to the person and analysts and above, as for names.
+    pub async fn contacts(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default)] current: bool,
+    ) -> Result<Vec<ContactData>> {
+        let contacts = ctx.data::<Loaders>()?.contacts_by_person(self.id).await?;
+
+        let now = chrono::Utc::now().naive_utc();
+
+        Ok(contacts.into_iter().filter(|c| !current || c.in_effect_on(now)).collect())
+    }
+
+    #[graphql(
+        guard = "SelfOrRoleGuard::new(self.user_id, UserRole::Analyst).and(PersonalDataGuard::new(self.id, self.user_id))",
+        visible = "is_signed_in",
+        directive = identifying_field::apply(),
+    )]
+    /// Returns the person's current primary contact, if they have one.
+    /// Only available to the person and analysts and above.
+    pub async fn primary_contact(&self, ctx: &Context<'_>) -> Result<Option<ContactData>> {
+        let contacts = ctx.data::<Loaders>()?.contacts_by_person(self.id).await?;
+
+        Ok(contacts.into_iter().find(|c| c.is_primary && c.end_date.is_none()))
+    }
+
     /// Returns the person's organization
-    pub async fn organization(&self) -> Result<Organization> {
-        
-        Organization::get_by_id(&self.organization_id)
+    pub async fn organization(&self, ctx: &Context<'_>) -> Result<Organization> {
+        ctx.data::<Loaders>()?.organization(self.organization_id).await
     }
 
     /*
@@ -234,13 +288,22 @@ impl Person {
     )]
      */
     /// Returns active or inactive roles depending on the active boolean of true or false
-    pub async fn inactive_roles(&self) -> Result<Vec<Role>> {
-        Role::get_by_person_id(self.id, false)
+    pub async fn inactive_roles(&self, ctx: &Context<'_>) -> Result<Vec<Role>> {
+        ctx.data::<Loaders>()?.roles_by_person(self.id, false).await
     }
 
-    /// Returns active role
-    pub async fn active_roles(&self) -> Result<Vec<Role>> {
-        Role::get_by_person_id(self.id, true)
+    /// Returns active roles, or the roles the person held on as_of if it is given
+    pub async fn active_roles(
+        &self,
+        ctx: &Context<'_>,
+        as_of: Option<NaiveDateTime>,
+    ) -> Result<Vec<Role>> {
+        let loaders = ctx.data::<Loaders>()?;
+
+        match as_of {
+            Some(date) => loaders.roles_by_person_as_of(self.id, date).await,
+            None => loaders.roles_by_person(self.id, true).await,
+        }
     }
 
     /// Returns person's affiliations with other organizations
@@ -269,8 +332,8 @@ impl Person {
     )]
      */
     /// Returns the persons capabilities
-    pub async fn capabilities(&self) -> Result<Vec<Capability>> {
-        Capability::get_by_person_id(self.id)
+    pub async fn capabilities(&self, ctx: &Context<'_>) -> Result<Vec<Capability>> {
+        ctx.data::<Loaders>()?.capabilities_by_person(self.id).await
     }
 
     #[graphql(
@@ -287,16 +350,76 @@ impl Person {
     }
 
     #[graphql(
-        guard = "RoleGuard::new(UserRole::Analyst)",
+        guard = "RoleGuard::new(UserRole::Analyst).and(PersonalDataGuard::new(self.id, self.user_id))",
         visible = "is_analyst",
+        directive = identifying_field::apply(),
     )]
     /// Returns a vector of the language results for the person
     pub async fn language_data(&self) -> Result<Vec<LanguageData>> {
         LanguageData::get_by_person_id(self.id)
     }
 
-    pub async fn find_matches(&self) -> Result<Vec<Role>> {
-        find_roles_by_requirements_met(self)
+    #[graphql(
+        guard = "RoleGuard::new(UserRole::Analyst)",
+        visible = "is_analyst",
+    )]
+    /// Returns the person's deployments, most recent first
+    pub async fn deployments(&self, ctx: &Context<'_>) -> Result<Vec<Deployment>> {
+        ctx.data::<Loaders>()?.deployments_by_person(self.id).await
+    }
+
+    #[graphql(
+        guard = "RoleGuard::new(UserRole::Analyst)",
+        visible = "is_analyst",
+    )]
+    /// Returns the person's substantive and acting classifications, most recent first.
+    /// Set current to only return those in effect today.
+    pub async fn employee_data(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default)] current: bool,
+    ) -> Result<Vec<EmployeeData>> {
+        let employee_data = ctx.data::<Loaders>()?.employee_data_by_person(self.id).await?;
+
+        let now = chrono::Utc::now().naive_utc();
+
+        Ok(employee_data.into_iter().filter(|e| !current || e.in_effect_on(now)).collect())
+    }
+
+    #[graphql(
+        guard = "RoleGuard::new(UserRole::Analyst)",
+        visible = "is_analyst",
+    )]
+    /// Returns the person's current HR state, if one has been recorded
+    pub async fn hr_state(&self, ctx: &Context<'_>) -> Result<Option<HrStatePeriod>> {
+        let periods = ctx.data::<Loaders>()?.hr_states_by_person(self.id).await?;
+
+        Ok(periods.into_iter().find(|p| p.end_date.is_none()))
+    }
+
+    #[graphql(
+        guard = "RoleGuard::new(UserRole::Analyst)",
+        visible = "is_analyst",
+    )]
+    /// Returns deployment counts and days over the last `months` months, used to track burnout
+    pub async fn deployment_metrics(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default_with = "DEPLOYMENT_WINDOW_MONTHS")] months: i32,
+    ) -> Result<DeploymentMetrics> {
+        let deployments = ctx.data::<Loaders>()?.deployments_by_person(self.id).await?;
+
+        Ok(DeploymentMetrics::from_deployments(&deployments, months, chrono::Utc::now().naive_utc()))
+    }
+
+    /// Returns active, vacant roles ranked by how well this person meets their requirements
+    pub async fn find_matches(
+        &self,
+        #[graphql(default)] source: LevelSource,
+        #[graphql(default)] min_score: f64,
+        #[graphql(default = 25)] limit: i32,
+    ) -> Result<Vec<MatchResult<Role>>> {
+        match_roles_for_person(self.id, source, min_score, limit.max(0) as usize)
     }
 }
 
@@ -307,14 +430,6 @@ pub struct NewPerson {
     pub user_id: Uuid,
     pub family_name: String,
     pub given_name: String,
-    // contact info - this will be another module - just here for expediency
-    pub email: String,
-    pub phone: String,
-    pub work_address: String,
-    pub city: String,
-    pub province: String,
-    pub postal_code: String,
-    pub country: String,
     pub organization_id: Uuid, // Organization
     pub peoplesoft_id: String,
     pub orcid_id: String,
@@ -326,13 +441,6 @@ impl NewPerson {
         user_id: Uuid,
         family_name: String,
         given_name: String,
-        email: String,
-        phone: String,
-        work_address: String,
-        city: String,
-        province: String,
-        postal_code: String,
-        country: String,
         organization_id: Uuid, // Organization
         peoplesoft_id: String,
         orcid_id: String,
@@ -341,49 +449,9 @@ impl NewPerson {
             user_id,
             family_name,
             given_name,
-            email,
-            phone,
-            work_address,
-            city,
-            province,
-            postal_code,
-            country,
             organization_id,
             peoplesoft_id,
             orcid_id,
         }
     }
 }
-
-pub fn find_roles_by_requirements_met(person: &Person) -> Result<Vec<Role>> {
-
-    let capabilities = Capability::get_by_person_id(person.id)?;
-
-    let mut role_ids: Vec<Uuid> = Vec::new();
-
-    for cap in capabilities {
-
-        let reqs = Requirement::get_by_skill_id_and_level(cap.skill_id, cap.validated_level.unwrap())?;
-
-        for r in reqs {
-            role_ids.push(r.role_id);
-        };
-    }
-
-    let id_counts: HashMap<Uuid, i32> =
-        role_ids.iter()
-            .fold(HashMap::new(), |mut map, id| {
-                *map.entry(*id).or_insert(0) += 1;
-                map
-            });
-
-    let mut validated_ids: Vec<Uuid> = Vec::new();
-
-    for (k, v) in id_counts {
-        if v >= 3 {
-            validated_ids.push(k);
-        }
-    };
-
-    Role::get_active_vacant_by_ids(&validated_ids)
-}
\ No newline at end of file
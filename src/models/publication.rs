@@ -12,6 +12,7 @@ use crate::schema::*;
 use crate::database::connection;
 
 use crate::models::{Person, PublicationContributor, Organization};
+use crate::graphql::{Keyed, Keyset, Loaders, PageRequest, keyset_page};
 
 #[derive(Debug, Clone, Deserialize, Serialize, Queryable, Insertable, Identifiable, AsChangeset, SimpleObject)]
 #[graphql(complex)]
@@ -47,12 +48,12 @@ pub enum PublicationStatus {
 
 #[ComplexObject]
 impl Publication {
-    pub async fn lead_author(&self) -> Result<Person> {
-        Person::get_by_id(&self.lead_author_id)
+    pub async fn lead_author(&self, ctx: &Context<'_>) -> Result<Person> {
+        ctx.data::<Loaders>()?.person(self.lead_author_id).await
     }
 
-    pub async fn publishing_organization(&self) -> Result<Organization> {
-        Organization::get_by_id(&self.publishing_organization_id)
+    pub async fn publishing_organization(&self, ctx: &Context<'_>) -> Result<Organization> {
+        ctx.data::<Loaders>()?.organization(self.publishing_organization_id).await
     }
 
     pub async fn contributors(&self) -> Result<Vec<Person>> {
@@ -63,6 +64,12 @@ impl Publication {
     }
 }
 
+impl Keyed for Publication {
+    fn keyset(&self) -> Keyset {
+        Keyset { created_at: self.created_at, id: self.id }
+    }
+}
+
 // Non Graphql
 impl Publication {
     pub fn create(publication: &NewPublication) -> Result<Publication> {
@@ -105,6 +112,22 @@ impl Publication {
         Ok(res)
     }
 
+    /// Returns a page of publications ordered by (created_at, id) for cursor pagination
+    pub fn get_page(page: &PageRequest) -> Result<Vec<Self>> {
+        keyset_page!(publications, page)
+    }
+
+    /// Returns the number of publications in the system
+    pub fn count() -> Result<i64> {
+        let mut conn = connection()?;
+
+        let res = publications::table
+            .count()
+            .get_result(&mut conn)?;
+
+        Ok(res)
+    }
+
     pub fn get_count(count: i64) -> Result<Vec<Self>> {
         let mut conn = connection()?;
         let res = publications::table
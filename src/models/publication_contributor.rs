@@ -11,6 +11,7 @@ use crate::schema::*;
 use crate::database::connection;
 
 use super::{Publication, Person};
+use crate::graphql::Loaders;
 
 #[derive(Debug, Clone, Deserialize, Serialize, Queryable, Insertable, AsChangeset)]
 #[table_name = "publication_contributors"]
@@ -30,8 +31,8 @@ impl PublicationContributor {
         Publication::get_by_id(&self.publication_id)
     }
 
-    pub async fn contributor(&self) -> Result<Person> {
-        Person::get_by_id(&self.contributor_id)
+    pub async fn contributor(&self, ctx: &Context<'_>) -> Result<Person> {
+        ctx.data::<Loaders>()?.person(self.contributor_id).await
     }
 
     pub async fn contributor_role(&self) -> Result<String> {
@@ -13,7 +13,8 @@ use crate::{database::connection};
 
 use crate::{schema::*, database};
 
-use crate::models::{Role, Skill, CapabilityLevel, SkillDomain};
+use crate::models::{Role, Skill, CapabilityLevel, SkillDomain, Cell, protect_cells};
+use crate::graphql::Loaders;
 
 #[derive(Debug, Clone, Deserialize, Serialize, Queryable, Identifiable, Insertable, AsChangeset, SimpleObject, Associations)]
 #[diesel(belongs_to(Role))]
@@ -43,16 +44,16 @@ pub struct Requirement {
 // Graphql
 #[ComplexObject]
 impl Requirement {
-    pub async fn role(&self) -> Result<Role> {
-        Role::get_by_id(&self.role_id)
+    pub async fn role(&self, ctx: &Context<'_>) -> Result<Role> {
+        ctx.data::<Loaders>()?.role(self.role_id).await
     }
 
-    pub async fn skill_name(&self) -> Result<String> {
-        Skill::get_name_by_id(&self.skill_id)
+    pub async fn skill_name(&self, ctx: &Context<'_>) -> Result<String> {
+        Ok(ctx.data::<Loaders>()?.skill(self.skill_id).await?.name_en)
     }
 
-    pub async fn skill(&self) -> Result<Skill> {
-        Skill::get_by_id(&self.skill_id)
+    pub async fn skill(&self, ctx: &Context<'_>) -> Result<Skill> {
+        ctx.data::<Loaders>()?.skill(self.skill_id).await
     }
 }
 
@@ -182,6 +183,39 @@ impl Requirement {
         Ok(res)
     }
 
+    pub fn get_by_skill_ids(ids: &[Uuid]) -> Result<Vec<Self>> {
+        let mut conn = connection()?;
+
+        let res = requirements::table
+            .filter(requirements::skill_id.eq_any(ids))
+            .filter(requirements::retired_at.is_null())
+            .load::<Requirement>(&mut conn)?;
+
+        Ok(res)
+    }
+
+    pub fn get_by_role_ids(ids: &[Uuid]) -> Result<Vec<Self>> {
+        let mut conn = connection()?;
+
+        let res = requirements::table
+            .filter(requirements::role_id.eq_any(ids))
+            .load::<Requirement>(&mut conn)?;
+
+        Ok(res)
+    }
+
+    /// Returns the requirements of a set of roles that haven't been retired
+    pub fn get_active_by_role_ids(ids: &[Uuid]) -> Result<Vec<Self>> {
+        let mut conn = connection()?;
+
+        let res = requirements::table
+            .filter(requirements::role_id.eq_any(ids))
+            .filter(requirements::retired_at.is_null())
+            .load::<Requirement>(&mut conn)?;
+
+        Ok(res)
+    }
+
     pub fn get_level_counts_by_name(name: String) -> Result<Vec<RequirementCount>> {
         let mut conn = connection()?;
 
@@ -202,7 +236,7 @@ impl Requirement {
             counts.push(count);
         }
 
-        Ok(counts)
+        Ok(RequirementCount::protected(counts))
     }
 
     pub fn get_level_counts_by_domain(domain: SkillDomain) -> Result<Vec<RequirementCount>> {
@@ -223,7 +257,7 @@ impl Requirement {
             counts.push(count);
         }
 
-        Ok(counts)
+        Ok(RequirementCount::protected(counts))
     }
     
     /// Updates a Requirement based on changed data
@@ -277,7 +311,9 @@ pub struct RequirementCount {
     pub name: String,
     pub domain: SkillDomain,
     pub level: CapabilityLevel,
-    pub counts: i64,
+    /// Null when suppressed to protect small groups
+    pub counts: Option<i64>,
+    pub suppressed: bool,
 }
 
 impl From<(String, SkillDomain, CapabilityLevel, i64)> for RequirementCount {
@@ -286,7 +322,8 @@ impl From<(String, SkillDomain, CapabilityLevel, i64)> for RequirementCount {
             name,
             domain,
             level,
-            counts,
+            counts: Some(counts),
+            suppressed: false,
         }
     }
 }
@@ -297,7 +334,26 @@ impl RequirementCount {
             name,
             domain,
             level,
-            counts,
+            counts: Some(counts),
+            suppressed: false,
         }
     }
+
+    /// Suppresses small counts, and counts they could be worked out from, across the
+    /// results of one query by requirement name and level. See protect_cells.
+    pub fn protected(mut counts: Vec<Self>) -> Vec<Self> {
+        let levels: Vec<String> = counts.iter().map(|c| c.level.to_string()).collect();
+
+        let published = protect_cells(&counts.iter()
+            .zip(levels.iter())
+            .map(|(c, level)| Cell { row: &c.name, column: level, count: c.counts.unwrap_or(0) })
+            .collect::<Vec<Cell>>());
+
+        for (count, p) in counts.iter_mut().zip(published) {
+            count.suppressed = p.is_none();
+            count.counts = p;
+        }
+
+        counts
+    }
 }
@@ -1,4 +1,4 @@
-use std::{fmt::Debug, collections::HashMap};
+use std::fmt::Debug;
 
 use chrono::{prelude::*};
 use diesel_derive_enum::DbEnum;
@@ -13,8 +13,11 @@ use crate::config_variables::DATE_FORMAT;
 
 use crate::schema::*;
 use crate::database::connection;
+use crate::graphql::{Keyed, Keyset, Loaders, PageRequest, keyset_page};
 
-use super::{Person, Team, Work, Requirement, Capability};
+use std::collections::HashMap;
+
+use super::{AsOf, AsOfKey, Audited, ChangeRecord, Person, Team, Work, Requirement, LevelSource, MatchResult, match_people_for_role};
 
 #[derive(Debug, Clone, Deserialize, Serialize, Queryable, Insertable, AsChangeset)]
 #[diesel(table_name = roles)]
@@ -47,16 +50,16 @@ impl Role {
         self.id
     }
 
-    pub async fn person(&self) -> Option<Person> {
+    pub async fn person(&self, ctx: &Context<'_>) -> Result<Option<Person>> {
 
         match self.person_id {
-            Some(p) => Some(Person::get_by_id(&p).unwrap()),
-            None => None
+            Some(p) => Ok(Some(ctx.data::<Loaders>()?.person(p).await?)),
+            None => Ok(None)
         }
     }
 
-    pub async fn team(&self) -> Result<Team> {
-        Team::get_by_id(&self.team_id)
+    pub async fn team(&self, ctx: &Context<'_>) -> Result<Team> {
+        ctx.data::<Loaders>()?.team(self.team_id).await
     }
 
     pub async fn title_english(&self) -> Result<String> {
@@ -86,8 +89,8 @@ impl Role {
         }
     }
 
-    pub async fn requirements(&self) -> Result<Vec<Requirement>> {
-        Requirement::get_by_role_id(self.id)
+    pub async fn requirements(&self, ctx: &Context<'_>) -> Result<Vec<Requirement>> {
+        ctx.data::<Loaders>()?.requirements_by_role(self.id).await
     }
 
     pub async fn hr_group(&self) -> Result<String> {
@@ -117,14 +120,22 @@ impl Role {
         Ok(self.updated_at.format(DATE_FORMAT).to_string())
     }
 
-    pub async fn find_matches(&self) -> Result<Vec<Person>> {
-
-        let requirements = Requirement::get_by_role_id(self.id)?;
-
-        find_people_by_requirements_met(requirements)
+    /// Returns people ranked by how well they meet this role's requirements
+    pub async fn find_matches(
+        &self,
+        #[graphql(default)] source: LevelSource,
+        #[graphql(default)] min_score: f64,
+        #[graphql(default = 25)] limit: i32,
+    ) -> Result<Vec<MatchResult<Person>>> {
+        match_people_for_role(self.id, source, min_score, limit.max(0) as usize)
     }
 }
 
+impl Keyed for Role {
+    fn keyset(&self) -> Keyset {
+        Keyset { created_at: self.created_at, id: self.id }
+    }
+}
 
 // Non Graphql
 impl Role {
@@ -176,6 +187,23 @@ impl Role {
         Ok(roles)
     }
 
+    /// Returns a page of active roles ordered by (created_at, id) for cursor pagination
+    pub fn get_active_page(page: &PageRequest) -> Result<Vec<Self>> {
+        keyset_page!(roles, page, roles::active.eq(true))
+    }
+
+    /// Returns the number of active roles in the system
+    pub fn count_active() -> Result<i64> {
+        let mut conn = connection()?;
+
+        let res = roles::table
+            .filter(roles::active.eq(true))
+            .count()
+            .get_result(&mut conn)?;
+
+        Ok(res)
+    }
+
     pub fn get_active(count: i64) -> Result<Vec<Self>> {
         let mut conn = connection()?;
         let roles = roles::table
@@ -202,6 +230,14 @@ impl Role {
         Ok(role)
     }
 
+    pub fn get_by_ids(ids: &[Uuid]) -> Result<Vec<Self>> {
+        let mut conn = connection()?;
+        let roles = roles::table
+            .filter(roles::id.eq_any(ids))
+            .load::<Self>(&mut conn)?;
+        Ok(roles)
+    }
+
     pub fn get_active_vacant_by_ids(ids: &Vec<Uuid>) -> Result<Vec<Self>> {
         let mut conn = connection()?;
         let roles = roles::table
@@ -222,6 +258,17 @@ impl Role {
         Ok(res)
     }
 
+    /// Returns all roles for a set of teams, used to batch team -> roles lookups
+    pub fn get_by_team_ids(ids: &[Uuid]) -> Result<Vec<Role>> {
+        let mut conn = connection()?;
+
+        let res = roles::table
+            .filter(roles::team_id.eq_any(ids))
+            .load::<Role>(&mut conn)?;
+
+        Ok(res)
+    }
+
     /// Returns active and occupied roles by a team_id
     pub fn get_occupied_by_team_id(id: Uuid) -> Result<Vec<Role>> {
         let mut conn = connection()?;
@@ -272,7 +319,58 @@ impl Role {
 
         Ok(res)
     }
-    
+
+    /// Returns active and inactive roles for a set of people, used to batch person -> roles lookups
+    pub fn get_by_person_ids(ids: &[Uuid]) -> Result<Vec<Role>> {
+        let mut conn = connection()?;
+
+        let res = roles::table
+            .filter(roles::person_id.eq_any(ids))
+            .load::<Role>(&mut conn)?;
+
+        Ok(res)
+    }
+
+    /// Returns the roles each (person, as_of) key's person held on its date. Holders are
+    /// read from the role history, so roles since reassigned or vacated are included
+    /// as they were on that date.
+    pub fn get_by_person_ids_as_of(keys: &[AsOfKey]) -> Result<Vec<(AsOfKey, Role)>> {
+        let Some(since) = keys.iter().map(|(_, as_of)| *as_of).min() else {
+            return Ok(Vec::new());
+        };
+
+        let person_ids: Vec<Uuid> = keys.iter().map(|(id, _)| *id).collect();
+
+        let mut role_ids: Vec<Uuid> = Role::get_by_person_ids(&person_ids)?.iter().map(|r| r.id).collect();
+        role_ids.extend(ChangeRecord::get_entity_ids_by_field_values(Role::ENTITY, "person_id", &person_ids)?);
+        role_ids.sort();
+        role_ids.dedup();
+
+        let roles = Role::get_by_ids(&role_ids)?;
+
+        let mut history: HashMap<Uuid, Vec<ChangeRecord>> = HashMap::new();
+        for record in ChangeRecord::get_by_entity_ids_since(&role_ids, since)? {
+            history.entry(record.entity_id).or_default().push(record);
+        }
+
+        let mut res = Vec::new();
+
+        for key in keys {
+            for role in &roles {
+                let records: Vec<&ChangeRecord> = history.get(&role.id)
+                    .map(|r| r.iter().collect())
+                    .unwrap_or_default();
+
+                if let Some(held) = ChangeRecord::rewind(role, &records, key.1)?
+                    && held.person_id == Some(key.0) && held.in_effect_on(key.1) {
+                    res.push((*key, held));
+                }
+            }
+        }
+
+        Ok(res)
+    }
+
     pub fn update(&mut self) -> Result<Self> {
         let mut conn = connection()?;
 
@@ -365,37 +463,3 @@ impl Distribution<HrGroup> for Standard {
         }
     }
 }
-
-pub fn find_people_by_requirements_met(requirements: Vec<Requirement>) -> Result<Vec<Person>> {
-
-    let mut people_ids = Vec::new();
-
-    let num_matches_required = *&requirements.len() as i32;
-
-    for req in requirements {
-
-        let caps = Capability::get_by_skill_id_and_level(req.skill_id, req.required_level)?;
-
-        for c in caps {
-            people_ids.push(c.person_id);
-        };
-    }
-
-    let id_counts: HashMap<Uuid, i32> =
-        people_ids.iter()
-            .fold(HashMap::new(), |mut map, id| {
-                *map.entry(*id).or_insert(0) += 1;
-                map
-            });
-
-    let mut validated_ids: Vec<Uuid> = Vec::new();
-
-    for (k, v) in id_counts {
-        if v >= num_matches_required {
-            validated_ids.push(k);
-        }
-    };
-
-
-    Person::get_by_ids(&validated_ids)
-}
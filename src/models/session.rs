@@ -0,0 +1,252 @@
+use chrono::{prelude::*, Duration};
+use diesel::{self, Connection, Insertable, Queryable, ExpressionMethods, BoolExpressionMethods, OptionalExtension};
+use diesel::{RunQueryDsl, QueryDsl};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+use async_graphql::*;
+
+use crate::config_variables::{REFRESH_TOKEN_DURATION, TOKEN_DURATION};
+use crate::database::connection;
+use crate::schema::*;
+
+#[derive(Debug, Clone, Deserialize, Serialize, Queryable, Insertable)]
+#[diesel(table_name = refresh_tokens)]
+/// A long lived, single use token exchanged for a new access token.
+/// Each use rotates it: the old token is revoked and a new one in the same family is issued.
+/// Presenting a revoked token revokes the whole family, since it means the token leaked.
+pub struct RefreshToken {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub token_hash: String,
+    pub family_id: Uuid,
+    pub replaced_by: Option<Uuid>,
+    pub expires_at: NaiveDateTime,
+    pub revoked_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = refresh_tokens)]
+struct NewRefreshToken {
+    id: Uuid,
+    user_id: Uuid,
+    token_hash: String,
+    family_id: Uuid,
+    expires_at: NaiveDateTime,
+}
+
+/// Returns a new opaque token and the SHA-256 stored in its place
+pub fn generate_token() -> (String, String) {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+
+    let token = hex::encode(bytes);
+    let hash = hash_token(&token);
+
+    (token, hash)
+}
+
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+impl NewRefreshToken {
+    fn new(user_id: Uuid, family_id: Uuid, token_hash: String) -> Self {
+        NewRefreshToken {
+            id: Uuid::new_v4(),
+            user_id,
+            token_hash,
+            family_id,
+            expires_at: chrono::Utc::now().naive_utc() + Duration::seconds(REFRESH_TOKEN_DURATION),
+        }
+    }
+}
+
+impl RefreshToken {
+
+    /// Starts a new token family on sign in. Returns the stored row and the token to hand out.
+    pub fn issue(user_id: Uuid) -> Result<(RefreshToken, String)> {
+        let mut conn = connection()?;
+
+        let (token, hash) = generate_token();
+
+        let res = diesel::insert_into(refresh_tokens::table)
+            .values(&NewRefreshToken::new(user_id, Uuid::new_v4(), hash))
+            .get_result(&mut conn)?;
+
+        Ok((res, token))
+    }
+
+    pub fn get_by_token(token: &str) -> Result<Option<Self>> {
+        let mut conn = connection()?;
+
+        let res = refresh_tokens::table
+            .filter(refresh_tokens::token_hash.eq(hash_token(token)))
+            .first(&mut conn)
+            .optional()?;
+
+        Ok(res)
+    }
+
+    /// Exchanges a refresh token for a new one in the same family
+    pub fn rotate(token: &str) -> Result<(RefreshToken, String)> {
+
+        let current = RefreshToken::get_by_token(token)?
+            .ok_or_else(|| Error::new("Invalid refresh token"))?;
+
+        let now = chrono::Utc::now().naive_utc();
+
+        if current.revoked_at.is_some() {
+            RefreshToken::revoke_family(current.family_id)?;
+            return Err(Error::new("Refresh token has already been used. Please sign in again"));
+        }
+
+        if current.expires_at <= now {
+            return Err(Error::new("Refresh token has expired. Please sign in again"));
+        }
+
+        let (new_token, hash) = generate_token();
+        let next = NewRefreshToken::new(current.user_id, current.family_id, hash);
+
+        let mut conn = connection()?;
+
+        let rotated = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            // Only one caller can retire the token, a concurrent use finds nothing to update
+            let retired = diesel::update(refresh_tokens::table)
+                .filter(refresh_tokens::id.eq(current.id))
+                .filter(refresh_tokens::revoked_at.is_null())
+                .set((
+                    refresh_tokens::revoked_at.eq(now),
+                    refresh_tokens::replaced_by.eq(next.id),
+                ))
+                .execute(conn)?;
+
+            if retired == 0 {
+                return Ok(None);
+            }
+
+            diesel::insert_into(refresh_tokens::table)
+                .values(&next)
+                .get_result::<RefreshToken>(conn)
+                .map(Some)
+        })?;
+
+        match rotated {
+            Some(r) => Ok((r, new_token)),
+            None => {
+                RefreshToken::revoke_family(current.family_id)?;
+                Err(Error::new("Refresh token has already been used. Please sign in again"))
+            }
+        }
+    }
+
+    /// Revokes every token rotated from the same sign in
+    pub fn revoke_family(family_id: Uuid) -> Result<usize> {
+        let mut conn = connection()?;
+
+        let res = diesel::update(refresh_tokens::table)
+            .filter(refresh_tokens::family_id.eq(family_id))
+            .filter(refresh_tokens::revoked_at.is_null())
+            .set(refresh_tokens::revoked_at.eq(chrono::Utc::now().naive_utc()))
+            .execute(&mut conn)?;
+
+        Ok(res)
+    }
+
+    /// Revokes every outstanding refresh token for a user
+    pub fn revoke_all_for_user(user_id: Uuid) -> Result<usize> {
+        let mut conn = connection()?;
+
+        let res = diesel::update(refresh_tokens::table)
+            .filter(refresh_tokens::user_id.eq(user_id))
+            .filter(refresh_tokens::revoked_at.is_null())
+            .set(refresh_tokens::revoked_at.eq(chrono::Utc::now().naive_utc()))
+            .execute(&mut conn)?;
+
+        Ok(res)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Queryable)]
+#[diesel(table_name = revoked_tokens)]
+/// Server side denylist for access tokens, checked by AuthContext::from_request
+pub struct RevokedToken {
+    pub id: Uuid,
+    /// Denies a single access token
+    pub jti: Option<Uuid>,
+    pub user_id: Uuid,
+    /// Denies every access token for user_id issued at or before this
+    pub issued_before: Option<NaiveDateTime>,
+    pub expires_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = revoked_tokens)]
+struct NewRevokedToken {
+    jti: Option<Uuid>,
+    user_id: Uuid,
+    issued_before: Option<NaiveDateTime>,
+    expires_at: NaiveDateTime,
+}
+
+fn from_timestamp(seconds: i64) -> NaiveDateTime {
+    DateTime::from_timestamp(seconds, 0).unwrap_or_default().naive_utc()
+}
+
+impl RevokedToken {
+
+    fn insert(revoked: &NewRevokedToken) -> Result<()> {
+        let mut conn = connection()?;
+
+        // Entries are useless once the tokens they deny have expired
+        diesel::delete(revoked_tokens::table)
+            .filter(revoked_tokens::expires_at.lt(chrono::Utc::now().naive_utc()))
+            .execute(&mut conn)?;
+
+        diesel::insert_into(revoked_tokens::table)
+            .values(revoked)
+            .execute(&mut conn)?;
+
+        Ok(())
+    }
+
+    /// Denies a single access token until it expires at exp (seconds since the epoch)
+    pub fn revoke_access_token(jti: Uuid, user_id: Uuid, exp: i64) -> Result<()> {
+        RevokedToken::insert(&NewRevokedToken {
+            jti: Some(jti),
+            user_id,
+            issued_before: None,
+            expires_at: from_timestamp(exp),
+        })
+    }
+
+    /// Denies every access token issued to the user up to now, to the microsecond.
+    /// A token issued later in the same request, as changePassword does, stays valid.
+    pub fn revoke_all_access_tokens(user_id: Uuid) -> Result<()> {
+        let now = chrono::Utc::now().naive_utc();
+
+        RevokedToken::insert(&NewRevokedToken {
+            jti: None,
+            user_id,
+            issued_before: Some(now),
+            expires_at: now + Duration::seconds(TOKEN_DURATION),
+        })
+    }
+
+    /// True if the token with jti, issued to user_id at issued_at, has been revoked
+    pub fn is_revoked(jti: Uuid, user_id: Uuid, issued_at: NaiveDateTime) -> Result<bool> {
+        let mut conn = connection()?;
+
+        let count: i64 = revoked_tokens::table
+            .filter(revoked_tokens::jti.eq(jti)
+                .or(revoked_tokens::user_id.eq(user_id)
+                    .and(revoked_tokens::issued_before.ge(issued_at))))
+            .count()
+            .get_result(&mut conn)?;
+
+        Ok(count > 0)
+    }
+}
@@ -17,6 +17,7 @@ use crate::models::Capability;
 
 use crate::database::connection;
 use crate::schema::*;
+use crate::graphql::{Keyed, Keyset, PageRequest, keyset_page};
 
 
 #[derive(Debug, Clone, Deserialize, Serialize, Queryable, Identifiable, AsChangeset, SimpleObject, PartialEq)]
@@ -83,6 +84,12 @@ impl Distribution<SkillDomain> for Standard {
     }
 }
 
+impl Keyed for Skill {
+    fn keyset(&self) -> Keyset {
+        Keyset { created_at: self.created_at, id: self.id }
+    }
+}
+
 impl Skill {
     pub fn create(skill: &NewSkill) -> Result<Skill> {
 
@@ -121,6 +128,22 @@ impl Skill {
         Ok(res)
     }
 
+    /// Returns a page of skills ordered by (created_at, id) for cursor pagination
+    pub fn get_page(page: &PageRequest) -> Result<Vec<Self>> {
+        keyset_page!(skills, page)
+    }
+
+    /// Returns the number of skills in the system
+    pub fn count() -> Result<i64> {
+        let mut conn = connection()?;
+
+        let res = skills::table
+            .count()
+            .get_result(&mut conn)?;
+
+        Ok(res)
+    }
+
     pub fn get_by_name(name: String) -> Result<Vec<Self>> {
         let mut conn = connection()?;
 
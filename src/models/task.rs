@@ -11,6 +11,7 @@ use crate::schema::*;
 use crate::database::connection;
 
 use crate::models::{SkillDomain, WorkStatus};
+use crate::graphql::{Keyed, Keyset, Loaders, PageRequest, keyset_page};
 
 use super::{Work, Role};
 
@@ -44,8 +45,14 @@ impl Task {
         Work::sum_task_effort(&self.id)
     }
 
-    pub async fn created_by(&self) -> Result<Role> {
-        Role::get_by_id(&self.created_by_role_id)
+    pub async fn created_by(&self, ctx: &Context<'_>) -> Result<Role> {
+        ctx.data::<Loaders>()?.role(self.created_by_role_id).await
+    }
+}
+
+impl Keyed for Task {
+    fn keyset(&self) -> Keyset {
+        Keyset { created_at: self.created_at, id: self.id }
     }
 }
 
@@ -90,6 +97,22 @@ impl Task {
         Ok(res)
     }
 
+    /// Returns a page of tasks ordered by (created_at, id) for cursor pagination
+    pub fn get_page(page: &PageRequest) -> Result<Vec<Self>> {
+        keyset_page!(tasks, page)
+    }
+
+    /// Returns the number of tasks in the system
+    pub fn count() -> Result<i64> {
+        let mut conn = connection()?;
+
+        let res = tasks::table
+            .count()
+            .get_result(&mut conn)?;
+
+        Ok(res)
+    }
+
     pub fn get_count(count: i64) -> Result<Vec<Self>> {
         let mut conn = connection()?;
         let res = tasks::table
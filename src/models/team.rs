@@ -2,7 +2,7 @@ use std::fmt::Debug;
 
 use chrono::{prelude::*};
 use serde::{Deserialize, Serialize};
-use diesel::{self, Insertable, Queryable, BoolExpressionMethods, ExpressionMethods, PgTextExpressionMethods};
+use diesel::{self, Connection, Insertable, Queryable, BoolExpressionMethods, ExpressionMethods, PgTextExpressionMethods};
 use diesel::{RunQueryDsl, QueryDsl};
 use uuid::Uuid;
 use async_graphql::*;
@@ -12,8 +12,9 @@ use crate::config_variables::DATE_FORMAT;
 
 use crate::schema::*;
 use crate::database::connection;
+use crate::graphql::{Keyed, Keyset, Loaders, PageRequest, keyset_page};
 
-use super::{Role, Person, TeamOwnership, SkillDomain};
+use super::{Role, Person, SkillDomain, TeamOwnership, AsOf};
 
 
 #[derive(Debug, Clone, Deserialize, Serialize, Identifiable, Queryable, Insertable, AsChangeset)]
@@ -40,6 +41,12 @@ pub struct Team {
     // pub milestones: Uuid // Refers to Github Milestones
 }
 
+impl Keyed for Team {
+    fn keyset(&self) -> Keyset {
+        Keyset { created_at: self.created_at, id: self.id }
+    }
+}
+
 // Non Graphql
 impl Team {
     pub fn create(team: &NewTeam) -> Result<Team> {
@@ -93,6 +100,22 @@ impl Team {
         Ok(res)
     }
 
+    /// Returns a page of teams ordered by (created_at, id) for cursor pagination
+    pub fn get_page(page: &PageRequest) -> Result<Vec<Self>> {
+        keyset_page!(teams, page)
+    }
+
+    /// Returns the number of teams in the system
+    pub fn count() -> Result<i64> {
+        let mut conn = connection()?;
+
+        let res = teams::table
+            .count()
+            .get_result(&mut conn)?;
+
+        Ok(res)
+    }
+
     pub fn get_by_name(name: String) -> Result<Vec<Self>> {
         let mut conn = connection()?;
 
@@ -113,6 +136,51 @@ impl Team {
         Ok(res)
     }
 
+    pub fn get_by_org_id(id: &Uuid) -> Result<Vec<Self>> {
+        let mut conn = connection()?;
+
+        let res = teams::table
+            .filter(teams::organization_id.eq(id))
+            .load::<Team>(&mut conn)?;
+
+        Ok(res)
+    }
+
+    /// Returns the teams under an org tier that existed at as_of
+    pub fn get_by_org_tier_id_as_of(id: &Uuid, as_of: NaiveDateTime) -> Result<Vec<Self>> {
+        let mut conn = connection()?;
+
+        let res = teams::table
+            .filter(teams::org_tier_id.eq(id))
+            .filter(teams::created_at.le(as_of))
+            .filter(teams::retired_at.is_null().or(teams::retired_at.gt(as_of)))
+            .load::<Team>(&mut conn)?;
+
+        Ok(res)
+    }
+
+    pub fn get_by_org_tier_ids(ids: &[Uuid]) -> Result<Vec<Self>> {
+        let mut conn = connection()?;
+
+        let res = teams::table
+            .filter(teams::org_tier_id.eq_any(ids))
+            .load::<Team>(&mut conn)?;
+
+        Ok(res)
+    }
+
+    /// Returns the teams under a set of org tiers that haven't been retired
+    pub fn get_active_by_org_tier_ids(ids: &[Uuid]) -> Result<Vec<Self>> {
+        let mut conn = connection()?;
+
+        let res = teams::table
+            .filter(teams::org_tier_id.eq_any(ids))
+            .filter(teams::retired_at.is_null())
+            .load::<Team>(&mut conn)?;
+
+        Ok(res)
+    }
+
     pub fn get_by_ids(ids: &Vec<Uuid>) -> Result<Vec<Self>> {
 
         let mut conn = connection()?;
@@ -134,6 +202,37 @@ impl Team {
         
         Ok(res)
     }
+
+    /// Sets retired_at on the team and end_date on its open ownership in one transaction.
+    /// Returns the retired team and the ownerships it closed.
+    pub fn retire(&self) -> Result<(Self, Vec<TeamOwnership>)> {
+        let mut conn = connection()?;
+
+        let now = chrono::Utc::now().naive_utc();
+
+        let res = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            let team = diesel::update(teams::table)
+                .filter(teams::id.eq(&self.id))
+                .set((
+                    teams::retired_at.eq(now),
+                    teams::updated_at.eq(now),
+                ))
+                .get_result::<Team>(conn)?;
+
+            let closed = diesel::update(team_ownerships::table)
+                .filter(team_ownerships::team_id.eq(&self.id))
+                .filter(team_ownerships::end_date.is_null())
+                .set((
+                    team_ownerships::end_date.eq(now),
+                    team_ownerships::updated_at.eq(now),
+                ))
+                .get_results::<TeamOwnership>(conn)?;
+
+            Ok((team, closed))
+        })?;
+
+        Ok(res)
+    }
 }
 
 #[Object]
@@ -142,12 +241,12 @@ impl Team {
         self.id
     }
 
-    pub async fn organization(&self) -> Result<Organization> {
-        Organization::get_by_id(&self.organization_id)
+    pub async fn organization(&self, ctx: &Context<'_>) -> Result<Organization> {
+        ctx.data::<Loaders>()?.organization(self.organization_id).await
     }
 
-    pub async fn organization_level(&self) -> Result<OrgTier> {
-        OrgTier::get_by_id(&self.org_tier_id)
+    pub async fn organization_level(&self, ctx: &Context<'_>) -> Result<OrgTier> {
+        ctx.data::<Loaders>()?.org_tier(self.org_tier_id).await
     }
 
     pub async fn name_english(&self) -> Result<String> {
@@ -181,22 +280,48 @@ impl Team {
         Ok(self.updated_at.format(DATE_FORMAT).to_string())
     }
 
-    pub async fn occupied_roles(&self) -> Result<Vec<Role>> {
-        Role::get_occupied_by_team_id(self.id)
+    /// Returns active and occupied roles
+    pub async fn occupied_roles(&self, ctx: &Context<'_>) -> Result<Vec<Role>> {
+        let roles = ctx.data::<Loaders>()?.roles_by_team(self.id).await?;
+
+        Ok(roles.into_iter().filter(|r| r.active && r.person_id.is_some()).collect())
     }
 
-    pub async fn vacant_roles(&self) -> Result<Vec<Role>> {
-        Role::get_vacant_by_team_id(self.id)
+    /// Returns active and vacant roles
+    pub async fn vacant_roles(&self, ctx: &Context<'_>) -> Result<Vec<Role>> {
+        let roles = ctx.data::<Loaders>()?.roles_by_team(self.id).await?;
+
+        Ok(roles.into_iter().filter(|r| r.active && r.person_id.is_none()).collect())
     }
 
-    pub async fn roles(&self) -> Result<Vec<Role>> {
-        Role::get_by_team_id(self.id)
+    /// Returns all roles on the team, or the roles that were in effect on as_of if it is given
+    pub async fn roles(
+        &self,
+        ctx: &Context<'_>,
+        as_of: Option<NaiveDateTime>,
+    ) -> Result<Vec<Role>> {
+        let roles = ctx.data::<Loaders>()?.roles_by_team(self.id).await?;
+
+        match as_of {
+            Some(date) => Ok(roles.into_iter().filter(|r| r.in_effect_on(date)).collect()),
+            None => Ok(roles),
+        }
     }
 
-    pub async fn owner(&self) -> Result<Person> {
-        let team_ownership = TeamOwnership::get_by_team_id(&self.id).unwrap();
+    /// Returns the current owner, or the owner on as_of if it is given
+    pub async fn owner(
+        &self,
+        ctx: &Context<'_>,
+        as_of: Option<NaiveDateTime>,
+    ) -> Result<Person> {
+        let loaders = ctx.data::<Loaders>()?;
+
+        let owner = match as_of {
+            Some(date) => loaders.team_owner_as_of(self.id, date).await?,
+            None => loaders.team_owner(self.id).await?,
+        };
 
-        Person::get_by_id(&team_ownership.person_id)
+        owner.ok_or_else(|| Error::new(format!("Team {} has no owner on that date", self.id)))
     }
     
 }
@@ -2,13 +2,16 @@ use std::fmt::Debug;
 
 use chrono::{prelude::*};
 use serde::{Deserialize, Serialize};
-use diesel::{self, Insertable, Queryable, ExpressionMethods};
+use diesel::{self, Connection, Insertable, Queryable, ExpressionMethods};
 use diesel::{RunQueryDsl, QueryDsl};
 use uuid::Uuid;
 use async_graphql::*;
 
 use crate::schema::*;
 use crate::database::connection;
+use crate::graphql::person_id_field;
+
+use super::{AsOfKey, in_effect_for};
 
 #[derive(Debug, Clone, Deserialize, Serialize, Queryable, Insertable, AsChangeset, SimpleObject)]
 #[diesel(table_name = team_ownerships)]
@@ -17,6 +20,7 @@ use crate::database::connection;
 // Represents ownership of a team by a person
 pub struct TeamOwnership {
     pub id: Uuid,
+    #[graphql(directive = person_id_field::apply())]
     pub person_id: Uuid,
     pub team_id: Uuid,
 
@@ -103,6 +107,68 @@ impl TeamOwnership {
         Ok(res)
     }
 
+    /// Returns the open (no end_date) ownerships for a set of teams
+    pub fn get_current_by_team_ids(ids: &[Uuid]) -> Result<Vec<Self>> {
+        let mut conn = connection()?;
+
+        let res = team_ownerships::table
+            .filter(team_ownerships::team_id.eq_any(ids))
+            .filter(team_ownerships::end_date.is_null())
+            .load::<Self>(&mut conn)?;
+
+        Ok(res)
+    }
+
+    /// Returns all ownerships, open and closed, for a set of teams
+    pub fn get_by_team_ids(ids: &[Uuid]) -> Result<Vec<Self>> {
+        let mut conn = connection()?;
+
+        let res = team_ownerships::table
+            .filter(team_ownerships::team_id.eq_any(ids))
+            .load::<Self>(&mut conn)?;
+
+        Ok(res)
+    }
+
+    /// Returns the ownerships in effect for each (team, as_of) key, latest first
+    pub fn get_by_team_ids_as_of(keys: &[AsOfKey]) -> Result<Vec<(AsOfKey, Self)>> {
+        let mut conn = connection()?;
+
+        let ids: Vec<Uuid> = keys.iter().map(|(id, _)| *id).collect();
+
+        let res = team_ownerships::table
+            .filter(team_ownerships::team_id.eq_any(ids))
+            .order_by(team_ownerships::start_datestamp.desc())
+            .load::<Self>(&mut conn)?;
+
+        Ok(in_effect_for(keys, &res, |o| o.team_id))
+    }
+
+    /// Closes the team's open ownership (if any) and opens a new one for person_id.
+    /// The previous ownership keeps its history with end_date set to now.
+    pub fn transfer(team_id: Uuid, person_id: Uuid) -> Result<Self> {
+        let mut conn = connection()?;
+
+        let now = chrono::Utc::now().naive_utc();
+
+        let res = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            diesel::update(team_ownerships::table)
+                .filter(team_ownerships::team_id.eq(team_id))
+                .filter(team_ownerships::end_date.is_null())
+                .set((
+                    team_ownerships::end_date.eq(now),
+                    team_ownerships::updated_at.eq(now),
+                ))
+                .execute(conn)?;
+
+            diesel::insert_into(team_ownerships::table)
+                .values(&NewTeamOwnership::new(person_id, team_id, now, None))
+                .get_result(conn)
+        })?;
+
+        Ok(res)
+    }
+
     pub fn get_team_ids_by_owner_id(id: &Uuid) -> Result<Vec<Uuid>> {
         let mut conn = connection()?;
 
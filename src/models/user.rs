@@ -2,14 +2,15 @@
 
 use chrono::prelude::*;
 use serde::{Deserialize, Serialize};
-use diesel::{self, ExpressionMethods, Insertable, QueryDsl, Queryable, RunQueryDsl};
+use diesel::{self, ExpressionMethods, Insertable, OptionalExtension, QueryDsl, Queryable, RunQueryDsl};
 use uuid::Uuid;
 use async_graphql::*;
 
 use crate::{schema::*};
 use crate::common_utils::{is_admin, RoleGuard, UserRole};
-use crate::models::hash_password;
+use crate::models::{hash_password, Granularity};
 use crate::database::connection;
+use crate::graphql::{Keyed, Keyset, PageRequest, identifying_field, keyset_page};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct UserInstance {
@@ -29,6 +30,7 @@ pub struct User {
     #[graphql(
         guard = "RoleGuard::new(UserRole::Admin)",
         visible = "is_admin",
+        directive = identifying_field::apply(),
     )]
     pub email: String,
     pub role: String,
@@ -36,6 +38,7 @@ pub struct User {
     #[graphql(
         guard = "RoleGuard::new(UserRole::Admin)",
         visible = "is_admin",
+        directive = identifying_field::apply(),
     )]
     pub name: String,
     pub access_level: String, // AccessLevelEnum
@@ -60,6 +63,40 @@ pub struct User {
     )]
     /// Access Level: Admin
     pub approved_by_user_uid: Option<Uuid>,
+
+    #[graphql(
+        guard = "RoleGuard::new(UserRole::Admin)",
+        visible = "is_admin",
+    )]
+    /// When the user confirmed their current email address. Access Level: Admin
+    pub email_verified_at: Option<NaiveDateTime>,
+
+    #[graphql(
+        guard = "RoleGuard::new(UserRole::Admin)",
+        visible = "is_admin",
+    )]
+    /// Identity at the OIDC provider for users who sign in with it. Access Level: Admin
+    pub external_id: Option<String>,
+
+    #[graphql(
+        guard = "RoleGuard::new(UserRole::Admin)",
+        visible = "is_admin",
+    )]
+    /// When an Admin approved the user. Self-registered users can't sign in until then. Access Level: Admin
+    pub approved_at: Option<NaiveDateTime>,
+
+    #[graphql(
+        guard = "RoleGuard::new(UserRole::Admin)",
+        visible = "is_admin",
+    )]
+    /// The most detailed view of personal data the user is granted. Access Level: Admin
+    pub granularity: Granularity,
+}
+
+impl Keyed for User {
+    fn keyset(&self) -> Keyset {
+        Keyset { created_at: self.created_at, id: self.id }
+    }
 }
 
 impl User {
@@ -82,6 +119,81 @@ impl User {
         Ok(user)
     }
 
+    pub fn get_by_external_id(external_id: &str) -> Result<Option<Self>> {
+        let mut conn = connection()?;
+        let user = users::table
+            .filter(users::external_id.eq(external_id))
+            .get_result(&mut conn)
+            .optional()?;
+
+        Ok(user)
+    }
+
+    /// Returns a page of users ordered by (created_at, id) for cursor pagination
+    pub fn get_page(page: &PageRequest) -> Result<Vec<Self>> {
+        keyset_page!(users, page)
+    }
+
+    /// Returns self-registered users waiting for approval, oldest first
+    pub fn get_pending() -> Result<Vec<Self>> {
+        let mut conn = connection()?;
+
+        let res = users::table
+            .filter(users::approved_at.is_null())
+            .order_by((users::created_at.asc(), users::id.asc()))
+            .load::<User>(&mut conn)?;
+
+        Ok(res)
+    }
+
+    pub fn is_approved(&self) -> bool {
+        self.approved_at.is_some()
+    }
+
+    /// Approves a pending user, recording who approved them and when
+    pub fn approve(id: &Uuid, approver: Option<Uuid>) -> Result<Self> {
+        let mut conn = connection()?;
+
+        let now = chrono::Utc::now().naive_utc();
+
+        let res = diesel::update(users::table)
+            .filter(users::id.eq(id))
+            .filter(users::approved_at.is_null())
+            .set((
+                users::approved_by_user_uid.eq(approver),
+                users::approved_at.eq(now),
+                users::updated_at.eq(now),
+            ))
+            .get_result(&mut conn)
+            .optional()?;
+
+        res.ok_or_else(|| Error::new("No pending user with this id"))
+    }
+
+    /// Deletes a pending user. Approved users can't be removed this way.
+    pub fn delete_pending(id: &Uuid) -> Result<Self> {
+        let mut conn = connection()?;
+
+        let res = diesel::delete(users::table)
+            .filter(users::id.eq(id))
+            .filter(users::approved_at.is_null())
+            .get_result(&mut conn)
+            .optional()?;
+
+        res.ok_or_else(|| Error::new("No pending user with this id"))
+    }
+
+    /// Returns the number of users in the system
+    pub fn count() -> Result<i64> {
+        let mut conn = connection()?;
+
+        let res = users::table
+            .count()
+            .get_result(&mut conn)?;
+
+        Ok(res)
+    }
+
     pub fn create(user: InsertableUser) -> Result<Self> {
         let mut conn = connection()?;
         let user = diesel::insert_into(users::table)
@@ -91,6 +203,18 @@ impl User {
         Ok(user)
     }
 
+    /// Replaces the password hash without touching updated_at, used to upgrade legacy hashes
+    pub fn set_hash(id: Uuid, hash: &str) -> Result<()> {
+        let mut conn = connection()?;
+
+        diesel::update(users::table)
+            .filter(users::id.eq(id))
+            .set(users::hash.eq(hash))
+            .execute(&mut conn)?;
+
+        Ok(())
+    }
+
     pub fn update(&mut self) -> Result<Self> {
         let mut conn = connection()?;
 
@@ -117,6 +241,10 @@ pub struct InsertableUser {
     pub updated_at: NaiveDateTime,
     pub access_key: String,
     pub approved_by_user_uid: Option<Uuid>,
+    pub email_verified_at: Option<NaiveDateTime>,
+    pub external_id: Option<String>,
+    pub approved_at: Option<NaiveDateTime>,
+    pub granularity: Granularity,
 }
 
 #[derive(Debug, Deserialize, Serialize, InputObject)]
@@ -129,6 +257,15 @@ pub struct UserData {
     pub role: String,
 }
 
+#[derive(Debug, Deserialize, Serialize, InputObject)]
+/// Input Struct for self-registration. The new user has UserRole::User and Granularity::Anonymized
+/// and can't sign in until an Administrator approves them.
+pub struct SignUpData {
+    pub name: String,
+    pub email: String,
+    pub password: String,
+}
+
 #[derive(Debug, Deserialize, Serialize, InputObject)]
 /// Input Struct to create a new user. Only accessible by Administrators.
 pub struct UserUpdate {
@@ -138,6 +275,8 @@ pub struct UserUpdate {
     pub password: Option<String>,
     /// UserRole in system: USER, OPERATOR, ANALYST, ADMIN
     pub role: Option<String>,
+    /// Applies from the user's next sign in or token refresh
+    pub granularity: Option<Granularity>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, SimpleObject)]
@@ -184,6 +323,33 @@ impl From<UserData> for InsertableUser {
             access_key: "".to_owned(),
             access_level: "detailed".to_owned(),
             approved_by_user_uid: None,
+            email_verified_at: None,
+            external_id: None,
+            // Users created by an Administrator or at setup don't need approval
+            approved_at: Some(updated_at),
+            granularity: Granularity::Identifiable,
+        }
+    }
+}
+
+impl From<SignUpData> for InsertableUser {
+    fn from(sign_up: SignUpData) -> Self {
+
+        let SignUpData {
+            name,
+            email,
+            password,
+        } = sign_up;
+
+        Self {
+            approved_at: None,
+            granularity: Granularity::Anonymized,
+            ..InsertableUser::from(UserData {
+                name,
+                email,
+                password,
+                role: UserRole::User.to_string(),
+            })
         }
     }
 }
@@ -207,6 +373,13 @@ impl From<User> for SlimUser {
     }
 }
 
+#[derive(Debug, Deserialize, InputObject)]
+/// Input Struct for a signed in user to change their own password
+pub struct PasswordChange {
+    pub current_password: String,
+    pub new_password: String,
+}
+
 #[derive(Debug, Deserialize, InputObject)]
 pub struct LoginQuery {
     pub email: String,
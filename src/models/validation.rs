@@ -9,10 +9,12 @@ use async_graphql::*;
 
 use crate::schema::*;
 use crate::common_utils::{RoleGuard, UserRole, is_admin};
+use crate::config_variables::VALIDATION_QUORUM;
 use crate::database::connection;
 use crate::models::{CapabilityLevel};
 
 use super::{Person, Capability};
+use crate::graphql::{Loaders, person_id_field};
 
 #[derive(Debug, Clone, Deserialize, Serialize, Queryable, Insertable, AsChangeset, SimpleObject)]
 #[diesel(table_name = validations)]
@@ -26,6 +28,7 @@ pub struct Validation {
     #[graphql(
         guard = "RoleGuard::new(UserRole::Admin)",
         visible = "is_admin",
+        directive = person_id_field::apply(),
     )]
     pub validator_id: Uuid, // Person
     pub capability_id: Uuid, // Capability
@@ -36,8 +39,8 @@ pub struct Validation {
 
 #[ComplexObject]
 impl Validation {
-    pub async fn validator(&self) -> Result<Person> {
-        Person::get_by_id(&self.validator_id)
+    pub async fn validator(&self, ctx: &Context<'_>) -> Result<Person> {
+        ctx.data::<Loaders>()?.person(self.validator_id).await
     }
 }
 
@@ -55,11 +58,56 @@ impl Validation {
 
         let mut capability = Capability::get_by_id(&res.capability_id)?;
 
-        capability.update_from_validation(&res.validated_level)?;
+        capability.rescore()?;
         
         Ok(res)
     }
 
+    /// Records a validation of a capability by another person and returns the recomputed level.
+    /// The validator cannot validate their own capability, can only validate a capability once,
+    /// and must hold a validated capability in the same skill at or above the level they validate.
+    /// Until VALIDATION_QUORUM people hold a validated level in the skill, so that no one could
+    /// be validated otherwise, the validator's self-identified level is accepted instead.
+    pub fn validate(capability_id: Uuid, validator_id: Uuid, validated_level: CapabilityLevel) -> Result<ValidatedLevel> {
+
+        let capability = Capability::get_by_id(&capability_id)?;
+
+        if capability.retired_at.is_some() {
+            return Err(Error::new("Cannot validate a retired capability"));
+        }
+
+        if capability.person_id == validator_id {
+            return Err(Error::new("A person cannot validate their own capability"));
+        }
+
+        let existing = Validation::get_by_capability_id(&capability_id)?;
+
+        if existing.iter().any(|v| v.validator_id == validator_id) {
+            return Err(Error::new("Validator has already validated this capability"));
+        }
+
+        let validator_capability = Capability::get_by_person_and_skill_id(&validator_id, &capability.skill_id)?
+            .ok_or_else(|| Error::new("Validator does not hold this skill"))?;
+
+        let validator_level = match validator_capability.validated_level {
+            Some(level) => level,
+            None if Capability::count_validated_by_skill_id(&capability.skill_id)? < VALIDATION_QUORUM as i64 =>
+                validator_capability.self_identified_level,
+            None => return Err(Error::new("Validator's own capability in this skill has not been validated")),
+        };
+
+        if validator_level < validated_level {
+            return Err(Error::new(format!(
+                "Validator's level ({}) is below the level being validated ({})", validator_level, validated_level)));
+        }
+
+        Validation::batch_create(vec![NewValidation::new(validator_id, capability_id, validated_level)])?;
+
+        let mut capability = Capability::get_by_id(&capability_id)?;
+
+        capability.rescore()
+    }
+
     pub fn batch_create(validations: Vec<NewValidation>) -> Result<usize> {
         let mut conn = connection()?;
 
@@ -153,26 +201,36 @@ impl Validation {
 
         let mut capability = Capability::get_by_id(&res.capability_id)?;
 
-        capability.update_from_validation(&res.validated_level)?;
+        capability.rescore()?;
         
         Ok(res)
     }
 }
 
 #[derive(Debug, Copy, Clone, Serialize, Deserialize, SimpleObject)]
+/// Result of scoring a capability's validations
 pub struct ValidatedLevel {
-    capability_level: CapabilityLevel,
-    average: f32,
+    /// None until the validation quorum is met, unless the capability already had a level
+    pub capability_level: Option<CapabilityLevel>,
+    /// Weighted average of the validations on a 0 (Desired) to 4 (Specialist) scale
+    pub average: f32,
+    /// 0 to 1, grows with the weight of evidence and shrinks as validators disagree
+    pub confidence: f32,
+    pub validation_count: i32,
 }
 
 impl ValidatedLevel {
     pub fn new(
-        capability_level: CapabilityLevel, 
+        capability_level: Option<CapabilityLevel>,
         average: f32,
+        confidence: f32,
+        validation_count: i32,
     ) -> Self {
         ValidatedLevel { 
             capability_level,
             average,
+            confidence,
+            validation_count,
          }
     }
 
@@ -202,26 +260,6 @@ impl ValidatedLevel {
         cap
     }
 
-    pub fn return_validated_level(validation_values: &Vec<i64>) -> Result<ValidatedLevel> {
-    
-        let average_value = validation_values.iter().sum::<i64>() / validation_values.len() as i64;
-    
-        let cap = match &average_value {
-            00..=070 => CapabilityLevel::Desired,
-            71..=170 => CapabilityLevel::Novice,
-            171..=270 => CapabilityLevel::Experienced,
-            271..=370 => CapabilityLevel::Expert,
-            371..=470 => CapabilityLevel::Specialist,
-            _ => CapabilityLevel::Desired,
-        };
-    
-        let res = ValidatedLevel::new(
-            cap,
-            average_value as f32 / 100.0,
-        );
-    
-        Ok(res)
-    }
 }
 
 
@@ -0,0 +1,220 @@
+use chrono::NaiveDateTime;
+
+use crate::config_variables::{VALIDATION_HALF_LIFE_DAYS, VALIDATION_QUORUM, VALIDATION_CONFIDENCE_PRIOR};
+
+use super::{CapabilityLevel, ValidatedLevel, Validation};
+
+/// A validation prepared for scoring
+#[derive(Debug, Clone, Copy)]
+pub struct ScoredValidation {
+    /// Value of the level assessed (see ValidatedLevel::get_value_from_capability_level)
+    pub value: i64,
+    /// The validator's own validated level in the same skill, if any
+    pub validator_level: Option<CapabilityLevel>,
+    /// Age of the validation in days
+    pub age_days: f64,
+}
+
+impl ScoredValidation {
+    pub fn new(validation: &Validation, validator_level: Option<CapabilityLevel>, now: NaiveDateTime) -> Self {
+        ScoredValidation {
+            value: ValidatedLevel::get_value_from_capability_level(&validation.validated_level),
+            validator_level,
+            age_days: (now - validation.created_at).num_seconds().max(0) as f64 / 86_400.0,
+        }
+    }
+}
+
+/// Turns a set of validations into a ValidatedLevel.
+/// Implement this to change how validations are combined.
+pub trait ScoringPolicy {
+    fn score(&self, validations: &[ScoredValidation]) -> ValidatedLevel;
+}
+
+/// Default policy:
+/// * each validation is weighted by the validator's own validated level in the skill
+///   (Desired or unvalidated = 1 through Specialist = 5)
+/// * weights halve every `half_life_days`
+/// * no level is set until at least `quorum` validations exist
+/// * confidence grows with the total weight of evidence and shrinks as validators disagree
+#[derive(Debug, Clone, Copy)]
+pub struct WeightedDecayPolicy {
+    pub half_life_days: f64,
+    pub quorum: usize,
+    /// Total weight at which evidence counts for half of the confidence
+    pub confidence_prior: f64,
+}
+
+impl Default for WeightedDecayPolicy {
+    fn default() -> Self {
+        WeightedDecayPolicy {
+            half_life_days: VALIDATION_HALF_LIFE_DAYS,
+            quorum: VALIDATION_QUORUM,
+            confidence_prior: VALIDATION_CONFIDENCE_PRIOR,
+        }
+    }
+}
+
+impl WeightedDecayPolicy {
+    pub fn validator_weight(level: Option<CapabilityLevel>) -> f64 {
+        match level {
+            None | Some(CapabilityLevel::Desired) => 1.0,
+            Some(CapabilityLevel::Novice) => 2.0,
+            Some(CapabilityLevel::Experienced) => 3.0,
+            Some(CapabilityLevel::Expert) => 4.0,
+            Some(CapabilityLevel::Specialist) => 5.0,
+        }
+    }
+
+    pub fn decay(&self, age_days: f64) -> f64 {
+        if self.half_life_days <= 0.0 {
+            return 1.0;
+        }
+        0.5_f64.powf(age_days / self.half_life_days)
+    }
+}
+
+impl ScoringPolicy for WeightedDecayPolicy {
+    fn score(&self, validations: &[ScoredValidation]) -> ValidatedLevel {
+
+        let weighted: Vec<(f64, f64)> = validations.iter()
+            .map(|v| (v.value as f64, Self::validator_weight(v.validator_level) * self.decay(v.age_days)))
+            .collect();
+
+        let total_weight: f64 = weighted.iter().map(|(_, w)| w).sum();
+
+        if validations.is_empty() || total_weight <= 0.0 {
+            return ValidatedLevel::new(None, 0.0, 0.0, validations.len() as i32);
+        }
+
+        let mean = weighted.iter().map(|(v, w)| v * w).sum::<f64>() / total_weight;
+
+        let variance = weighted.iter().map(|(v, w)| w * (v - mean).powi(2)).sum::<f64>() / total_weight;
+
+        // One level (100) of spread halves agreement, two levels removes it
+        let agreement = 1.0 - (variance.sqrt() / 200.0).min(1.0);
+        let evidence = total_weight / (total_weight + self.confidence_prior);
+
+        let level = if validations.len() >= self.quorum {
+            Some(ValidatedLevel::get_capability_level_from_value(&(mean.round() as i64)))
+        } else {
+            None
+        };
+
+        ValidatedLevel::new(
+            level,
+            (mean / 100.0) as f32,
+            (evidence * agreement) as f32,
+            validations.len() as i32,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> WeightedDecayPolicy {
+        WeightedDecayPolicy { half_life_days: 100.0, quorum: 3, confidence_prior: 6.0 }
+    }
+
+    fn validation(level: CapabilityLevel, validator_level: Option<CapabilityLevel>, age_days: f64) -> ScoredValidation {
+        ScoredValidation {
+            value: ValidatedLevel::get_value_from_capability_level(&level),
+            validator_level,
+            age_days,
+        }
+    }
+
+    #[test]
+    fn weights_halve_every_half_life() {
+        let policy = policy();
+
+        assert_eq!(policy.decay(0.0), 1.0);
+        assert_eq!(policy.decay(100.0), 0.5);
+        assert_eq!(policy.decay(200.0), 0.25);
+    }
+
+    #[test]
+    fn no_decay_without_a_half_life() {
+        let policy = WeightedDecayPolicy { half_life_days: 0.0, ..policy() };
+
+        assert_eq!(policy.decay(10_000.0), 1.0);
+    }
+
+    #[test]
+    fn no_level_or_confidence_without_validations() {
+        let level = policy().score(&[]);
+
+        assert_eq!(level.capability_level, None);
+        assert_eq!(level.confidence, 0.0);
+        assert_eq!(level.validation_count, 0);
+    }
+
+    #[test]
+    fn no_level_below_quorum() {
+        let level = policy().score(&[
+            validation(CapabilityLevel::Expert, None, 0.0),
+            validation(CapabilityLevel::Expert, None, 0.0),
+        ]);
+
+        assert_eq!(level.capability_level, None);
+        assert_eq!(level.average, 3.0);
+        assert_eq!(level.validation_count, 2);
+    }
+
+    #[test]
+    fn sets_level_at_quorum() {
+        let level = policy().score(&[validation(CapabilityLevel::Experienced, None, 0.0); 3]);
+
+        assert_eq!(level.capability_level, Some(CapabilityLevel::Experienced));
+        assert_eq!(level.average, 2.0);
+    }
+
+    #[test]
+    fn senior_validators_outweigh_junior_ones() {
+        let level = policy().score(&[
+            validation(CapabilityLevel::Specialist, Some(CapabilityLevel::Specialist), 0.0),
+            validation(CapabilityLevel::Novice, None, 0.0),
+            validation(CapabilityLevel::Novice, None, 0.0),
+        ]);
+
+        // (400 * 5 + 100 + 100) / 7 rounds to 314
+        assert_eq!(level.capability_level, Some(CapabilityLevel::Expert));
+    }
+
+    #[test]
+    fn recent_validations_outweigh_old_ones() {
+        let level = policy().score(&[
+            validation(CapabilityLevel::Novice, None, 400.0),
+            validation(CapabilityLevel::Novice, None, 400.0),
+            validation(CapabilityLevel::Expert, None, 0.0),
+        ]);
+
+        assert_eq!(level.capability_level, Some(CapabilityLevel::Expert));
+    }
+
+    #[test]
+    fn disagreement_lowers_confidence() {
+        let agreed = policy().score(&[validation(CapabilityLevel::Experienced, None, 0.0); 4]);
+
+        let split = policy().score(&[
+            validation(CapabilityLevel::Desired, None, 0.0),
+            validation(CapabilityLevel::Desired, None, 0.0),
+            validation(CapabilityLevel::Specialist, None, 0.0),
+            validation(CapabilityLevel::Specialist, None, 0.0),
+        ]);
+
+        assert!(split.confidence < agreed.confidence);
+    }
+
+    #[test]
+    fn confidence_grows_with_evidence() {
+        let few = policy().score(&[validation(CapabilityLevel::Expert, None, 0.0); 3]);
+        let many = policy().score(&[validation(CapabilityLevel::Expert, None, 0.0); 12]);
+
+        // Unanimous, so confidence is the evidence share: 3 / (3 + 6) and 12 / (12 + 6)
+        assert!((few.confidence - 1.0 / 3.0).abs() < 1e-6);
+        assert!((many.confidence - 2.0 / 3.0).abs() < 1e-6);
+    }
+}
@@ -14,6 +14,7 @@ use async_graphql::*;
 use crate::schema::*;
 use crate::models::{SkillDomain, Role, Task, CapabilityLevel};
 use crate::database::connection;
+use crate::graphql::{Keyed, Keyset, Loaders, PageRequest, keyset_page};
 
 /// Data structure for a relationship between a person and work
 /// This is a many to many relationship as multiple people may be 
@@ -44,11 +45,16 @@ impl Work {
         Task::get_by_id(&self.task_id)
     }
 
-    pub async fn role(&self) -> Result<Role> {
-        Role::get_by_id(&self.role_id)
+    pub async fn role(&self, ctx: &Context<'_>) -> Result<Role> {
+        ctx.data::<Loaders>()?.role(self.role_id).await
     }
 }
 
+impl Keyed for Work {
+    fn keyset(&self) -> Keyset {
+        Keyset { created_at: self.created_at, id: self.id }
+    }
+}
 
 // Non Graphql
 impl Work {
@@ -100,6 +106,22 @@ impl Work {
         Ok(persons)
     }
 
+    /// Returns a page of work ordered by (created_at, id) for cursor pagination
+    pub fn get_page(page: &PageRequest) -> Result<Vec<Self>> {
+        keyset_page!(works, page)
+    }
+
+    /// Returns the number of work in the system
+    pub fn count() -> Result<i64> {
+        let mut conn = connection()?;
+
+        let res = works::table
+            .count()
+            .get_result(&mut conn)?;
+
+        Ok(res)
+    }
+
     pub fn get_count(count: i64) -> Result<Vec<Self>> {
         let mut conn = connection()?;
         let persons = works::table
@@ -154,6 +176,18 @@ impl Work {
         Ok(total_effort)
     }
 
+    /// Returns work that is not completed or cancelled for a set of roles
+    pub fn get_open_by_role_ids(ids: &[Uuid]) -> Result<Vec<Self>> {
+        let mut conn = connection()?;
+
+        let res = works::table
+            .filter(works::role_id.eq_any(ids))
+            .filter(works::work_status.ne_all(vec![WorkStatus::Cancelled, WorkStatus::Completed]))
+            .load::<Self>(&mut conn)?;
+
+        Ok(res)
+    }
+
     /// Return the numeric indicator of the total effort allocated to a task.
     pub fn sum_task_effort(task_id: &Uuid) -> Result<i32> {
         let mut conn = connection()?;
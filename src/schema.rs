@@ -1,14 +1,42 @@
 // @generated automatically by Diesel CLI.
 
 pub mod sql_types {
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "access_rationale"))]
+    pub struct AccessRationale;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "api_scope"))]
+    pub struct ApiScope;
+
     #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "capability_level"))]
     pub struct CapabilityLevel;
 
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "change_action"))]
+    pub struct ChangeAction;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "classification_type"))]
+    pub struct ClassificationType;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "contact_type"))]
+    pub struct ContactType;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "granularity"))]
+    pub struct Granularity;
+
     #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "hr_group"))]
     pub struct HrGroup;
 
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "hr_state"))]
+    pub struct HrState;
+
     #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "language_level"))]
     pub struct LanguageLevel;
@@ -30,6 +58,25 @@ pub mod sql_types {
     pub struct WorkStatus;
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::AccessRationale;
+    use super::sql_types::Granularity;
+
+    access_logs (id) {
+        id -> Uuid,
+        user_id -> Nullable<Uuid>,
+        api_key_id -> Nullable<Uuid>,
+        rationale -> AccessRationale,
+        granularity -> Granularity,
+        #[max_length = 128]
+        operation_name -> Nullable<Varchar>,
+        fields -> Array<Text>,
+        person_ids -> Array<Uuid>,
+        accessed_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     affiliations (id) {
         id -> Uuid,
@@ -45,6 +92,28 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::ApiScope;
+
+    api_keys (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        #[max_length = 128]
+        name -> Varchar,
+        #[max_length = 16]
+        key_prefix -> Varchar,
+        #[max_length = 64]
+        key_hash -> Varchar,
+        scopes -> Array<ApiScope>,
+        expires_at -> Timestamp,
+        last_used_at -> Nullable<Timestamp>,
+        revoked_at -> Nullable<Timestamp>,
+        created_by -> Nullable<Uuid>,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use super::sql_types::SkillDomain;
@@ -65,7 +134,118 @@ diesel::table! {
         created_at -> Timestamp,
         updated_at -> Timestamp,
         retired_at -> Nullable<Timestamp>,
-        validation_values -> Array<Nullable<Int8>>,
+        validation_confidence -> Nullable<Float4>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::ChangeAction;
+
+    change_records (id) {
+        id -> Uuid,
+        #[max_length = 64]
+        entity_type -> Varchar,
+        entity_id -> Uuid,
+        action -> ChangeAction,
+        changes -> Jsonb,
+        actor_id -> Nullable<Uuid>,
+        changed_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::ContactType;
+
+    contact_datas (id) {
+        id -> Uuid,
+        person_id -> Uuid,
+        contact_type -> ContactType,
+        #[max_length = 128]
+        email -> Nullable<Varchar>,
+        #[max_length = 32]
+        phone -> Nullable<Varchar>,
+        #[max_length = 256]
+        work_address -> Nullable<Varchar>,
+        #[max_length = 128]
+        city -> Nullable<Varchar>,
+        #[max_length = 128]
+        province -> Nullable<Varchar>,
+        #[max_length = 16]
+        postal_code -> Nullable<Varchar>,
+        #[max_length = 128]
+        country -> Nullable<Varchar>,
+        is_primary -> Bool,
+        start_datestamp -> Timestamp,
+        end_date -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    deployments (id) {
+        id -> Uuid,
+        person_id -> Uuid,
+        role_id -> Nullable<Uuid>,
+        #[max_length = 256]
+        event_name -> Varchar,
+        effort -> Float8,
+        start_datestamp -> Timestamp,
+        end_date -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    email_verifications (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        #[max_length = 128]
+        email_address -> Varchar,
+        #[max_length = 64]
+        code_hash -> Varchar,
+        expires_on -> Timestamp,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::HrGroup;
+    use super::sql_types::ClassificationType;
+
+    employee_datas (id) {
+        id -> Uuid,
+        person_id -> Uuid,
+        #[max_length = 256]
+        title_en -> Varchar,
+        #[max_length = 256]
+        title_fr -> Varchar,
+        hr_group -> HrGroup,
+        hr_level -> Int4,
+        classification -> ClassificationType,
+        start_datestamp -> Timestamp,
+        end_date -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::HrState;
+
+    hr_state_periods (id) {
+        id -> Uuid,
+        person_id -> Uuid,
+        hr_state -> HrState,
+        start_datestamp -> Timestamp,
+        end_date -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
     }
 }
 
@@ -138,26 +318,23 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    password_reset_tokens (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        #[max_length = 64]
+        token_hash -> Varchar,
+        expires_on -> Timestamp,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     persons (id) {
         id -> Uuid,
         user_id -> Uuid,
         family_name -> Varchar,
         given_name -> Varchar,
-        #[max_length = 128]
-        email -> Varchar,
-        #[max_length = 32]
-        phone -> Varchar,
-        #[max_length = 256]
-        work_address -> Varchar,
-        #[max_length = 128]
-        city -> Varchar,
-        #[max_length = 128]
-        province -> Varchar,
-        #[max_length = 16]
-        postal_code -> Varchar,
-        #[max_length = 128]
-        country -> Varchar,
         organization_id -> Uuid,
         peoplesoft_id -> Varchar,
         orcid_id -> Varchar,
@@ -203,6 +380,20 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    refresh_tokens (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        #[max_length = 64]
+        token_hash -> Varchar,
+        family_id -> Uuid,
+        replaced_by -> Nullable<Uuid>,
+        expires_at -> Timestamp,
+        revoked_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use super::sql_types::SkillDomain;
@@ -224,6 +415,17 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    revoked_tokens (id) {
+        id -> Uuid,
+        jti -> Nullable<Uuid>,
+        user_id -> Uuid,
+        issued_before -> Nullable<Timestamp>,
+        expires_at -> Timestamp,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use super::sql_types::HrGroup;
@@ -327,6 +529,9 @@ diesel::table! {
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::Granularity;
+
     users (id) {
         id -> Uuid,
         #[max_length = 255]
@@ -344,6 +549,11 @@ diesel::table! {
         #[max_length = 256]
         access_key -> Varchar,
         approved_by_user_uid -> Nullable<Uuid>,
+        email_verified_at -> Nullable<Timestamp>,
+        #[max_length = 256]
+        external_id -> Nullable<Varchar>,
+        approved_at -> Nullable<Timestamp>,
+        granularity -> Granularity,
     }
 }
 
@@ -391,21 +601,33 @@ diesel::table! {
     }
 }
 
+diesel::joinable!(access_logs -> api_keys (api_key_id));
+diesel::joinable!(access_logs -> users (user_id));
 diesel::joinable!(affiliations -> persons (person_id));
 diesel::joinable!(capabilities -> organizations (organization_id));
 diesel::joinable!(capabilities -> persons (person_id));
 diesel::joinable!(capabilities -> skills (skill_id));
+diesel::joinable!(change_records -> users (actor_id));
+diesel::joinable!(contact_datas -> persons (person_id));
+diesel::joinable!(deployments -> persons (person_id));
+diesel::joinable!(deployments -> roles (role_id));
+diesel::joinable!(email_verifications -> users (user_id));
+diesel::joinable!(employee_datas -> persons (person_id));
+diesel::joinable!(hr_state_periods -> persons (person_id));
 diesel::joinable!(language_datas -> persons (person_id));
 diesel::joinable!(org_tier_ownerships -> org_tiers (org_tier_id));
 diesel::joinable!(org_tier_ownerships -> persons (owner_id));
 diesel::joinable!(org_tiers -> organizations (organization_id));
 diesel::joinable!(persons -> organizations (organization_id));
+diesel::joinable!(password_reset_tokens -> users (user_id));
 diesel::joinable!(publication_contributors -> persons (contributor_id));
 diesel::joinable!(publication_contributors -> publications (publication_id));
 diesel::joinable!(publications -> organizations (publishing_organization_id));
 diesel::joinable!(publications -> persons (lead_author_id));
+diesel::joinable!(refresh_tokens -> users (user_id));
 diesel::joinable!(requirements -> roles (role_id));
 diesel::joinable!(requirements -> skills (skill_id));
+diesel::joinable!(revoked_tokens -> users (user_id));
 diesel::joinable!(roles -> persons (person_id));
 diesel::joinable!(roles -> teams (team_id));
 diesel::joinable!(tasks -> roles (created_by_role_id));
@@ -420,16 +642,27 @@ diesel::joinable!(works -> roles (role_id));
 diesel::joinable!(works -> tasks (task_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    access_logs,
     affiliations,
+    api_keys,
     capabilities,
+    change_records,
+    contact_datas,
+    deployments,
+    email_verifications,
+    employee_datas,
+    hr_state_periods,
     language_datas,
     org_tier_ownerships,
     org_tiers,
     organizations,
+    password_reset_tokens,
     persons,
     publication_contributors,
     publications,
+    refresh_tokens,
     requirements,
+    revoked_tokens,
     roles,
     skills,
     tasks,